@@ -1,7 +1,9 @@
-use mongodb::bson::{doc, Bson};
-use mongodb::error::Error;
-use mongodb::options::{FindOneAndReplaceOptions, FindOneOptions, ReadConcern, WriteConcern};
-use mongodb::Database;
+use mongodb::bson::{doc, Bson, Document};
+use mongodb::error::{Error, ErrorKind, WriteFailure, TRANSIENT_TRANSACTION_ERROR};
+use mongodb::options::{
+    DeleteOptions, FindOneAndReplaceOptions, FindOneOptions, ReadConcern, WriteConcern,
+};
+use mongodb::{ClientSession, Database};
 use mongodm::{sync_indexes, CollectionConfig, Index, IndexOption, Model, ToRepository};
 
 /// Trait that is
@@ -51,6 +53,98 @@ where
     }
 }
 
+/// A compare-and-swap variant of upsert, which replaces an existing record only if it also
+/// matches the given condition (e.g. on a version field). If no record with the same id exists,
+/// then the record is inserted.
+///
+/// Returns false, without modifying anything, if a record with the same id exists but does not
+/// match the condition. This relies upon the unique index on the id field (see Record::id_index).
+pub async fn upsert_if<R>(
+    database: &Database,
+    record: &R,
+    condition: Document,
+) -> Result<bool, Error>
+where
+    R: Record,
+{
+    let mut filter = condition;
+    filter.insert(R::id_field(), R::id(record).into());
+    let options = FindOneAndReplaceOptions::builder()
+        .upsert(Some(true))
+        .write_concern(Some(WriteConcern::MAJORITY))
+        .build();
+    match database
+        .repository::<R>()
+        .find_one_and_replace(filter, record, options)
+        .await
+    {
+        Ok(_) => Ok(true),
+        // the existing record did not match the condition, so an insert was attempted instead
+        Err(err) if is_duplicate_key(&err) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// upsert, as part of the transaction of the session, whose write concern applies in place of that
+/// of the write
+pub async fn upsert_with_session<R>(
+    database: &Database,
+    record: &R,
+    session: &mut ClientSession,
+) -> Result<(), Error>
+where
+    R: Record,
+{
+    let filter = doc! { R::id_field() : R::id(record).into() };
+    let options = FindOneAndReplaceOptions::builder()
+        .upsert(Some(true))
+        .build();
+    database
+        .repository::<R>()
+        .find_one_and_replace_with_session(filter, record, options, session)
+        .await?;
+    Ok(())
+}
+
+/// upsert_if, as part of the transaction of the session, whose write concern applies in place of
+/// that of the write. A write that conflicts with that of a concurrent transaction is also taken
+/// to not match the condition.
+pub async fn upsert_if_with_session<R>(
+    database: &Database,
+    record: &R,
+    condition: Document,
+    session: &mut ClientSession,
+) -> Result<bool, Error>
+where
+    R: Record,
+{
+    let mut filter = condition;
+    filter.insert(R::id_field(), R::id(record).into());
+    let options = FindOneAndReplaceOptions::builder()
+        .upsert(Some(true))
+        .build();
+    match database
+        .repository::<R>()
+        .find_one_and_replace_with_session(filter, record, options, session)
+        .await
+    {
+        Ok(_) => Ok(true),
+        Err(err) if is_duplicate_key(&err) || err.contains_label(TRANSIENT_TRANSACTION_ERROR) => {
+            Ok(false)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+fn is_duplicate_key(error: &Error) -> bool {
+    const DUPLICATE_KEY_CODE: i32 = 11000;
+    match error.kind.as_ref() {
+        ErrorKind::Command(error) => error.code == DUPLICATE_KEY_CODE,
+        ErrorKind::Write(WriteFailure::WriteError(error)) => error.code == DUPLICATE_KEY_CODE,
+        _ => false,
+    }
+}
+
 pub async fn find_by_id<R>(database: &Database, id: R::IdType) -> Result<Option<R>, Error>
 where
     R: Record + Send + Sync,
@@ -64,3 +158,19 @@ where
         .find_one(filter, Some(options))
         .await
 }
+
+/// Deletes the record with the given id, returning whether a record was deleted
+pub async fn delete_by_id<R>(database: &Database, id: R::IdType) -> Result<bool, Error>
+where
+    R: Record,
+{
+    let filter = doc! { R::id_field() : id.into() };
+    let options = DeleteOptions::builder()
+        .write_concern(Some(WriteConcern::MAJORITY))
+        .build();
+    let result = database
+        .repository::<R>()
+        .delete_one(filter, options)
+        .await?;
+    Ok(result.deleted_count > 0)
+}
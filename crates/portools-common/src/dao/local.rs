@@ -1,23 +1,46 @@
-use crate::dao::Dao;
-use crate::model::{AssetClass, Portfolio, PortfolioSummary};
+use crate::dao::{get_metadata, Dao};
+use crate::model::{
+    AcquisitionYear, AssetClass, CorporateAction, FxRate, HoldingPeriod, Lot, LotQuery, Page,
+    Portfolio, PortfolioMetadata, PortfolioSummary, PortfolioTransactions, PortfolioVersion,
+};
 use async_trait::async_trait;
+use chrono::{NaiveDate, Utc};
 use mongodb::error::Error;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Mutex;
 
 #[derive(Default)]
 pub struct InMemoryDao {
     portfolios: Mutex<HashMap<u32, Portfolio>>,
+    portfolio_to_versions: Mutex<HashMap<u32, BTreeMap<u32, PortfolioVersion>>>,
     portfolio_to_summary_by_asset_class: Mutex<HashMap<u32, PortfolioSummary<AssetClass>>>,
     portfolio_to_summary_by_symbol: Mutex<HashMap<u32, PortfolioSummary<String>>>,
+    portfolio_to_summary_by_holding_period: Mutex<HashMap<u32, PortfolioSummary<HoldingPeriod>>>,
+    portfolio_to_summary_by_acquisition_year:
+        Mutex<HashMap<u32, PortfolioSummary<AcquisitionYear>>>,
+    fx_rates: Mutex<BTreeMap<(String, String, NaiveDate), FxRate>>,
+    portfolio_to_transactions: Mutex<HashMap<u32, PortfolioTransactions>>,
+    corporate_actions: Mutex<BTreeMap<(NaiveDate, String), CorporateAction>>,
 }
 
 #[async_trait]
 impl Dao for InMemoryDao {
-    async fn put_portfolio(&self, portfolio: &Portfolio) -> Result<(), Error> {
+    async fn put_portfolio(
+        &self,
+        portfolio: &Portfolio,
+        expected_version: u32,
+    ) -> Result<bool, Error> {
         let mut l = self.portfolios.lock().unwrap();
-        l.insert(portfolio.id, portfolio.clone());
-        Ok(())
+        let current_version = l.get(&portfolio.id).map_or(0, |p| p.version);
+        if current_version != expected_version {
+            return Ok(false);
+        }
+        let portfolio = Portfolio {
+            last_updated: Some(Utc::now()),
+            ..portfolio.clone()
+        };
+        l.insert(portfolio.id, portfolio);
+        Ok(true)
     }
 
     async fn get_portfolio(&self, id: u32) -> Result<Option<Portfolio>, Error> {
@@ -25,6 +48,129 @@ impl Dao for InMemoryDao {
         Ok(l.get(&id).map(|p| p.to_owned()))
     }
 
+    async fn query_portfolio(&self, id: u32, query: &LotQuery) -> Result<Option<Portfolio>, Error> {
+        let l = self.portfolios.lock().unwrap();
+        Ok(l.get(&id).map(|p| Portfolio {
+            lots: query.apply(&p.lots),
+            ..p.to_owned()
+        }))
+    }
+
+    async fn list_portfolios(&self, page: &Page) -> Result<Vec<PortfolioMetadata>, Error> {
+        let l = self.portfolios.lock().unwrap();
+        let mut portfolios: Vec<&Portfolio> = l.values().collect();
+        portfolios.sort_by_key(|p| p.id);
+        portfolios
+            .into_iter()
+            .skip(page.offset)
+            .take(page.limit.unwrap_or(usize::MAX))
+            .map(get_metadata)
+            .collect()
+    }
+
+    async fn delete_portfolio(&self, id: u32) -> Result<bool, Error> {
+        let mut l = self.portfolios.lock().unwrap();
+        self.portfolio_to_versions.lock().unwrap().remove(&id);
+        self.portfolio_to_transactions.lock().unwrap().remove(&id);
+        Ok(l.remove(&id).is_some())
+    }
+
+    async fn put_portfolio_version(&self, version: &PortfolioVersion) -> Result<(), Error> {
+        let mut l = self.portfolio_to_versions.lock().unwrap();
+        l.entry(version.portfolio_id)
+            .or_default()
+            .insert(version.version, version.clone());
+        Ok(())
+    }
+
+    async fn get_portfolio_version(
+        &self,
+        portfolio_id: u32,
+        version: u32,
+    ) -> Result<Option<PortfolioVersion>, Error> {
+        let l = self.portfolio_to_versions.lock().unwrap();
+        Ok(l.get(&portfolio_id)
+            .and_then(|versions| versions.get(&version))
+            .cloned())
+    }
+
+    async fn list_portfolio_versions(
+        &self,
+        portfolio_id: u32,
+        page: &Page,
+    ) -> Result<Vec<PortfolioVersion>, Error> {
+        let l = self.portfolio_to_versions.lock().unwrap();
+        Ok(l.get(&portfolio_id)
+            .map(|versions| {
+                versions
+                    .values()
+                    .rev()
+                    .skip(page.offset)
+                    .take(page.limit.unwrap_or(usize::MAX))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    async fn add_lots(
+        &self,
+        portfolio_id: u32,
+        lots: &[Lot],
+        expected_version: Option<u32>,
+    ) -> Result<Option<Portfolio>, Error> {
+        let mut l = self.portfolios.lock().unwrap();
+        Ok(
+            get_expected(&mut l, portfolio_id, expected_version).map(|portfolio| {
+                portfolio.add_lots(lots.to_vec());
+                modified(portfolio)
+            }),
+        )
+    }
+
+    async fn update_lot(
+        &self,
+        portfolio_id: u32,
+        lot: &Lot,
+        expected_version: Option<u32>,
+    ) -> Result<Option<Portfolio>, Error> {
+        let mut l = self.portfolios.lock().unwrap();
+        Ok(
+            get_expected(&mut l, portfolio_id, expected_version).and_then(|portfolio| {
+                portfolio
+                    .update_lot(lot.clone())
+                    .then(|| modified(portfolio))
+            }),
+        )
+    }
+
+    async fn delete_lot(
+        &self,
+        portfolio_id: u32,
+        lot_id: u32,
+        expected_version: Option<u32>,
+    ) -> Result<Option<Portfolio>, Error> {
+        let mut l = self.portfolios.lock().unwrap();
+        Ok(get_expected(&mut l, portfolio_id, expected_version)
+            .and_then(|portfolio| portfolio.remove_lot(lot_id).map(|_| modified(portfolio))))
+    }
+
+    async fn get_summary_by_asset_class(
+        &self,
+        id: u32,
+    ) -> Result<Option<PortfolioSummary<AssetClass>>, Error> {
+        let l = self.portfolio_to_summary_by_asset_class.lock().unwrap();
+        Ok(l.get(&id).map(|s| s.to_owned()))
+    }
+
+    async fn get_summary_by_symbol(
+        &self,
+        id: u32,
+    ) -> Result<Option<PortfolioSummary<String>>, Error> {
+        let l = self.portfolio_to_summary_by_symbol.lock().unwrap();
+        Ok(l.get(&id).map(|s| s.to_owned()))
+    }
+
     async fn put_summary_by_asset_class(
         &self,
         asset_allocation: &PortfolioSummary<AssetClass>,
@@ -42,4 +188,160 @@ impl Dao for InMemoryDao {
         l.insert(asset_allocation.id, asset_allocation.clone());
         Ok(())
     }
+
+    async fn delete_summary_by_asset_class(&self, id: u32) -> Result<bool, Error> {
+        let mut l = self.portfolio_to_summary_by_asset_class.lock().unwrap();
+        Ok(l.remove(&id).is_some())
+    }
+
+    async fn delete_summary_by_symbol(&self, id: u32) -> Result<bool, Error> {
+        let mut l = self.portfolio_to_summary_by_symbol.lock().unwrap();
+        Ok(l.remove(&id).is_some())
+    }
+
+    async fn get_summary_by_holding_period(
+        &self,
+        id: u32,
+    ) -> Result<Option<PortfolioSummary<HoldingPeriod>>, Error> {
+        let l = self.portfolio_to_summary_by_holding_period.lock().unwrap();
+        Ok(l.get(&id).cloned())
+    }
+
+    async fn get_summary_by_acquisition_year(
+        &self,
+        id: u32,
+    ) -> Result<Option<PortfolioSummary<AcquisitionYear>>, Error> {
+        let l = self
+            .portfolio_to_summary_by_acquisition_year
+            .lock()
+            .unwrap();
+        Ok(l.get(&id).cloned())
+    }
+
+    async fn put_summary_by_holding_period(
+        &self,
+        summary: &PortfolioSummary<HoldingPeriod>,
+    ) -> Result<(), Error> {
+        let mut l = self.portfolio_to_summary_by_holding_period.lock().unwrap();
+        l.insert(summary.id, summary.clone());
+        Ok(())
+    }
+
+    async fn put_summary_by_acquisition_year(
+        &self,
+        summary: &PortfolioSummary<AcquisitionYear>,
+    ) -> Result<(), Error> {
+        let mut l = self
+            .portfolio_to_summary_by_acquisition_year
+            .lock()
+            .unwrap();
+        l.insert(summary.id, summary.clone());
+        Ok(())
+    }
+
+    async fn delete_summary_by_holding_period(&self, id: u32) -> Result<bool, Error> {
+        let mut l = self.portfolio_to_summary_by_holding_period.lock().unwrap();
+        Ok(l.remove(&id).is_some())
+    }
+
+    async fn delete_summary_by_acquisition_year(&self, id: u32) -> Result<bool, Error> {
+        let mut l = self
+            .portfolio_to_summary_by_acquisition_year
+            .lock()
+            .unwrap();
+        Ok(l.remove(&id).is_some())
+    }
+
+    async fn put_fx_rates(&self, rates: &[FxRate]) -> Result<(), Error> {
+        let mut l = self.fx_rates.lock().unwrap();
+        for rate in rates {
+            l.insert(
+                (rate.from.clone(), rate.to.clone(), rate.date),
+                rate.clone(),
+            );
+        }
+        Ok(())
+    }
+
+    async fn get_fx_rates(&self) -> Result<Vec<FxRate>, Error> {
+        let l = self.fx_rates.lock().unwrap();
+        Ok(l.values().cloned().collect())
+    }
+
+    async fn get_fx_rates_to(&self, currencies: &[String], to: &str) -> Result<Vec<FxRate>, Error> {
+        let l = self.fx_rates.lock().unwrap();
+        Ok(l.values()
+            .filter(|rate| {
+                (rate.to == to && currencies.contains(&rate.from))
+                    || (rate.from == to && currencies.contains(&rate.to))
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn put_portfolio_and_transactions(
+        &self,
+        portfolio: &Portfolio,
+        transactions: &PortfolioTransactions,
+        expected_version: u32,
+    ) -> Result<bool, Error> {
+        // held until the transactions are stored, so that both are stored as one
+        let mut l = self.portfolios.lock().unwrap();
+        let current_version = l.get(&portfolio.id).map_or(0, |p| p.version);
+        if current_version != expected_version {
+            return Ok(false);
+        }
+        let portfolio = Portfolio {
+            last_updated: Some(Utc::now()),
+            ..portfolio.clone()
+        };
+        l.insert(portfolio.id, portfolio);
+        self.portfolio_to_transactions
+            .lock()
+            .unwrap()
+            .insert(transactions.id, transactions.clone());
+        Ok(true)
+    }
+
+    async fn get_transactions(
+        &self,
+        portfolio_id: u32,
+    ) -> Result<Option<PortfolioTransactions>, Error> {
+        let l = self.portfolio_to_transactions.lock().unwrap();
+        Ok(l.get(&portfolio_id).cloned())
+    }
+
+    async fn put_corporate_actions(&self, actions: &[CorporateAction]) -> Result<(), Error> {
+        let mut l = self.corporate_actions.lock().unwrap();
+        for action in actions {
+            l.insert(
+                (action.effective_date, action.symbol.clone()),
+                action.clone(),
+            );
+        }
+        Ok(())
+    }
+
+    async fn get_corporate_actions(&self) -> Result<Vec<CorporateAction>, Error> {
+        let l = self.corporate_actions.lock().unwrap();
+        Ok(l.values().cloned().collect())
+    }
+}
+
+// the portfolio with the given id, provided that it is of the expected version, if any
+fn get_expected(
+    portfolios: &mut HashMap<u32, Portfolio>,
+    portfolio_id: u32,
+    expected_version: Option<u32>,
+) -> Option<&mut Portfolio> {
+    portfolios
+        .get_mut(&portfolio_id)
+        .filter(|portfolio| expected_version.unwrap_or(portfolio.version) == portfolio.version)
+}
+
+// records that the portfolio was modified, returning a copy of it
+fn modified(portfolio: &mut Portfolio) -> Portfolio {
+    portfolio.version += 1;
+    portfolio.last_updated = Some(Utc::now());
+    portfolio.clone()
 }
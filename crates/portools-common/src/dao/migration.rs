@@ -0,0 +1,334 @@
+use crate::dao::mongo::{
+    FxRateConfig, PortfolioConfig, PortfolioSummaryByAssetClassConfig,
+    PortfolioSummaryBySymbolConfig, PortfolioVersionConfig, DB_NAME, PORTFOLIO_KEY_FIELD,
+};
+use crate::model::{GroupSummary, Lot, Portfolio, PortfolioSummary};
+use mongo_util::record;
+use mongo_util::record::Record;
+use mongodb::bson::{doc, Bson, Document};
+use mongodb::error::Error;
+use mongodb::options::{ReplaceOptions, WriteConcern};
+use mongodb::{Client, Database};
+use mongodm::{field, sync_indexes, CollectionConfig, Indexes, Model, ToRepository};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+// the id of the single record of the schema version
+const SCHEMA_VERSION_ID: &str = "portools";
+
+/// The version of the schema of the stored documents, which is the number of migrations that have
+/// been applied to them
+#[derive(Debug, Serialize, Deserialize)]
+struct SchemaVersion {
+    id: String,
+    version: u32,
+}
+
+/// Applies, in order, each migration that has not yet been applied to the stored documents, and
+/// records the resulting schema version, so that a migration runs only once.
+pub async fn migrate(client: &Client) -> Result<(), Error> {
+    let db = client.database(DB_NAME);
+    sync_indexes::<SchemaVersionConfig>(&db).await?;
+    let version = record::find_by_id::<SchemaVersion>(&db, SCHEMA_VERSION_ID.into())
+        .await?
+        .map_or(0, |schema| schema.version);
+    tracing::info!(version, "found schema version");
+    if version < 1 {
+        migrate_decimals_to_strings(client).await?;
+        put_schema_version(&db, 1).await?;
+    }
+    if version < 2 {
+        key_portfolios_by_id(client).await?;
+        put_schema_version(&db, 2).await?;
+    }
+    if version < 3 {
+        assign_lot_ids(&db).await?;
+        put_schema_version(&db, 3).await?;
+    }
+    if version < 4 {
+        migrate_costs_to_currencies(client).await?;
+        put_schema_version(&db, 4).await?;
+    }
+    Ok(())
+}
+
+async fn put_schema_version(db: &Database, version: u32) -> Result<(), Error> {
+    let schema = SchemaVersion {
+        id: SCHEMA_VERSION_ID.into(),
+        version,
+    };
+    record::upsert(db, &schema).await?;
+    tracing::info!(version, "migrated to schema version");
+    Ok(())
+}
+
+/// Rewrites the decimals of the stored documents as strings, where they were stored as doubles
+/// (i.e. while the serde-float feature of rust_decimal was enabled). Each double is rewritten as
+/// the decimal that it was read as, so the values that are read are unchanged. Documents without
+/// any doubles are left as they are, so the migration may be run any number of times.
+async fn migrate_decimals_to_strings(client: &Client) -> Result<(), Error> {
+    let db = client.database(DB_NAME);
+    let migrate = doubles_to_strings;
+    migrate_collection::<PortfolioConfig>(&db, migrate).await?;
+    migrate_collection::<PortfolioVersionConfig>(&db, migrate).await?;
+    migrate_collection::<PortfolioSummaryByAssetClassConfig>(&db, migrate).await?;
+    migrate_collection::<PortfolioSummaryBySymbolConfig>(&db, migrate).await?;
+    migrate_collection::<FxRateConfig>(&db, migrate).await
+}
+
+/// Rewrites the cost of each group of the stored summaries as a list of costs per currency, where
+/// it was stored as a single cost (i.e. before lots could be acquired in different currencies).
+async fn migrate_costs_to_currencies(client: &Client) -> Result<(), Error> {
+    let db = client.database(DB_NAME);
+    let migrate = cost_to_currencies;
+    migrate_collection::<PortfolioSummaryByAssetClassConfig>(&db, migrate).await?;
+    migrate_collection::<PortfolioSummaryBySymbolConfig>(&db, migrate).await
+}
+
+// Rewrites each document of the collection that the given function modifies, which returns
+// whether it modified the document
+async fn migrate_collection<C: CollectionConfig>(
+    db: &Database,
+    migrate: fn(&mut Document) -> bool,
+) -> Result<(), Error> {
+    let collection = db.collection::<Document>(C::collection_name());
+    let options = ReplaceOptions::builder()
+        .write_concern(Some(WriteConcern::MAJORITY))
+        .build();
+    let mut cursor = collection.find(None, None).await?;
+    let mut num_migrated = 0;
+    while cursor.advance().await? {
+        let mut document: Document = cursor.deserialize_current()?;
+        if !migrate(&mut document) {
+            continue;
+        }
+        let id = document.get("_id").cloned().unwrap_or(Bson::Null);
+        collection
+            .replace_one(doc! { "_id": id }, &document, options.clone())
+            .await?;
+        num_migrated += 1;
+    }
+    tracing::info!(
+        collection = C::collection_name(),
+        num_migrated,
+        "migrated collection"
+    );
+    Ok(())
+}
+
+// Re-keys each portfolio document that was stored with a generated key, so that it is keyed by the
+// id of the portfolio (see MongoDao::put_portfolio). As the key of a document cannot be modified,
+// each document is deleted and inserted under its new key within a transaction.
+async fn key_portfolios_by_id(client: &Client) -> Result<(), Error> {
+    let collection = client
+        .database(DB_NAME)
+        .collection::<Document>(PortfolioConfig::collection_name());
+    let filter = doc! {
+        "$expr": { "$ne": [format!("${PORTFOLIO_KEY_FIELD}"), format!("${}", Portfolio::id_field())] }
+    };
+    let mut cursor = collection.find(filter, None).await?;
+    let mut session = client.start_session(None).await?;
+    let mut num_migrated = 0;
+    while cursor.advance().await? {
+        let mut document: Document = cursor.deserialize_current()?;
+        let key = document.remove(PORTFOLIO_KEY_FIELD).unwrap_or(Bson::Null);
+        let id = document
+            .get(Portfolio::id_field())
+            .cloned()
+            .unwrap_or(Bson::Null);
+        document.insert(PORTFOLIO_KEY_FIELD, id);
+        session.start_transaction(None).await?;
+        collection
+            .delete_one_with_session(doc! { PORTFOLIO_KEY_FIELD: key }, None, &mut session)
+            .await?;
+        collection
+            .insert_one_with_session(&document, None, &mut session)
+            .await?;
+        session.commit_transaction().await?;
+        num_migrated += 1;
+    }
+    tracing::info!(num_migrated, "keyed portfolios by id");
+    Ok(())
+}
+
+// Assigns ids to the lots of each portfolio that were stored before lots were assigned ids, so
+// that they may be updated or removed individually
+async fn assign_lot_ids(db: &Database) -> Result<(), Error> {
+    let repository = db.repository::<Portfolio>();
+    let options = ReplaceOptions::builder()
+        .write_concern(Some(WriteConcern::MAJORITY))
+        .build();
+    // matches a lot whose id is either missing or null
+    let filter = doc! {
+        field!(lots in Portfolio): { "$elemMatch": { field!(id in Lot): Bson::Null } }
+    };
+    let mut cursor = repository.find(filter, None).await?;
+    let mut num_migrated = 0;
+    while cursor.advance().await? {
+        let mut portfolio: Portfolio = cursor.deserialize_current()?;
+        if !portfolio.assign_lot_ids() {
+            continue;
+        }
+        repository
+            .replace_one(
+                doc! { Portfolio::id_field(): portfolio.id },
+                &portfolio,
+                options.clone(),
+            )
+            .await?;
+        num_migrated += 1;
+    }
+    tracing::info!(num_migrated, "assigned ids to lots");
+    Ok(())
+}
+
+// Replaces the cost of each group of a summary with a list of it, unless it is already a list.
+// Returns whether any were replaced.
+fn cost_to_currencies(document: &mut Document) -> bool {
+    let groups =
+        match document.get_document_mut(field!(group_to_summary in PortfolioSummary<String>)) {
+            Ok(groups) => groups,
+            Err(_) => return false,
+        };
+    let mut replaced = false;
+    for (_, group) in groups.iter_mut() {
+        let Bson::Document(group) = group else {
+            continue;
+        };
+        let cost_field = field!(cost in GroupSummary);
+        if let Some(cost @ Bson::Document(_)) = group.get(cost_field) {
+            let cost = Bson::Array(vec![cost.clone()]);
+            group.insert(cost_field, cost);
+            replaced = true;
+        }
+    }
+    replaced
+}
+
+// Replaces every double within the document, however deeply nested, with the string of the
+// decimal that it is deserialized as. Returns whether any were replaced.
+fn doubles_to_strings(document: &mut Document) -> bool {
+    let mut replaced = false;
+    for (_, value) in document.iter_mut() {
+        replaced |= double_to_string(value);
+    }
+    replaced
+}
+
+fn double_to_string(value: &mut Bson) -> bool {
+    match value {
+        // the same conversion as rust_decimal applies when deserializing a double
+        Bson::Double(double) => match Decimal::from_str(&double.to_string()) {
+            Ok(decimal) => {
+                *value = Bson::String(decimal.to_string());
+                true
+            }
+            Err(error) => {
+                tracing::error!(?error, double, "failed to migrate double to decimal");
+                false
+            }
+        },
+        Bson::Document(document) => doubles_to_strings(document),
+        Bson::Array(values) => values
+            .iter_mut()
+            .fold(false, |replaced, value| double_to_string(value) | replaced),
+        _ => false,
+    }
+}
+
+// ODM for Schema Version
+
+struct SchemaVersionConfig;
+
+impl CollectionConfig for SchemaVersionConfig {
+    fn collection_name() -> &'static str {
+        "schema_version"
+    }
+
+    fn indexes() -> Indexes {
+        Indexes::new().with(SchemaVersion::id_index())
+    }
+}
+
+impl Model for SchemaVersion {
+    type CollConf = SchemaVersionConfig;
+}
+
+impl Record for SchemaVersion {
+    type IdType = String;
+
+    fn id_field() -> &'static str {
+        field!(id in SchemaVersion)
+    }
+
+    fn id(&self) -> String {
+        self.id.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dao::migration::{cost_to_currencies, doubles_to_strings};
+    use crate::model::{AssetClass, Currency, GroupSummary, Portfolio, PortfolioSummary};
+    use crate::unit_test_util::fixture;
+    use mongodb::bson::{doc, from_document, to_document};
+
+    #[test]
+    fn doubles_to_strings_without_loss() {
+        let mut document = doc! {
+            "id": 1,
+            "lots": [{
+                "id": 1,
+                "account": "Taxable",
+                "symbol": "VOO",
+                "date_acquired": "2023-03-27",
+                "quantity": 6.0,
+                "cost_basis": { "amount": 300.64, "symbol": "USD" },
+            }],
+            "next_lot_id": 2,
+        };
+        assert!(doubles_to_strings(&mut document));
+        assert_eq!(
+            doc! { "amount": "300.64", "symbol": "USD" },
+            document.get_array("lots").unwrap()[0]
+                .as_document()
+                .unwrap()
+                .get_document("cost_basis")
+                .unwrap()
+                .clone()
+        );
+        let portfolio: Portfolio = from_document(document.clone()).unwrap();
+        assert_eq!(Portfolio::new(1, vec![fixture::lot()]), portfolio);
+
+        // already migrated
+        assert!(!doubles_to_strings(&mut document));
+        let mut document = to_document(&portfolio).unwrap();
+        assert!(!doubles_to_strings(&mut document));
+    }
+
+    #[test]
+    fn cost_to_currencies_wraps_single_cost() {
+        let mut document = doc! {
+            "id": 1,
+            "group_to_summary": {
+                "UsStocks": { "cost": { "amount": "300.64", "symbol": "USD" } },
+                "UsBonds": { "cost": [{ "amount": "100.00", "symbol": "USD" }] },
+            },
+        };
+        assert!(cost_to_currencies(&mut document));
+        let summary: PortfolioSummary<AssetClass> = from_document(document.clone()).unwrap();
+        let usd = |amount: &str| Currency::new(amount.parse().unwrap(), "USD").unwrap();
+        assert_eq!(
+            &GroupSummary::new(vec![usd("300.64")]).unwrap(),
+            summary.group_to_summary.get(&AssetClass::UsStocks).unwrap()
+        );
+        assert_eq!(
+            &GroupSummary::new(vec![usd("100.00")]).unwrap(),
+            summary.group_to_summary.get(&AssetClass::UsBonds).unwrap()
+        );
+
+        // already migrated
+        assert!(!cost_to_currencies(&mut document));
+    }
+}
@@ -1,16 +1,103 @@
-use crate::model::{AssetClass, Portfolio, PortfolioSummary};
+use crate::model::{
+    AcquisitionYear, AssetClass, CorporateAction, FxRate, HoldingPeriod, Lot, LotQuery, Page,
+    Portfolio, PortfolioMetadata, PortfolioSummary, PortfolioTransactions, PortfolioVersion,
+};
 use async_trait::async_trait;
 use mongodb::error::Error;
 
 pub mod local;
+pub mod migration;
 pub mod mongo;
 
 #[async_trait]
 pub trait Dao: Send + Sync {
-    async fn put_portfolio(&self, portfolio: &Portfolio) -> Result<(), Error>;
+    /// Stores the portfolio, but only if the stored portfolio is of the expected version, where 0
+    /// means that no versioned portfolio may be stored. Returns whether the portfolio was stored.
+    ///
+    /// The version of the portfolio should be incremented by the caller, whereas the current
+    /// time is recorded as when it was last updated. Every other operation that modifies a
+    /// portfolio both increments its version and records the time.
+    async fn put_portfolio(
+        &self,
+        portfolio: &Portfolio,
+        expected_version: u32,
+    ) -> Result<bool, Error>;
 
     async fn get_portfolio(&self, id: u32) -> Result<Option<Portfolio>, Error>;
 
+    /// Returns the portfolio, with only the lots that match the query, in the order and page
+    /// that the query specifies
+    async fn query_portfolio(&self, id: u32, query: &LotQuery) -> Result<Option<Portfolio>, Error>;
+
+    /// Returns the metadata of the portfolios within the page, ordered by their ids
+    async fn list_portfolios(&self, page: &Page) -> Result<Vec<PortfolioMetadata>, Error>;
+
+    /// Deletes the portfolio along with its versions and transactions. Returns whether the
+    /// portfolio existed
+    async fn delete_portfolio(&self, id: u32) -> Result<bool, Error>;
+
+    /// Stores the version, replacing any that has the same portfolio id and version
+    async fn put_portfolio_version(&self, version: &PortfolioVersion) -> Result<(), Error>;
+
+    async fn get_portfolio_version(
+        &self,
+        portfolio_id: u32,
+        version: u32,
+    ) -> Result<Option<PortfolioVersion>, Error>;
+
+    /// Returns the versions of the portfolio within the page, the most recent first
+    async fn list_portfolio_versions(
+        &self,
+        portfolio_id: u32,
+        page: &Page,
+    ) -> Result<Vec<PortfolioVersion>, Error>;
+
+    /// Appends the lots to the portfolio, assigning each of them a new id. If an expected version
+    /// is given, the portfolio is modified only if it is of that version.
+    ///
+    /// Returns the updated portfolio, or None if the portfolio does not exist or is not of the
+    /// expected version
+    async fn add_lots(
+        &self,
+        portfolio_id: u32,
+        lots: &[Lot],
+        expected_version: Option<u32>,
+    ) -> Result<Option<Portfolio>, Error>;
+
+    /// Replaces the lot within the portfolio that has the same id as the given lot. If an expected
+    /// version is given, the portfolio is modified only if it is of that version.
+    ///
+    /// Returns the updated portfolio, or None if either the portfolio or the lot does not exist,
+    /// or the portfolio is not of the expected version
+    async fn update_lot(
+        &self,
+        portfolio_id: u32,
+        lot: &Lot,
+        expected_version: Option<u32>,
+    ) -> Result<Option<Portfolio>, Error>;
+
+    /// Removes the lot with the given id from the portfolio. If an expected version is given, the
+    /// portfolio is modified only if it is of that version.
+    ///
+    /// Returns the updated portfolio, or None if either the portfolio or the lot does not exist,
+    /// or the portfolio is not of the expected version
+    async fn delete_lot(
+        &self,
+        portfolio_id: u32,
+        lot_id: u32,
+        expected_version: Option<u32>,
+    ) -> Result<Option<Portfolio>, Error>;
+
+    async fn get_summary_by_asset_class(
+        &self,
+        id: u32,
+    ) -> Result<Option<PortfolioSummary<AssetClass>>, Error>;
+
+    async fn get_summary_by_symbol(
+        &self,
+        id: u32,
+    ) -> Result<Option<PortfolioSummary<String>>, Error>;
+
     async fn put_summary_by_asset_class(
         &self,
         asset_allocation: &PortfolioSummary<AssetClass>,
@@ -20,4 +107,80 @@ pub trait Dao: Send + Sync {
         &self,
         asset_allocation: &PortfolioSummary<String>,
     ) -> Result<(), Error>;
+
+    async fn delete_summary_by_asset_class(&self, id: u32) -> Result<bool, Error>;
+
+    async fn delete_summary_by_symbol(&self, id: u32) -> Result<bool, Error>;
+
+    async fn get_summary_by_holding_period(
+        &self,
+        id: u32,
+    ) -> Result<Option<PortfolioSummary<HoldingPeriod>>, Error>;
+
+    async fn get_summary_by_acquisition_year(
+        &self,
+        id: u32,
+    ) -> Result<Option<PortfolioSummary<AcquisitionYear>>, Error>;
+
+    async fn put_summary_by_holding_period(
+        &self,
+        summary: &PortfolioSummary<HoldingPeriod>,
+    ) -> Result<(), Error>;
+
+    async fn put_summary_by_acquisition_year(
+        &self,
+        summary: &PortfolioSummary<AcquisitionYear>,
+    ) -> Result<(), Error>;
+
+    async fn delete_summary_by_holding_period(&self, id: u32) -> Result<bool, Error>;
+
+    async fn delete_summary_by_acquisition_year(&self, id: u32) -> Result<bool, Error>;
+
+    /// Stores the FX rates, replacing any that have the same currency pair and date
+    async fn put_fx_rates(&self, rates: &[FxRate]) -> Result<(), Error>;
+
+    /// Returns every FX rate, ordered by currency pair and then by date
+    async fn get_fx_rates(&self) -> Result<Vec<FxRate>, Error>;
+
+    /// Returns the FX rates between any of the given currencies and the `to` currency, in either
+    /// direction, ordered by currency pair and then by date
+    async fn get_fx_rates_to(&self, currencies: &[String], to: &str) -> Result<Vec<FxRate>, Error>;
+
+    /// Stores the portfolio along with the transactions that its lots were derived from, replacing
+    /// any previously stored for it, as a single write. Both are stored only if the stored
+    /// portfolio is of the expected version (per put_portfolio), and neither is stored otherwise.
+    /// Returns whether they were stored.
+    async fn put_portfolio_and_transactions(
+        &self,
+        portfolio: &Portfolio,
+        transactions: &PortfolioTransactions,
+        expected_version: u32,
+    ) -> Result<bool, Error>;
+
+    async fn get_transactions(
+        &self,
+        portfolio_id: u32,
+    ) -> Result<Option<PortfolioTransactions>, Error>;
+
+    /// Stores the corporate actions, replacing any that have the same symbol and effective date
+    async fn put_corporate_actions(&self, actions: &[CorporateAction]) -> Result<(), Error>;
+
+    /// Returns every corporate action, ordered by effective date and then by symbol
+    async fn get_corporate_actions(&self) -> Result<Vec<CorporateAction>, Error>;
+}
+
+// the metadata of the portfolio, where a failure to compute it (i.e. an overflow of its total
+// cost) is reported as a custom error
+pub(crate) fn get_metadata(portfolio: &Portfolio) -> Result<PortfolioMetadata, Error> {
+    portfolio.get_metadata().map_err(|error| {
+        tracing::error!(
+            ?error,
+            portfolio_id = portfolio.id,
+            "failed to get metadata"
+        );
+        Error::custom(format!(
+            "failed to get metadata of portfolio {}",
+            portfolio.id
+        ))
+    })
 }
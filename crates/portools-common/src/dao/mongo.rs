@@ -1,16 +1,29 @@
-use crate::dao::Dao;
-use crate::model::{AssetClass, Portfolio, PortfolioSummary};
+use crate::dao::{get_metadata, Dao};
+use crate::model::{
+    AcquisitionYear, AssetClass, CorporateAction, FxRate, HoldingPeriod, Lot, LotQuery,
+    LotSortField, Page, Portfolio, PortfolioMetadata, PortfolioSummary, PortfolioTransactions,
+    PortfolioVersion, SortOrder,
+};
 use async_trait::async_trait;
+use chrono::Utc;
 use mongo_util::record;
-use mongodb::error::Error;
+use mongodb::bson::{doc, from_document, to_bson, Bson, Document};
+use mongodb::error::{Error, TRANSIENT_TRANSACTION_ERROR};
+use mongodb::options::{
+    DeleteOptions, FindOneAndUpdateOptions, FindOneOptions, FindOptions, ReadConcern,
+    ReplaceOptions, ReturnDocument, TransactionOptions, UpdateModifications, WriteConcern,
+};
 use mongodb::Client;
 
 use mongo_util::record::{drop_and_create, Record};
 use mongodm::field;
-use mongodm::{sync_indexes, CollectionConfig, Indexes, Model};
+use mongodm::{sync_indexes, CollectionConfig, Index, IndexOption, Indexes, Model, ToRepository};
 
 pub const DB_NAME: &str = "portools";
 
+/// The field that keys each portfolio document, whose value is the id of the portfolio
+pub const PORTFOLIO_KEY_FIELD: &str = "_id";
+
 #[derive(Clone, Debug)]
 pub struct MongoDao {
     client: Client,
@@ -20,13 +33,202 @@ impl MongoDao {
     pub fn new(client: Client) -> MongoDao {
         MongoDao { client }
     }
+
+    async fn find_portfolio_and_update(
+        &self,
+        filter: Document,
+        update: impl Into<UpdateModifications>,
+    ) -> Result<Option<Portfolio>, Error> {
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(Some(ReturnDocument::After))
+            .write_concern(Some(WriteConcern::MAJORITY))
+            .build();
+        self.client
+            .database(DB_NAME)
+            .repository::<Portfolio>()
+            .find_one_and_update(filter, update, options)
+            .await
+    }
+
+    // finds the FX rates that match the filter, ordered by currency pair and then by date
+    async fn find_fx_rates(&self, filter: Option<Document>) -> Result<Vec<FxRate>, Error> {
+        let options = FindOptions::builder()
+            .sort(doc! {
+                field!(from in FxRate): 1,
+                field!(to in FxRate): 1,
+                field!(date in FxRate): 1,
+            })
+            .read_concern(Some(ReadConcern::MAJORITY))
+            .build();
+        let mut cursor = self
+            .client
+            .database(DB_NAME)
+            .repository::<FxRate>()
+            .find(filter, options)
+            .await?;
+        let mut rates = Vec::new();
+        while cursor.advance().await? {
+            rates.push(cursor.deserialize_current()?);
+        }
+        Ok(rates)
+    }
+}
+
+// the name of the field identifying a lot within a portfolio document
+fn lot_id_field() -> String {
+    format!("{}.{}", field!(lots in Portfolio), field!(id in Lot))
+}
+
+// selects the portfolio with the given id, provided that it is of the expected version, if any
+fn portfolio_filter(portfolio_id: u32, expected_version: Option<u32>) -> Document {
+    let mut filter = doc! { Portfolio::id_field(): portfolio_id };
+    if let Some(version) = expected_version {
+        filter.insert(field!(version in Portfolio), version);
+    }
+    filter
+}
+
+// the condition on the stored portfolio that putting the portfolio with the given id requires
+fn put_condition(portfolio_id: u32, expected_version: u32) -> Document {
+    let version_field = field!(version in Portfolio);
+    let mut condition = if expected_version == 0 {
+        // portfolios stored prior to versioning have no version
+        doc! { version_field: { "$in": [Bson::Null, 0] } }
+    } else {
+        doc! { version_field: expected_version }
+    };
+    // the document is keyed by the id of the portfolio, which an insert takes from the filter,
+    // so that the id is within the document key of every change stream event for it
+    condition.insert(PORTFOLIO_KEY_FIELD, portfolio_id);
+    condition
+}
+
+fn version_filter(portfolio_id: u32, version: u32) -> Document {
+    doc! {
+        field!(portfolio_id in PortfolioVersion): portfolio_id,
+        field!(version in PortfolioVersion): version,
+    }
+}
+
+// the value of last_updated for a portfolio that is being modified
+fn last_updated() -> Result<Bson, Error> {
+    Ok(to_bson(&Utc::now())?)
+}
+
+// the field of a lot that holds the key that the lots are sorted by, where it is not a field of Lot
+const SORT_KEY_FIELD: &str = "_sort_key";
+
+// Creates a stage of an aggregation pipeline that replaces each lot, as "$$lot", per the expression
+fn map_lots_stage(expression: Document) -> Document {
+    let lots_field = field!(lots in Portfolio);
+    doc! {
+        "$set": {
+            lots_field: {
+                "$map": { "input": format!("${lots_field}"), "as": "lot", "in": expression }
+            }
+        }
+    }
+}
+
+// Creates the stages of an aggregation pipeline that select, order and paginate the lots of the
+// portfolio documents that it is applied to, in the same manner as LotQuery::apply
+fn lot_query_stages(query: &LotQuery) -> Result<Vec<Document>, Error> {
+    let lots_field = field!(lots in Portfolio);
+    let lot_field = |name: &str| format!("$$lot.{name}");
+    let mut conditions = Vec::new();
+    if let Some(account) = &query.account {
+        conditions.push(doc! { "$eq": [lot_field(field!(account in Lot)), account] });
+    }
+    if let Some(symbol) = &query.symbol {
+        conditions.push(doc! { "$eq": [lot_field(field!(symbol in Lot)), symbol] });
+    }
+    // dates are stored as ISO 8601 strings, which compare in chronological order
+    if let Some(date) = &query.acquired_after {
+        conditions.push(doc! { "$gt": [lot_field(field!(date_acquired in Lot)), to_bson(date)?] });
+    }
+    if let Some(date) = &query.acquired_before {
+        conditions.push(doc! { "$lt": [lot_field(field!(date_acquired in Lot)), to_bson(date)?] });
+    }
+
+    let mut stages = Vec::new();
+    if !conditions.is_empty() {
+        stages.push(doc! {
+            "$set": {
+                lots_field: {
+                    "$filter": {
+                        "input": format!("${lots_field}"),
+                        "as": "lot",
+                        "cond": { "$and": conditions },
+                    }
+                }
+            }
+        });
+    }
+    if let Some(sort) = query.sort {
+        let direction = match query.order {
+            SortOrder::Asc => 1,
+            SortOrder::Desc => -1,
+        };
+        let sort_field = match sort {
+            LotSortField::Id => field!(id in Lot),
+            LotSortField::Account => field!(account in Lot),
+            LotSortField::Symbol => field!(symbol in Lot),
+            LotSortField::DateAcquired => field!(date_acquired in Lot),
+            LotSortField::Quantity => SORT_KEY_FIELD,
+        };
+        // decimals are stored as strings, so a numeric sort key is added to each lot for the
+        // duration of the sort
+        if sort == LotSortField::Quantity {
+            let quantity = lot_field(field!(quantity in Lot));
+            stages.push(map_lots_stage(doc! {
+                "$mergeObjects": ["$$lot", { SORT_KEY_FIELD: { "$toDecimal": quantity } }]
+            }));
+        }
+        let mut sort_by = doc! { sort_field: direction };
+        // break ties by id, which is a no-op when sorting by id
+        sort_by.insert(field!(id in Lot), direction);
+        stages.push(doc! {
+            "$set": {
+                lots_field: { "$sortArray": { "input": format!("${lots_field}"), "sortBy": sort_by } }
+            }
+        });
+        if sort == LotSortField::Quantity {
+            stages.push(map_lots_stage(doc! {
+                "$unsetField": { "field": SORT_KEY_FIELD, "input": "$$lot" }
+            }));
+        }
+    }
+    if query.offset > 0 || query.limit.is_some() {
+        let lots = match query.limit {
+            // $slice requires a positive number of elements
+            Some(0) => doc! { "$literal": [] },
+            limit => {
+                let offset = i64::try_from(query.offset).unwrap_or(i64::MAX);
+                let limit = limit
+                    .and_then(|limit| i32::try_from(limit).ok())
+                    .unwrap_or(i32::MAX);
+                doc! { "$slice": [format!("${lots_field}"), offset, limit] }
+            }
+        };
+        stages.push(doc! { "$set": { lots_field: lots } });
+    }
+    Ok(stages)
 }
 
 #[async_trait]
 impl Dao for MongoDao {
-    async fn put_portfolio(&self, portfolio: &Portfolio) -> Result<(), Error> {
+    async fn put_portfolio(
+        &self,
+        portfolio: &Portfolio,
+        expected_version: u32,
+    ) -> Result<bool, Error> {
         let database = self.client.database(DB_NAME);
-        record::upsert(&database, portfolio).await
+        let portfolio = Portfolio {
+            last_updated: Some(Utc::now()),
+            ..portfolio.clone()
+        };
+        let condition = put_condition(portfolio.id, expected_version);
+        record::upsert_if(&database, &portfolio, condition).await
     }
 
     async fn get_portfolio(&self, id: u32) -> Result<Option<Portfolio>, Error> {
@@ -34,6 +236,207 @@ impl Dao for MongoDao {
         record::find_by_id(&database, id).await
     }
 
+    async fn query_portfolio(&self, id: u32, query: &LotQuery) -> Result<Option<Portfolio>, Error> {
+        let mut pipeline = vec![doc! { "$match": { Portfolio::id_field(): id } }];
+        pipeline.extend(lot_query_stages(query)?);
+        let mut cursor = self
+            .client
+            .database(DB_NAME)
+            .repository::<Portfolio>()
+            .aggregate(pipeline, None)
+            .await?;
+        if cursor.advance().await? {
+            Ok(Some(from_document(cursor.deserialize_current()?)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn list_portfolios(&self, page: &Page) -> Result<Vec<PortfolioMetadata>, Error> {
+        let options = FindOptions::builder()
+            .sort(doc! { Portfolio::id_field(): 1 })
+            .skip(u64::try_from(page.offset).ok())
+            .limit(page.limit.and_then(|limit| i64::try_from(limit).ok()))
+            .build();
+        let mut cursor = self
+            .client
+            .database(DB_NAME)
+            .repository::<Portfolio>()
+            .find(None, options)
+            .await?;
+        let mut metadata = Vec::new();
+        while cursor.advance().await? {
+            metadata.push(get_metadata(&cursor.deserialize_current()?)?);
+        }
+        Ok(metadata)
+    }
+
+    async fn delete_portfolio(&self, id: u32) -> Result<bool, Error> {
+        let database = self.client.database(DB_NAME);
+        let deleted = record::delete_by_id::<Portfolio>(&database, id).await?;
+        let options = DeleteOptions::builder()
+            .write_concern(Some(WriteConcern::MAJORITY))
+            .build();
+        database
+            .repository::<PortfolioVersion>()
+            .delete_many(
+                doc! { field!(portfolio_id in PortfolioVersion): id },
+                options,
+            )
+            .await?;
+        record::delete_by_id::<PortfolioTransactions>(&database, id).await?;
+        Ok(deleted)
+    }
+
+    async fn put_portfolio_version(&self, version: &PortfolioVersion) -> Result<(), Error> {
+        let filter = version_filter(version.portfolio_id, version.version);
+        let options = ReplaceOptions::builder()
+            .upsert(Some(true))
+            .write_concern(Some(WriteConcern::MAJORITY))
+            .build();
+        self.client
+            .database(DB_NAME)
+            .repository::<PortfolioVersion>()
+            .replace_one(filter, version, options)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_portfolio_version(
+        &self,
+        portfolio_id: u32,
+        version: u32,
+    ) -> Result<Option<PortfolioVersion>, Error> {
+        let options = FindOneOptions::builder()
+            .read_concern(Some(ReadConcern::MAJORITY))
+            .build();
+        self.client
+            .database(DB_NAME)
+            .repository::<PortfolioVersion>()
+            .find_one(version_filter(portfolio_id, version), options)
+            .await
+    }
+
+    async fn list_portfolio_versions(
+        &self,
+        portfolio_id: u32,
+        page: &Page,
+    ) -> Result<Vec<PortfolioVersion>, Error> {
+        let options = FindOptions::builder()
+            .sort(doc! { field!(version in PortfolioVersion): -1 })
+            .skip(u64::try_from(page.offset).ok())
+            .limit(page.limit.and_then(|limit| i64::try_from(limit).ok()))
+            .build();
+        let filter = doc! { field!(portfolio_id in PortfolioVersion): portfolio_id };
+        let mut cursor = self
+            .client
+            .database(DB_NAME)
+            .repository::<PortfolioVersion>()
+            .find(filter, options)
+            .await?;
+        let mut versions = Vec::new();
+        while cursor.advance().await? {
+            versions.push(cursor.deserialize_current()?);
+        }
+        Ok(versions)
+    }
+
+    async fn add_lots(
+        &self,
+        portfolio_id: u32,
+        lots: &[Lot],
+        expected_version: Option<u32>,
+    ) -> Result<Option<Portfolio>, Error> {
+        let lots_field = field!(lots in Portfolio);
+        let next_lot_id_field = field!(next_lot_id in Portfolio);
+        let version_field = field!(version in Portfolio);
+        let next_lot_id = doc! {
+            "$ifNull": [format!("${next_lot_id_field}"), Portfolio::FIRST_LOT_ID]
+        };
+        let num_lots = lots.len() as i32;
+        // use an update pipeline, so that ids are assigned and the lots are appended atomically
+        let pipeline = vec![doc! {
+            "$set": {
+                lots_field: {
+                    "$concatArrays": [
+                        format!("${lots_field}"),
+                        {
+                            "$map": {
+                                "input": { "$range": [0, num_lots] },
+                                "as": "i",
+                                "in": {
+                                    "$mergeObjects": [
+                                        { "$arrayElemAt": [{ "$literal": to_bson(lots)? }, "$$i"] },
+                                        { field!(id in Lot): { "$add": [next_lot_id.clone(), "$$i"] } },
+                                    ]
+                                }
+                            }
+                        }
+                    ]
+                },
+                next_lot_id_field: { "$add": [next_lot_id, num_lots] },
+                field!(last_updated in Portfolio): last_updated()?,
+                version_field: { "$add": [{ "$ifNull": [format!("${version_field}"), 0] }, 1] },
+            }
+        }];
+        let filter = portfolio_filter(portfolio_id, expected_version);
+        self.find_portfolio_and_update(filter, pipeline).await
+    }
+
+    async fn update_lot(
+        &self,
+        portfolio_id: u32,
+        lot: &Lot,
+        expected_version: Option<u32>,
+    ) -> Result<Option<Portfolio>, Error> {
+        let lot_id = match lot.id {
+            Some(lot_id) => lot_id,
+            None => return Ok(None),
+        };
+        let mut filter = portfolio_filter(portfolio_id, expected_version);
+        filter.insert(lot_id_field(), lot_id);
+        let update = doc! {
+            "$set": {
+                format!("{}.$", field!(lots in Portfolio)): to_bson(lot)?,
+                field!(last_updated in Portfolio): last_updated()?,
+            },
+            "$inc": { field!(version in Portfolio): 1 },
+        };
+        self.find_portfolio_and_update(filter, update).await
+    }
+
+    async fn delete_lot(
+        &self,
+        portfolio_id: u32,
+        lot_id: u32,
+        expected_version: Option<u32>,
+    ) -> Result<Option<Portfolio>, Error> {
+        let mut filter = portfolio_filter(portfolio_id, expected_version);
+        filter.insert(lot_id_field(), lot_id);
+        let update = doc! {
+            "$pull": { field!(lots in Portfolio): { field!(id in Lot): lot_id } },
+            "$set": { field!(last_updated in Portfolio): last_updated()? },
+            "$inc": { field!(version in Portfolio): 1 },
+        };
+        self.find_portfolio_and_update(filter, update).await
+    }
+
+    async fn get_summary_by_asset_class(
+        &self,
+        id: u32,
+    ) -> Result<Option<PortfolioSummary<AssetClass>>, Error> {
+        let database = self.client.database(DB_NAME);
+        record::find_by_id(&database, id).await
+    }
+
+    async fn get_summary_by_symbol(
+        &self,
+        id: u32,
+    ) -> Result<Option<PortfolioSummary<String>>, Error> {
+        let database = self.client.database(DB_NAME);
+        record::find_by_id(&database, id).await
+    }
+
     async fn put_summary_by_asset_class(
         &self,
         asset_allocation: &PortfolioSummary<AssetClass>,
@@ -49,20 +452,203 @@ impl Dao for MongoDao {
         let database = self.client.database(DB_NAME);
         record::upsert(&database, asset_allocation).await
     }
+
+    async fn delete_summary_by_asset_class(&self, id: u32) -> Result<bool, Error> {
+        let database = self.client.database(DB_NAME);
+        record::delete_by_id::<PortfolioSummaryByAssetClass>(&database, id).await
+    }
+
+    async fn delete_summary_by_symbol(&self, id: u32) -> Result<bool, Error> {
+        let database = self.client.database(DB_NAME);
+        record::delete_by_id::<PortfolioSummaryBySymbol>(&database, id).await
+    }
+
+    async fn get_summary_by_holding_period(
+        &self,
+        id: u32,
+    ) -> Result<Option<PortfolioSummary<HoldingPeriod>>, Error> {
+        let database = self.client.database(DB_NAME);
+        record::find_by_id(&database, id).await
+    }
+
+    async fn get_summary_by_acquisition_year(
+        &self,
+        id: u32,
+    ) -> Result<Option<PortfolioSummary<AcquisitionYear>>, Error> {
+        let database = self.client.database(DB_NAME);
+        record::find_by_id(&database, id).await
+    }
+
+    async fn put_summary_by_holding_period(
+        &self,
+        summary: &PortfolioSummary<HoldingPeriod>,
+    ) -> Result<(), Error> {
+        let database = self.client.database(DB_NAME);
+        record::upsert(&database, summary).await
+    }
+
+    async fn put_summary_by_acquisition_year(
+        &self,
+        summary: &PortfolioSummary<AcquisitionYear>,
+    ) -> Result<(), Error> {
+        let database = self.client.database(DB_NAME);
+        record::upsert(&database, summary).await
+    }
+
+    async fn delete_summary_by_holding_period(&self, id: u32) -> Result<bool, Error> {
+        let database = self.client.database(DB_NAME);
+        record::delete_by_id::<PortfolioSummaryByHoldingPeriod>(&database, id).await
+    }
+
+    async fn delete_summary_by_acquisition_year(&self, id: u32) -> Result<bool, Error> {
+        let database = self.client.database(DB_NAME);
+        record::delete_by_id::<PortfolioSummaryByAcquisitionYear>(&database, id).await
+    }
+
+    async fn put_fx_rates(&self, rates: &[FxRate]) -> Result<(), Error> {
+        let repository = self.client.database(DB_NAME).repository::<FxRate>();
+        let options = ReplaceOptions::builder()
+            .upsert(Some(true))
+            .write_concern(Some(WriteConcern::MAJORITY))
+            .build();
+        for rate in rates {
+            let filter = doc! {
+                field!(from in FxRate): &rate.from,
+                field!(to in FxRate): &rate.to,
+                field!(date in FxRate): to_bson(&rate.date)?,
+            };
+            repository
+                .replace_one(filter, rate, options.clone())
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn get_fx_rates(&self) -> Result<Vec<FxRate>, Error> {
+        self.find_fx_rates(None).await
+    }
+
+    async fn get_fx_rates_to(&self, currencies: &[String], to: &str) -> Result<Vec<FxRate>, Error> {
+        let filter = doc! {
+            "$or": [
+                {
+                    field!(from in FxRate): { "$in": currencies },
+                    field!(to in FxRate): to,
+                },
+                {
+                    field!(from in FxRate): to,
+                    field!(to in FxRate): { "$in": currencies },
+                },
+            ]
+        };
+        self.find_fx_rates(Some(filter)).await
+    }
+
+    async fn put_portfolio_and_transactions(
+        &self,
+        portfolio: &Portfolio,
+        transactions: &PortfolioTransactions,
+        expected_version: u32,
+    ) -> Result<bool, Error> {
+        let database = self.client.database(DB_NAME);
+        let portfolio = Portfolio {
+            last_updated: Some(Utc::now()),
+            ..portfolio.clone()
+        };
+        let condition = put_condition(portfolio.id, expected_version);
+        let mut session = self.client.start_session(None).await?;
+        let options = TransactionOptions::builder()
+            .write_concern(Some(WriteConcern::MAJORITY))
+            .build();
+        session.start_transaction(options).await?;
+        if !record::upsert_if_with_session(&database, &portfolio, condition, &mut session).await? {
+            session.abort_transaction().await?;
+            return Ok(false);
+        }
+        record::upsert_with_session(&database, transactions, &mut session).await?;
+        match session.commit_transaction().await {
+            Ok(()) => Ok(true),
+            // the portfolio was modified by a concurrent transaction
+            Err(error) if error.contains_label(TRANSIENT_TRANSACTION_ERROR) => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+
+    async fn get_transactions(
+        &self,
+        portfolio_id: u32,
+    ) -> Result<Option<PortfolioTransactions>, Error> {
+        let database = self.client.database(DB_NAME);
+        record::find_by_id(&database, portfolio_id).await
+    }
+
+    async fn put_corporate_actions(&self, actions: &[CorporateAction]) -> Result<(), Error> {
+        let repository = self
+            .client
+            .database(DB_NAME)
+            .repository::<CorporateAction>();
+        let options = ReplaceOptions::builder()
+            .upsert(Some(true))
+            .write_concern(Some(WriteConcern::MAJORITY))
+            .build();
+        for action in actions {
+            let filter = doc! {
+                field!(symbol in CorporateAction): &action.symbol,
+                field!(effective_date in CorporateAction): to_bson(&action.effective_date)?,
+            };
+            repository
+                .replace_one(filter, action, options.clone())
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn get_corporate_actions(&self) -> Result<Vec<CorporateAction>, Error> {
+        let options = FindOptions::builder()
+            .sort(doc! {
+                field!(effective_date in CorporateAction): 1,
+                field!(symbol in CorporateAction): 1,
+            })
+            .read_concern(Some(ReadConcern::MAJORITY))
+            .build();
+        let mut cursor = self
+            .client
+            .database(DB_NAME)
+            .repository::<CorporateAction>()
+            .find(None, options)
+            .await?;
+        let mut actions = Vec::new();
+        while cursor.advance().await? {
+            actions.push(cursor.deserialize_current()?);
+        }
+        Ok(actions)
+    }
 }
 
 pub async fn drop_and_create_collections_and_indexes(client: &Client) -> Result<(), Error> {
     let db = client.database(DB_NAME);
     drop_and_create::<Portfolio>(&db).await?;
+    drop_and_create::<PortfolioVersion>(&db).await?;
     drop_and_create::<PortfolioSummaryByAssetClass>(&db).await?;
-    drop_and_create::<PortfolioSummaryBySymbol>(&db).await
+    drop_and_create::<PortfolioSummaryBySymbol>(&db).await?;
+    drop_and_create::<PortfolioSummaryByHoldingPeriod>(&db).await?;
+    drop_and_create::<PortfolioSummaryByAcquisitionYear>(&db).await?;
+    drop_and_create::<FxRate>(&db).await?;
+    drop_and_create::<PortfolioTransactions>(&db).await?;
+    drop_and_create::<CorporateAction>(&db).await
 }
 
 pub async fn create_collections_and_indexes(client: &Client) -> Result<(), Error> {
     let db = client.database(DB_NAME);
     sync_indexes::<PortfolioConfig>(&db).await?;
+    sync_indexes::<PortfolioVersionConfig>(&db).await?;
     sync_indexes::<PortfolioSummaryByAssetClassConfig>(&db).await?;
-    sync_indexes::<PortfolioSummaryBySymbolConfig>(&db).await
+    sync_indexes::<PortfolioSummaryBySymbolConfig>(&db).await?;
+    sync_indexes::<PortfolioSummaryByHoldingPeriodConfig>(&db).await?;
+    sync_indexes::<PortfolioSummaryByAcquisitionYearConfig>(&db).await?;
+    sync_indexes::<FxRateConfig>(&db).await?;
+    sync_indexes::<PortfolioTransactionsConfig>(&db).await?;
+    sync_indexes::<CorporateActionConfig>(&db).await
 }
 
 // -------------------------------------------
@@ -99,6 +685,31 @@ impl Record for Portfolio {
     }
 }
 
+// ODM for Portfolio Version
+
+pub struct PortfolioVersionConfig;
+
+impl CollectionConfig for PortfolioVersionConfig {
+    fn collection_name() -> &'static str {
+        "portfolio_version"
+    }
+
+    fn indexes() -> Indexes {
+        // the versions of a portfolio are listed in descending order
+        let index = Index::new(field!(portfolio_id in PortfolioVersion))
+            .with_key_with_direction(
+                field!(version in PortfolioVersion),
+                mongodm::SortOrder::Descending,
+            )
+            .with_option(IndexOption::Unique);
+        Indexes::new().with(index)
+    }
+}
+
+impl Model for PortfolioVersion {
+    type CollConf = PortfolioVersionConfig;
+}
+
 // ODM for Portfolio Summary By Asset Class
 
 type PortfolioSummaryByAssetClass = PortfolioSummary<AssetClass>;
@@ -162,3 +773,142 @@ impl Record for PortfolioSummaryBySymbol {
         self.id
     }
 }
+
+// ODM for Portfolio Summary By Holding Period
+
+type PortfolioSummaryByHoldingPeriod = PortfolioSummary<HoldingPeriod>;
+
+pub struct PortfolioSummaryByHoldingPeriodConfig;
+
+impl CollectionConfig for PortfolioSummaryByHoldingPeriodConfig {
+    fn collection_name() -> &'static str {
+        "portfolio_by_holding_period"
+    }
+
+    fn indexes() -> Indexes {
+        Indexes::new().with(PortfolioSummaryByHoldingPeriod::id_index())
+    }
+}
+
+impl Model for PortfolioSummaryByHoldingPeriod {
+    type CollConf = PortfolioSummaryByHoldingPeriodConfig;
+}
+
+impl Record for PortfolioSummaryByHoldingPeriod {
+    type IdType = u32;
+
+    fn id_field() -> &'static str {
+        field!(id in PortfolioSummaryByHoldingPeriod)
+    }
+
+    fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+// ODM for Portfolio Summary By Acquisition Year
+
+type PortfolioSummaryByAcquisitionYear = PortfolioSummary<AcquisitionYear>;
+
+pub struct PortfolioSummaryByAcquisitionYearConfig;
+
+impl CollectionConfig for PortfolioSummaryByAcquisitionYearConfig {
+    fn collection_name() -> &'static str {
+        "portfolio_by_acquisition_year"
+    }
+
+    fn indexes() -> Indexes {
+        Indexes::new().with(PortfolioSummaryByAcquisitionYear::id_index())
+    }
+}
+
+impl Model for PortfolioSummaryByAcquisitionYear {
+    type CollConf = PortfolioSummaryByAcquisitionYearConfig;
+}
+
+impl Record for PortfolioSummaryByAcquisitionYear {
+    type IdType = u32;
+
+    fn id_field() -> &'static str {
+        field!(id in PortfolioSummaryByAcquisitionYear)
+    }
+
+    fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+// ODM for FX Rate
+
+pub struct FxRateConfig;
+
+impl CollectionConfig for FxRateConfig {
+    fn collection_name() -> &'static str {
+        "fx_rate"
+    }
+
+    fn indexes() -> Indexes {
+        // there is at most one rate per currency pair and date
+        let index = Index::new(field!(from in FxRate))
+            .with_key(field!(to in FxRate))
+            .with_key(field!(date in FxRate))
+            .with_option(IndexOption::Unique);
+        Indexes::new().with(index)
+    }
+}
+
+impl Model for FxRate {
+    type CollConf = FxRateConfig;
+}
+
+// ODM for Portfolio Transactions
+
+pub struct PortfolioTransactionsConfig;
+
+impl CollectionConfig for PortfolioTransactionsConfig {
+    fn collection_name() -> &'static str {
+        "portfolio_transactions"
+    }
+
+    fn indexes() -> Indexes {
+        Indexes::new().with(PortfolioTransactions::id_index())
+    }
+}
+
+impl Model for PortfolioTransactions {
+    type CollConf = PortfolioTransactionsConfig;
+}
+
+impl Record for PortfolioTransactions {
+    type IdType = u32;
+
+    fn id_field() -> &'static str {
+        field!(id in PortfolioTransactions)
+    }
+
+    fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+// ODM for Corporate Action
+
+pub struct CorporateActionConfig;
+
+impl CollectionConfig for CorporateActionConfig {
+    fn collection_name() -> &'static str {
+        "corporate_action"
+    }
+
+    fn indexes() -> Indexes {
+        // there is at most one action per symbol and effective date
+        let index = Index::new(field!(symbol in CorporateAction))
+            .with_key(field!(effective_date in CorporateAction))
+            .with_option(IndexOption::Unique);
+        Indexes::new().with(index)
+    }
+}
+
+impl Model for CorporateAction {
+    type CollConf = CorporateActionConfig;
+}
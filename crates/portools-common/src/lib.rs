@@ -1,5 +1,6 @@
 pub mod dao;
 pub mod log;
 pub mod model;
+pub mod parse;
 pub mod unit_test_util;
 pub mod validate;
@@ -0,0 +1,542 @@
+use crate::model::sale::relieve;
+use crate::model::{
+    Currency, CurrencyError, Lot, Portfolio, RealizedGain, ReliefMethod, Sale, SaleError,
+};
+use crate::parse::ParseOptions;
+use crate::validate::{trim_and_validate_len, validate_positive, Invalid};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// a change to the shares of a security that takes effect as of a date, such as a split, a reverse
+// split or a change of symbol
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct CorporateAction {
+    // the symbol of the security prior to the action
+    pub symbol: String,
+
+    // the first date that shares are acquired in their changed form
+    pub effective_date: NaiveDate,
+
+    // the number of shares held after the action per share held before it (e.g. 2 for a 2-for-1
+    // split, or 0.1 for a 1-for-10 reverse split), which is 1 for only a change of symbol
+    pub ratio: Decimal,
+
+    // the symbol of the security after the action, if it changes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new_symbol: Option<String>,
+
+    // the per-share amount paid in lieu of the fractional shares that result from the action, if
+    // fractional shares are not issued
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cash_in_lieu: Option<Currency>,
+}
+
+// identifies a corporate action, as only one is stored per symbol and effective date
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CorporateActionId {
+    pub symbol: String,
+    pub effective_date: NaiveDate,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CorporateActionError {
+    // the quantity of a lot overflowed
+    Overflow,
+    CostBasisError { cause: CurrencyError<Decimal> },
+    // the gain realized by paying cash in lieu of fractional shares could not be computed
+    CashInLieuError { cause: SaleError },
+}
+
+impl CorporateAction {
+    /// Converts the fields of a corporate action, as they appear within a file, to a
+    /// CorporateAction. An empty new symbol or cash in lieu is treated as absent, and an invalid
+    /// currency is reported for the "currency" field.
+    pub fn from_str(
+        symbol: &str,
+        effective_date: &str,
+        ratio: &str,
+        new_symbol: &str,
+        cash_in_lieu: &str,
+        currency: &str,
+        options: &ParseOptions,
+    ) -> Result<CorporateAction, Invalid> {
+        let effective_date = options.parse_date("effective_date", effective_date)?;
+        let ratio = options.parse_decimal("ratio", ratio)?;
+        let new_symbol = Some(new_symbol).filter(|value| !value.trim().is_empty());
+        let cash_in_lieu = match cash_in_lieu.trim() {
+            "" => None,
+            value => {
+                let amount = options.parse_amount("cash_in_lieu", value)?;
+                Some(Currency::new(amount, currency).map_err(|cause| Invalid {
+                    field: "currency".into(),
+                    ..cause
+                })?)
+            }
+        };
+        CorporateAction::new(symbol, effective_date, ratio, new_symbol, cash_in_lieu)
+    }
+
+    pub fn new(
+        symbol: &str,
+        effective_date: NaiveDate,
+        ratio: Decimal,
+        new_symbol: Option<&str>,
+        cash_in_lieu: Option<Currency>,
+    ) -> Result<CorporateAction, Invalid> {
+        let symbol =
+            trim_and_validate_len("symbol", symbol, Lot::MIN_SYMBOL_LEN, Lot::MAX_SYMBOL_LEN)?;
+        validate_positive("ratio", &ratio)?;
+        let new_symbol = new_symbol
+            .map(|new_symbol| {
+                trim_and_validate_len(
+                    "new_symbol",
+                    new_symbol,
+                    Lot::MIN_SYMBOL_LEN,
+                    Lot::MAX_SYMBOL_LEN,
+                )
+            })
+            .transpose()?;
+        if let Some(cash_in_lieu) = &cash_in_lieu {
+            validate_positive("cash_in_lieu", &cash_in_lieu.amount)?;
+        }
+        Ok(CorporateAction {
+            symbol,
+            effective_date,
+            ratio,
+            new_symbol,
+            cash_in_lieu,
+        })
+    }
+
+    pub fn id(&self) -> CorporateActionId {
+        CorporateActionId {
+            symbol: self.symbol.clone(),
+            effective_date: self.effective_date,
+        }
+    }
+
+    /// Whether the action changes the lot, being a lot of its symbol that was acquired before the
+    /// action took effect
+    pub fn applies_to(&self, lot: &Lot) -> bool {
+        lot.symbol == self.symbol && lot.date_acquired < self.effective_date
+    }
+
+    // Applies the action to those of the lots that it applies to, where the changed lots of each
+    // account are a position. The quantity of each lot is multiplied by the ratio, and its
+    // per-share cost basis is derived from its total cost, where any remainder from rounding the
+    // per-share costs of a position is given to its last lot, so that the total cost of the
+    // position is unchanged. Where cash is paid in lieu, the fractional share of each position is
+    // sold from its lots, first acquired first, and any lot left without shares is removed. Returns
+    // the gains realized by the cash paid in lieu.
+    pub(crate) fn apply(
+        &self,
+        lots: &mut Vec<Lot>,
+    ) -> Result<Vec<RealizedGain>, CorporateActionError> {
+        let mut positions: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for (index, lot) in lots.iter().enumerate() {
+            if self.applies_to(lot) {
+                positions.entry(&lot.account).or_default().push(index);
+            }
+        }
+        let positions: Vec<Vec<usize>> = positions.into_values().collect();
+        let mut gains = Vec::new();
+        for indices in positions {
+            self.adjust_position(lots, &indices)?;
+            gains.extend(self.pay_cash_in_lieu(lots, &indices)?);
+        }
+        lots.retain(|lot| !lot.quantity.is_zero());
+        Ok(gains)
+    }
+
+    // adjusts the quantity, per-share cost basis and symbol of the lots of a position
+    fn adjust_position(
+        &self,
+        lots: &mut [Lot],
+        indices: &[usize],
+    ) -> Result<(), CorporateActionError> {
+        let cost_basis_error = |cause| CorporateActionError::CostBasisError { cause };
+        let mut total_costs = Vec::with_capacity(indices.len());
+        for index in indices {
+            let lot = &mut lots[*index];
+            let total_cost = lot
+                .cost_basis
+                .multiply(&lot.quantity)
+                .map_err(cost_basis_error)?;
+            lot.quantity = lot
+                .quantity
+                .checked_mul(self.ratio)
+                .ok_or(CorporateActionError::Overflow)?;
+            lot.cost_basis = total_cost.divide(&lot.quantity).map_err(cost_basis_error)?;
+            if let Some(new_symbol) = &self.new_symbol {
+                lot.symbol = new_symbol.clone();
+            }
+            total_costs.push(total_cost);
+        }
+        // the remainder of the lots in the same currency as the last lot
+        let last = match indices.last() {
+            Some(last) => *last,
+            None => return Ok(()),
+        };
+        let currency = lots[last].cost_basis.symbol.clone();
+        let mut remainder = Decimal::ZERO;
+        for (index, total_cost) in indices.iter().zip(&total_costs) {
+            let lot = &lots[*index];
+            if lot.cost_basis.symbol != currency {
+                continue;
+            }
+            remainder = lot
+                .quantity
+                .checked_mul(lot.cost_basis.amount)
+                .and_then(|cost| total_cost.amount.checked_sub(cost))
+                .and_then(|lot_remainder| remainder.checked_add(lot_remainder))
+                .ok_or(CorporateActionError::Overflow)?;
+        }
+        let lot = &mut lots[last];
+        lot.cost_basis.amount = remainder
+            .checked_div(lot.quantity)
+            .and_then(|per_share| lot.cost_basis.amount.checked_add(per_share))
+            .ok_or(CorporateActionError::Overflow)?;
+        Ok(())
+    }
+
+    // sells the fractional share of a position, whose lots were adjusted, for the cash in lieu
+    fn pay_cash_in_lieu(
+        &self,
+        lots: &mut [Lot],
+        indices: &[usize],
+    ) -> Result<Vec<RealizedGain>, CorporateActionError> {
+        let cash_in_lieu = match &self.cash_in_lieu {
+            Some(cash_in_lieu) => cash_in_lieu,
+            None => return Ok(Vec::new()),
+        };
+        let mut position: Vec<Lot> = indices.iter().map(|index| lots[*index].clone()).collect();
+        let quantity = position
+            .iter()
+            .try_fold(Decimal::ZERO, |quantity, lot| {
+                quantity.checked_add(lot.quantity)
+            })
+            .ok_or(CorporateActionError::Overflow)?;
+        let fraction = quantity.fract();
+        if fraction.is_zero() {
+            return Ok(Vec::new());
+        }
+        // the fractional share is sold as of the effective date
+        let sale = Sale {
+            account: position[0].account.clone(),
+            symbol: position[0].symbol.clone(),
+            date: self.effective_date,
+            quantity: fraction,
+            price: cash_in_lieu.clone(),
+            method: ReliefMethod::Fifo,
+        };
+        let gains = relieve(&mut position, &sale)
+            .map_err(|cause| CorporateActionError::CashInLieuError { cause })?;
+        // the lots that are left with shares remain in order
+        let mut relieved = position.into_iter().peekable();
+        for index in indices {
+            let lot = &mut lots[*index];
+            lot.quantity = relieved
+                .next_if(|relieved| {
+                    relieved.id == lot.id
+                        && relieved.date_acquired == lot.date_acquired
+                        && relieved.cost_basis == lot.cost_basis
+                })
+                .map_or(Decimal::ZERO, |relieved| relieved.quantity);
+        }
+        Ok(gains)
+    }
+}
+
+impl Portfolio {
+    /// Applies the corporate actions, in order of their effective dates, to the lots that they
+    /// apply to. A lot's quantity is multiplied by the ratio of the action, and its per-share cost
+    /// basis is derived from its total cost, so that the total cost of the lots of each account is
+    /// unchanged. Where cash is paid in lieu of fractional shares, the fractional share of the
+    /// lots of each account (i.e. of the position, as a broker pays it) is sold from them, and a
+    /// lot that is left without any shares is removed. Returns the gains realized by the cash paid
+    /// in lieu. The portfolio is unchanged if any action fails to apply.
+    ///
+    /// An action that applies to any lot is recorded as applied to the portfolio, and an action
+    /// that was already applied to it is skipped, so that applying the same actions again has no
+    /// effect.
+    pub fn apply_corporate_actions(
+        &mut self,
+        actions: &[CorporateAction],
+    ) -> Result<Vec<RealizedGain>, CorporateActionError> {
+        let mut actions: Vec<&CorporateAction> = actions
+            .iter()
+            .filter(|action| !self.corporate_actions.contains(&action.id()))
+            .collect();
+        actions.sort_by_key(|action| action.effective_date);
+        let mut lots = self.lots.clone();
+        let mut applied = Vec::new();
+        let mut gains = Vec::new();
+        for action in actions {
+            if !lots.iter().any(|lot| action.applies_to(lot)) {
+                continue;
+            }
+            gains.extend(action.apply(&mut lots)?);
+            applied.push(action.id());
+        }
+        self.lots = lots;
+        self.corporate_actions.extend(applied);
+        Ok(gains)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{CorporateAction, Currency, HoldingPeriod, Lot, Portfolio, USD};
+    use crate::parse::ParseOptions;
+    use crate::validate::Reason;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+    use test_util::assertion::assert_ok_eq;
+
+    fn usd(amount: &str) -> Currency {
+        Currency::new(amount.parse().unwrap(), USD).unwrap()
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn lot(symbol: &str, date_acquired: NaiveDate, quantity: &str, cost_basis: &str) -> Lot {
+        Lot::new(
+            "Taxable",
+            symbol,
+            date_acquired,
+            quantity.parse().unwrap(),
+            usd(cost_basis),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn from_str() {
+        assert_ok_eq(
+            &CorporateAction {
+                symbol: "VTI".into(),
+                effective_date: date(2023, 6, 1),
+                ratio: "0.1".parse().unwrap(),
+                new_symbol: Some("VTX".into()),
+                cash_in_lieu: Some(usd("2100.5")),
+            },
+            &CorporateAction::from_str(
+                "VTI",
+                "2023/06/01",
+                "0.1",
+                " VTX ",
+                "$2,100.50",
+                "usd",
+                &ParseOptions::default(),
+            ),
+        );
+        assert_ok_eq(
+            &CorporateAction::new("VTI", date(2023, 6, 1), Decimal::TWO, None, None).unwrap(),
+            &CorporateAction::from_str(
+                "VTI",
+                "2023/06/01",
+                "2",
+                "",
+                "",
+                USD,
+                &ParseOptions::default(),
+            ),
+        );
+        let from_str = |ratio: &str, currency: &str| {
+            CorporateAction::from_str(
+                "VTI",
+                "2023/06/01",
+                ratio,
+                "",
+                "1",
+                currency,
+                &ParseOptions::default(),
+            )
+            .unwrap_err()
+        };
+        assert_eq!("ratio", from_str("0", USD).field);
+        let invalid = from_str("2", "XYZ");
+        assert_eq!("currency", invalid.field);
+        assert_eq!(Reason::UnknownCurrency, invalid.reason);
+    }
+
+    #[test]
+    fn apply_split_and_symbol_change() {
+        let mut portfolio = Portfolio::new(
+            1,
+            vec![
+                lot("VTI", date(2023, 1, 3), "3", "200"),
+                // acquired as of the effective date, so already split
+                lot("VTI", date(2023, 6, 1), "2", "100"),
+                lot("BND", date(2023, 1, 3), "3", "72"),
+            ],
+        );
+        let actions = [
+            CorporateAction::new("VTX", date(2023, 9, 1), Decimal::ONE, Some("VTI"), None).unwrap(),
+            CorporateAction::new("VTI", date(2023, 6, 1), Decimal::TWO, Some("VTX"), None).unwrap(),
+        ];
+        assert_eq!(Ok(vec![]), portfolio.apply_corporate_actions(&actions));
+        assert_eq!(
+            vec![
+                lot("VTI", date(2023, 1, 3), "6", "100"),
+                lot("VTI", date(2023, 6, 1), "2", "100"),
+                lot("BND", date(2023, 1, 3), "3", "72"),
+            ],
+            portfolio
+                .lots
+                .iter()
+                .map(|lot| Lot {
+                    id: None,
+                    ..lot.clone()
+                })
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(Some(1), portfolio.lots[0].id);
+
+        // both actions are recorded as applied, so applying them again has no effect
+        assert_eq!(
+            vec![actions[1].id(), actions[0].id()],
+            portfolio.corporate_actions
+        );
+        let applied = portfolio.clone();
+        assert_eq!(Ok(vec![]), portfolio.apply_corporate_actions(&actions));
+        assert_eq!(applied, portfolio);
+
+        // an action that applies to no lot is not recorded
+        let action =
+            CorporateAction::new("BND", date(2022, 6, 1), Decimal::TWO, None, None).unwrap();
+        assert_eq!(Ok(vec![]), portfolio.apply_corporate_actions(&[action]));
+        assert_eq!(applied, portfolio);
+    }
+
+    #[test]
+    fn apply_reverse_split_with_cash_in_lieu() {
+        let mut portfolio = Portfolio::new(
+            1,
+            vec![
+                lot("VTI", date(2022, 1, 3), "25", "20"),
+                lot("VTI", date(2023, 1, 3), "5", "40"),
+                lot("VTI", date(2023, 2, 1), "5", "40"),
+                Lot {
+                    account: "IRA".into(),
+                    ..lot("VTI", date(2023, 1, 3), "5", "40")
+                },
+            ],
+        );
+        let action = CorporateAction::new(
+            "VTI",
+            date(2023, 6, 1),
+            "0.1".parse().unwrap(),
+            None,
+            Some(usd("250")),
+        )
+        .unwrap();
+        let gains = portfolio.apply_corporate_actions(&[action]).unwrap();
+
+        // only the half share of each position is sold, from the lots acquired first, so the
+        // total cost of the shares that remain is unchanged, and the IRA's lot of half a share is
+        // removed
+        assert_eq!(
+            vec![
+                lot("VTI", date(2022, 1, 3), "2", "200"),
+                lot("VTI", date(2023, 1, 3), "0.5", "400"),
+                lot("VTI", date(2023, 2, 1), "0.5", "400"),
+            ],
+            portfolio
+                .lots
+                .iter()
+                .map(|lot| Lot {
+                    id: None,
+                    ..lot.clone()
+                })
+                .collect::<Vec<_>>()
+        );
+        let gains: Vec<(&str, Decimal, Currency, Currency, HoldingPeriod)> = gains
+            .iter()
+            .map(|gain| {
+                (
+                    gain.account.as_str(),
+                    gain.quantity,
+                    gain.cost.clone(),
+                    gain.gain.clone(),
+                    gain.holding_period,
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    "IRA",
+                    "0.5".parse().unwrap(),
+                    usd("200"),
+                    usd("-75"),
+                    HoldingPeriod::ShortTerm
+                ),
+                (
+                    "Taxable",
+                    "0.5".parse().unwrap(),
+                    usd("100"),
+                    usd("25"),
+                    HoldingPeriod::LongTerm
+                ),
+            ],
+            gains
+        );
+    }
+
+    #[test]
+    fn apply_preserves_total_cost() {
+        let mut portfolio = Portfolio::new(
+            1,
+            vec![
+                lot("VTI", date(2023, 1, 3), "3", "123.45"),
+                lot("VTI", date(2023, 2, 1), "4", "50"),
+            ],
+        );
+        let action =
+            CorporateAction::new("VTI", date(2023, 6, 1), Decimal::from(7), None, None).unwrap();
+        portfolio.apply_corporate_actions(&[action]).unwrap();
+        let total_cost: Decimal = portfolio
+            .lots
+            .iter()
+            .map(|lot| lot.quantity * lot.cost_basis.amount)
+            .sum();
+        assert_eq!("570.35".parse::<Decimal>().unwrap(), total_cost);
+        assert_eq!(
+            vec![Decimal::from(21), Decimal::from(28)],
+            portfolio
+                .lots
+                .iter()
+                .map(|lot| lot.quantity)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn apply_leaves_portfolio_unchanged_on_failure() {
+        let lots = vec![
+            lot("VTI", date(2023, 1, 3), "2", "200"),
+            lot("BND", date(2023, 1, 3), "5", "72"),
+        ];
+        let mut portfolio = Portfolio::new(1, lots);
+        let expected = portfolio.clone();
+        let actions = [
+            CorporateAction::new("VTI", date(2023, 6, 1), Decimal::TWO, None, None).unwrap(),
+            CorporateAction::new(
+                "BND",
+                date(2023, 6, 1),
+                "0.5".parse().unwrap(),
+                None,
+                Some(Currency::new(Decimal::ONE, "EUR").unwrap()),
+            )
+            .unwrap(),
+        ];
+        assert!(portfolio.apply_corporate_actions(&actions).is_err());
+        assert_eq!(expected, portfolio);
+    }
+}
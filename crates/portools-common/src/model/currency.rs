@@ -1,6 +1,8 @@
-use crate::validate::{trim_and_validate_len, Invalid};
-use rust_decimal::Decimal;
+use crate::model::FxRate;
+use crate::validate::{trim_and_validate_len, Invalid, Reason};
+use rust_decimal::{Decimal, RoundingStrategy};
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 
 pub const USD: &str = "USD";
 pub const JPY: &str = "JPY";
@@ -19,51 +21,178 @@ impl Currency {
     const MIN_SYMBOL_LEN: usize = 1;
     const MAX_SYMBOL_LEN: usize = 5;
 
+    /// The symbol must be an ISO 4217 currency code, which is compared case insensitively
     pub fn new(amount: Decimal, symbol: &str) -> Result<Currency, Invalid> {
+        let symbol = Currency::validate_symbol("symbol", symbol)?;
+        Ok(Currency { amount, symbol })
+    }
+
+    /// Validates that the symbol is an ISO 4217 currency code, returning it in upper case
+    pub fn validate_symbol(field: &str, symbol: &str) -> Result<String, Invalid> {
         let symbol = trim_and_validate_len(
-            "symbol",
+            field,
             symbol,
             Currency::MIN_SYMBOL_LEN,
             Currency::MAX_SYMBOL_LEN,
-        )?;
-        Ok(Currency { amount, symbol })
+        )?
+        .to_ascii_uppercase();
+        if rusty_money::iso::find(&symbol).is_none() {
+            return Err(Invalid {
+                field: field.into(),
+                reason: Reason::UnknownCurrency,
+            });
+        }
+        Ok(symbol)
     }
 
     pub fn add(&self, other: &Currency) -> Result<Currency, CurrencyError<Currency>> {
-        if self.symbol != other.symbol {
+        self.check_symbol(&other.symbol)?;
+        self.amount
+            .checked_add(other.amount)
+            .map(|amount| self.with_amount(amount))
+            .ok_or_else(|| self.overflow(other.clone(), "add"))
+    }
+
+    pub fn subtract(&self, other: &Currency) -> Result<Currency, CurrencyError<Currency>> {
+        self.check_symbol(&other.symbol)?;
+        self.amount
+            .checked_sub(other.amount)
+            .map(|amount| self.with_amount(amount))
+            .ok_or_else(|| self.overflow(other.clone(), "subtract"))
+    }
+
+    pub fn multiply(&self, other: &Decimal) -> Result<Currency, CurrencyError<Decimal>> {
+        self.amount
+            .checked_mul(*other)
+            .map(|amount| self.with_amount(amount))
+            .ok_or_else(|| self.overflow(*other, "multiply"))
+    }
+
+    pub fn divide(&self, other: &Decimal) -> Result<Currency, CurrencyError<Decimal>> {
+        if other.is_zero() {
+            return Err(CurrencyError::DivisionByZero { left: self.clone() });
+        }
+        self.amount
+            .checked_div(*other)
+            .map(|amount| self.with_amount(amount))
+            .ok_or_else(|| self.overflow(*other, "divide"))
+    }
+
+    /// The ratio of this amount to the other amount of the same currency (e.g. 0.25 for $1 of $4)
+    pub fn ratio(&self, other: &Currency) -> Result<Decimal, CurrencyError<Currency>> {
+        self.check_symbol(&other.symbol)?;
+        if other.amount.is_zero() {
+            return Err(CurrencyError::DivisionByZero { left: self.clone() });
+        }
+        self.amount
+            .checked_div(other.amount)
+            .ok_or_else(|| self.overflow(other.clone(), "ratio"))
+    }
+
+    /// The percentage of the whole that this amount is (e.g. 25 for $1 of $4)
+    pub fn percentage_of(&self, whole: &Currency) -> Result<Decimal, CurrencyError<Currency>> {
+        self.ratio(whole)?
+            .checked_mul(Decimal::ONE_HUNDRED)
+            .ok_or_else(|| self.overflow(whole.clone(), "percentage_of"))
+    }
+
+    pub fn negate(&self) -> Currency {
+        self.with_amount(-self.amount)
+    }
+
+    /// Sums the amounts, which must all be of the currency of the given symbol. The sum of no
+    /// amounts is zero.
+    pub fn sum<'a>(
+        symbol: &str,
+        amounts: impl IntoIterator<Item = &'a Currency>,
+    ) -> Result<Currency, CurrencyError<Currency>> {
+        let zero = Currency {
+            amount: Decimal::ZERO,
+            symbol: symbol.into(),
+        };
+        amounts
+            .into_iter()
+            .try_fold(zero, |sum, amount| sum.add(amount))
+    }
+
+    /// Compares this amount to the other amount of the same currency
+    pub fn compare(&self, other: &Currency) -> Result<Ordering, CurrencyError<Currency>> {
+        self.check_symbol(&other.symbol)?;
+        Ok(self.amount.cmp(&other.amount))
+    }
+
+    /// The number of digits after the decimal point of the currency's minor unit (e.g. 2 for
+    /// USD, being cents, and 0 for JPY), per ISO 4217
+    pub fn minor_units(&self) -> Result<u32, CurrencyError<Decimal>> {
+        rusty_money::iso::find(&self.symbol)
+            .map(|currency| currency.exponent)
+            .ok_or_else(|| CurrencyError::UnknownSymbol {
+                symbol: self.symbol.clone(),
+            })
+    }
+
+    /// Rounds the amount to the currency's minor unit, where an amount halfway between two minor
+    /// units is rounded away from zero (e.g. $0.125 is rounded to $0.13)
+    pub fn round(&self) -> Result<Currency, CurrencyError<Decimal>> {
+        let minor_units = self.minor_units()?;
+        Ok(self.with_amount(
+            self.amount
+                .round_dp_with_strategy(minor_units, RoundingStrategy::MidpointAwayFromZero),
+        ))
+    }
+
+    /// Converts the amount to the currency that the rate converts to, provided that the rate
+    /// converts from this currency
+    pub fn convert(&self, rate: &FxRate) -> Result<Currency, CurrencyError<Decimal>> {
+        self.check_symbol(&rate.from)?;
+        self.amount
+            .checked_mul(rate.rate)
+            .map(|amount| Currency {
+                amount,
+                symbol: rate.to.clone(),
+            })
+            .ok_or_else(|| self.overflow(rate.rate, "convert"))
+    }
+
+    fn check_symbol<T>(&self, symbol: &str) -> Result<(), CurrencyError<T>> {
+        if self.symbol != symbol {
             return Err(CurrencyError::SymbolMismatch {
                 left: self.symbol.clone(),
-                right: other.symbol.clone(),
+                right: symbol.into(),
             });
         }
-        match self.amount.checked_add(other.amount) {
-            Some(sum) => Ok(Currency {
-                amount: sum,
-                symbol: self.symbol.clone(),
-            }),
-            None => Err(CurrencyError::Overflow {
-                left: self.clone(),
-                right: other.clone(),
-                operation: "add".into(),
-            }),
+        Ok(())
+    }
+
+    fn with_amount(&self, amount: Decimal) -> Currency {
+        Currency {
+            amount,
+            symbol: self.symbol.clone(),
         }
     }
 
-    pub fn multiply(&self, other: &Decimal) -> Result<Currency, CurrencyError<Decimal>> {
-        match self.amount.checked_mul(*other) {
-            Some(sum) => Ok(Currency {
-                amount: sum,
-                symbol: self.symbol.clone(),
-            }),
-            None => Err(CurrencyError::Overflow {
-                left: self.clone(),
-                right: *other,
-                operation: "add".into(),
-            }),
+    fn overflow<T>(&self, right: T, operation: &str) -> CurrencyError<T> {
+        CurrencyError::Overflow {
+            left: self.clone(),
+            right,
+            operation: operation.into(),
         }
     }
 }
 
+/// Adds the amount to the total for its currency, where the totals are ordered by the symbol of
+/// their currency. The amount becomes the total for its currency if there is not one already.
+pub fn add_to_totals(
+    totals: &mut Vec<Currency>,
+    amount: &Currency,
+) -> Result<(), CurrencyError<Currency>> {
+    match totals.binary_search_by(|total| total.symbol.cmp(&amount.symbol)) {
+        Ok(index) => totals[index] = totals[index].add(amount)?,
+        Err(index) => totals.insert(index, amount.clone()),
+    }
+    Ok(())
+}
+
 #[derive(Debug, PartialEq)]
 pub enum CurrencyError<T> {
     SymbolMismatch {
@@ -75,16 +204,37 @@ pub enum CurrencyError<T> {
         right: T,
         operation: String,
     },
+    DivisionByZero {
+        left: Currency,
+    },
+    // the symbol is not that of an ISO 4217 currency, whose minor unit is known
+    UnknownSymbol {
+        symbol: String,
+    },
+}
+
+impl PartialOrd for Currency {
+    // amounts of different currencies are not comparable
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.compare(other).ok()
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::model::currency::{Currency, JPY, USD};
-    use crate::model::CurrencyError;
+    use crate::model::currency::{add_to_totals, Currency, JPY, USD};
+    use crate::model::{CurrencyError, FxRate};
     use crate::unit_test_util::fixture;
+    use crate::validate::Reason;
+    use chrono::NaiveDate;
     use rust_decimal::Decimal;
+    use std::cmp::Ordering;
     use test_util::assertion::assert_ok_eq;
 
+    fn usd(amount: &str) -> Currency {
+        Currency::new(amount.parse().unwrap(), USD).unwrap()
+    }
+
     #[test]
     fn new() {
         assert_eq!(Ok(fixture::currency()), Currency::new(Decimal::ONE, USD));
@@ -108,6 +258,18 @@ mod tests {
         assert!(Currency::new(Decimal::ONE, "US Dollars").is_err());
     }
 
+    #[test]
+    fn new_with_lowercase_symbol() {
+        assert_eq!(Ok(fixture::currency()), Currency::new(Decimal::ONE, "usd"));
+    }
+
+    #[test]
+    fn new_with_unknown_symbol() {
+        let invalid = Currency::new(Decimal::ONE, "XYZ").unwrap_err();
+        assert_eq!("symbol", invalid.field);
+        assert_eq!(Reason::UnknownCurrency, invalid.reason);
+    }
+
     #[test]
     fn add_basic() {
         let currency_1 = Currency::new(Decimal::ONE, USD).unwrap();
@@ -156,11 +318,162 @@ mod tests {
         let currency = Currency::new(Decimal::MAX, USD).unwrap();
         match currency.multiply(&Decimal::TWO) {
             Ok(value) => panic!("expected error, but got {:?}", value),
-            Err(CurrencyError::Overflow { left, right, .. }) => {
+            Err(CurrencyError::Overflow {
+                left,
+                right,
+                operation,
+            }) => {
                 assert_eq!(currency, left);
                 assert_eq!(Decimal::TWO, right);
+                assert_eq!("multiply", operation);
             }
             Err(unexpected) => panic!("got a different error than expected: {:?}", unexpected),
         };
     }
+
+    #[test]
+    fn subtract_basic() {
+        let difference = usd("3").subtract(&usd("1.25"));
+        assert_ok_eq(&usd("1.75"), &difference);
+        assert_ok_eq(&usd("-1.75"), &usd("1.25").subtract(&usd("3")));
+        assert_eq!(
+            Err(CurrencyError::SymbolMismatch {
+                left: USD.into(),
+                right: JPY.into()
+            }),
+            usd("1").subtract(&Currency::new(Decimal::ONE, JPY).unwrap())
+        );
+    }
+
+    #[test]
+    fn subtract_causing_overflow() {
+        let currency = Currency::new(Decimal::MIN, USD).unwrap();
+        assert_eq!(
+            Err(CurrencyError::Overflow {
+                left: currency.clone(),
+                right: usd("1"),
+                operation: "subtract".into()
+            }),
+            currency.subtract(&usd("1"))
+        );
+    }
+
+    #[test]
+    fn divide_basic() {
+        assert_ok_eq(&usd("0.5"), &usd("1").divide(&Decimal::TWO));
+        assert_eq!(
+            Err(CurrencyError::DivisionByZero { left: usd("1") }),
+            usd("1").divide(&Decimal::ZERO)
+        );
+    }
+
+    #[test]
+    fn ratio_and_percentage_of() {
+        assert_ok_eq(&"0.25".parse().unwrap(), &usd("1").ratio(&usd("4")));
+        assert_ok_eq(&Decimal::from(25), &usd("1").percentage_of(&usd("4")));
+        assert_eq!(
+            Err(CurrencyError::DivisionByZero { left: usd("1") }),
+            usd("1").percentage_of(&usd("0"))
+        );
+        assert!(matches!(
+            usd("1").ratio(&Currency::new(Decimal::ONE, JPY).unwrap()),
+            Err(CurrencyError::SymbolMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn negate() {
+        assert_eq!(usd("-1.5"), usd("1.5").negate());
+        assert_eq!(usd("1.5"), usd("-1.5").negate());
+    }
+
+    #[test]
+    fn sum() {
+        assert_ok_eq(&usd("0"), &Currency::sum(USD, []));
+        assert_ok_eq(
+            &usd("6.5"),
+            &Currency::sum(USD, &[usd("1"), usd("2.5"), usd("3")]),
+        );
+        assert!(matches!(
+            Currency::sum(JPY, &[usd("1")]),
+            Err(CurrencyError::SymbolMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn compare() {
+        assert_ok_eq(&Ordering::Less, &usd("1").compare(&usd("2")));
+        assert_ok_eq(&Ordering::Equal, &usd("2").compare(&usd("2.00")));
+        assert!(usd("3") > usd("2"));
+        let yen = Currency::new(Decimal::ONE, JPY).unwrap();
+        assert_eq!(None, usd("1").partial_cmp(&yen));
+        assert!(usd("1").compare(&yen).is_err());
+    }
+
+    #[test]
+    fn round_to_minor_units() {
+        assert_ok_eq(&2, &usd("1").minor_units());
+        assert_ok_eq(&usd("0.13"), &usd("0.125").round());
+        assert_ok_eq(&usd("-0.13"), &usd("-0.125").round());
+        assert_ok_eq(&usd("0.12"), &usd("0.1249").round());
+        let yen = Currency::new("100.5".parse().unwrap(), JPY).unwrap();
+        assert_ok_eq(&0, &yen.minor_units());
+        assert_ok_eq(
+            &Currency::new(Decimal::from(101), JPY).unwrap(),
+            &yen.round(),
+        );
+        let unknown = Currency {
+            amount: Decimal::ONE,
+            symbol: "XYZ".into(),
+        };
+        assert_eq!(
+            Err(CurrencyError::UnknownSymbol {
+                symbol: "XYZ".into()
+            }),
+            unknown.round()
+        );
+    }
+
+    #[test]
+    fn add_to_totals_by_symbol() {
+        let mut totals = Vec::new();
+        for (amount, symbol) in [(1, USD), (100, JPY), (2, USD), (3, "EUR")] {
+            add_to_totals(
+                &mut totals,
+                &Currency::new(Decimal::from(amount), symbol).unwrap(),
+            )
+            .unwrap();
+        }
+        assert_eq!(
+            vec![
+                Currency::new(Decimal::from(3), "EUR").unwrap(),
+                Currency::new(Decimal::from(100), JPY).unwrap(),
+                Currency::new(Decimal::from(3), USD).unwrap(),
+            ],
+            totals
+        );
+    }
+
+    #[test]
+    fn convert_basic() {
+        let currency = Currency::new(Decimal::from(15000), JPY).unwrap();
+        let rate = FxRate::new(
+            JPY,
+            USD,
+            NaiveDate::from_ymd_opt(2023, 3, 1).unwrap(),
+            "0.0073".parse().unwrap(),
+        )
+        .unwrap();
+        assert_ok_eq(
+            &Currency::new("109.5".parse().unwrap(), USD).unwrap(),
+            &currency.convert(&rate),
+        );
+        assert_eq!(
+            Err(CurrencyError::SymbolMismatch {
+                left: USD.into(),
+                right: JPY.into()
+            }),
+            fixture::currency().convert(&rate)
+        );
+    }
 }
@@ -0,0 +1,226 @@
+use crate::model::{GroupSummary, Lot, PortfolioSummary};
+use serde::{Deserialize, Serialize};
+use std::hash::Hash;
+
+// the differences between two lists of lots
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PortfolioDiff {
+    // lots that are only present in the new list
+    pub added: Vec<Lot>,
+
+    // lots that are only present in the old list
+    pub removed: Vec<Lot>,
+
+    // lots that have the same account, symbol and date acquired, but otherwise differ
+    pub changed: Vec<LotChange>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LotChange {
+    pub before: Lot,
+    pub after: Lot,
+}
+
+impl PortfolioDiff {
+    // compares lots by their values, disregarding their ids, since the ids of lots parsed from
+    // an upload have no relation to those of the stored lots
+    pub fn between(before: &[Lot], after: &[Lot]) -> PortfolioDiff {
+        let mut unmatched_before: Vec<Option<&Lot>> = before.iter().map(Some).collect();
+
+        // first, pair up identical lots, since those are unchanged
+        let mut unmatched_after = Vec::new();
+        for lot in after {
+            match take_first(&mut unmatched_before, |b| b.has_same_values(lot)) {
+                Some(_) => {}
+                None => unmatched_after.push(lot),
+            }
+        }
+
+        // then, pair up the remaining lots that represent the same purchase
+        let mut diff = PortfolioDiff::default();
+        for lot in unmatched_after {
+            match take_first(&mut unmatched_before, |b| is_same_purchase(b, lot)) {
+                Some(before) => diff.changed.push(LotChange {
+                    before: before.clone(),
+                    after: lot.clone(),
+                }),
+                None => diff.added.push(lot.clone()),
+            }
+        }
+        diff.removed = unmatched_before.into_iter().flatten().cloned().collect();
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+fn take_first<'a>(
+    lots: &mut [Option<&'a Lot>],
+    predicate: impl Fn(&Lot) -> bool,
+) -> Option<&'a Lot> {
+    lots.iter_mut()
+        .find(|lot| matches!(lot, Some(l) if predicate(l)))
+        .and_then(|lot| lot.take())
+}
+
+fn is_same_purchase(a: &Lot, b: &Lot) -> bool {
+    a.account == b.account && a.symbol == b.symbol && a.date_acquired == b.date_acquired
+}
+
+// the change in the summary of a single group (e.g. a symbol) of a portfolio
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GroupSummaryChange<T> {
+    pub group: T,
+
+    // None if the group was not present in the old summary
+    pub before: Option<GroupSummary>,
+
+    // None if the group is not present in the new summary
+    pub after: Option<GroupSummary>,
+}
+
+impl<T: Hash + Eq + Clone> PortfolioSummary<T> {
+    // returns the changes for each group whose summary differs in the other summary
+    pub fn diff(&self, other: &PortfolioSummary<T>) -> Vec<GroupSummaryChange<T>> {
+        let mut changes: Vec<GroupSummaryChange<T>> = self
+            .group_to_summary
+            .iter()
+            .filter(|(group, before)| other.group_to_summary.get(group) != Some(before))
+            .map(|(group, before)| GroupSummaryChange {
+                group: group.clone(),
+                before: Some(before.clone()),
+                after: other.group_to_summary.get(group).cloned(),
+            })
+            .collect();
+        let added = other
+            .group_to_summary
+            .iter()
+            .filter(|(group, _)| !self.group_to_summary.contains_key(group))
+            .map(|(group, after)| GroupSummaryChange {
+                group: group.clone(),
+                before: None,
+                after: Some(after.clone()),
+            });
+        changes.extend(added);
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::diff::{GroupSummaryChange, LotChange, PortfolioDiff};
+    use crate::model::{GroupSummary, Lot, PortfolioSummary};
+    use crate::unit_test_util::factory::new_usd_unchecked;
+    use crate::unit_test_util::fixture;
+    use rust_decimal::Decimal;
+    use std::collections::HashMap;
+
+    #[test]
+    fn between_identical() {
+        let lots = vec![fixture::lot()];
+        let diff = PortfolioDiff::between(&lots, &lots);
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn between_with_added_removed_and_changed() {
+        let unchanged = fixture::lot();
+        let removed = Lot {
+            symbol: "BND".into(),
+            ..fixture::lot()
+        };
+        let added = Lot {
+            symbol: "VTI".into(),
+            ..fixture::lot()
+        };
+        let changed_before = Lot {
+            account: "IRA".into(),
+            ..fixture::lot()
+        };
+        let changed_after = Lot {
+            quantity: Decimal::from(7),
+            ..changed_before.clone()
+        };
+
+        let diff = PortfolioDiff::between(
+            &[unchanged.clone(), removed.clone(), changed_before.clone()],
+            &[changed_after.clone(), added.clone(), unchanged],
+        );
+        assert_eq!(
+            PortfolioDiff {
+                added: vec![added],
+                removed: vec![removed],
+                changed: vec![LotChange {
+                    before: changed_before,
+                    after: changed_after,
+                }],
+            },
+            diff
+        );
+    }
+
+    #[test]
+    fn between_with_duplicate_lots() {
+        let lot = fixture::lot();
+        let before = vec![lot.clone()];
+        let after = vec![lot.clone(), lot.clone()];
+        let diff = PortfolioDiff::between(&before, &after);
+        assert_eq!(
+            PortfolioDiff {
+                added: vec![lot],
+                ..Default::default()
+            },
+            diff
+        );
+    }
+
+    #[test]
+    fn summary_diff() {
+        let unchanged = GroupSummary::new(vec![new_usd_unchecked("1.00")]).unwrap();
+        let before = GroupSummary::new(vec![new_usd_unchecked("2.00")]).unwrap();
+        let after = GroupSummary::new(vec![new_usd_unchecked("3.00")]).unwrap();
+        let summary_1 = PortfolioSummary {
+            id: 1,
+            group_to_summary: HashMap::from([
+                ("VOO".to_string(), unchanged.clone()),
+                ("BND".to_string(), before.clone()),
+                ("VTI".to_string(), before.clone()),
+            ]),
+            as_of: None,
+        };
+        let summary_2 = PortfolioSummary {
+            id: 1,
+            group_to_summary: HashMap::from([
+                ("VOO".to_string(), unchanged),
+                ("BND".to_string(), after.clone()),
+                ("VEA".to_string(), after.clone()),
+            ]),
+            as_of: None,
+        };
+
+        let mut changes = summary_1.diff(&summary_2);
+        changes.sort_by(|a, b| a.group.cmp(&b.group));
+        assert_eq!(
+            vec![
+                GroupSummaryChange {
+                    group: "BND".to_string(),
+                    before: Some(before.clone()),
+                    after: Some(after.clone()),
+                },
+                GroupSummaryChange {
+                    group: "VEA".to_string(),
+                    before: None,
+                    after: Some(after),
+                },
+                GroupSummaryChange {
+                    group: "VTI".to_string(),
+                    before: Some(before),
+                    after: None,
+                },
+            ],
+            changes
+        );
+    }
+}
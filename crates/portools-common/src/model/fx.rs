@@ -0,0 +1,210 @@
+use crate::model::Currency;
+use crate::parse::ParseOptions;
+use crate::validate::{validate_positive, Invalid};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+// the rate at which one currency is exchanged for another as of a date, where an amount of the
+// `from` currency is multiplied by the rate in order to convert it to the `to` currency
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct FxRate {
+    // the symbol of the currency converted from (e.g. "JPY")
+    pub from: String,
+
+    // the symbol of the currency converted to (e.g. "USD")
+    pub to: String,
+
+    pub date: NaiveDate,
+
+    // the amount of the `to` currency per unit of the `from` currency
+    pub rate: Decimal,
+}
+
+impl FxRate {
+    /// Converts the fields of a rate, as they appear within a file, to an FxRate
+    pub fn from_str(
+        from: &str,
+        to: &str,
+        date: &str,
+        rate: &str,
+        options: &ParseOptions,
+    ) -> Result<FxRate, Invalid> {
+        let date = options.parse_date("date", date)?;
+        let rate = options.parse_decimal("rate", rate)?;
+        FxRate::new(from, to, date, rate)
+    }
+
+    pub fn new(from: &str, to: &str, date: NaiveDate, rate: Decimal) -> Result<FxRate, Invalid> {
+        let from = Currency::validate_symbol("from", from)?;
+        let to = Currency::validate_symbol("to", to)?;
+        validate_positive("rate", &rate)?;
+        Ok(FxRate {
+            from,
+            to,
+            date,
+            rate,
+        })
+    }
+
+    // the rate in the opposite direction, as of the same date
+    fn invert(&self) -> Option<FxRate> {
+        Decimal::ONE.checked_div(self.rate).map(|rate| FxRate {
+            from: self.to.clone(),
+            to: self.from.clone(),
+            date: self.date,
+            rate,
+        })
+    }
+}
+
+/// FX rates, by currency pair and date
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FxRates {
+    pair_to_rates: HashMap<(String, String), BTreeMap<NaiveDate, Decimal>>,
+}
+
+impl FxRates {
+    /// Adds the rate, replacing any for the same currency pair and date
+    pub fn insert(&mut self, rate: FxRate) {
+        self.pair_to_rates
+            .entry((rate.from, rate.to))
+            .or_default()
+            .insert(rate.date, rate.rate);
+    }
+
+    /// The rate for converting from one currency to another as of the date, being the most recent
+    /// rate on or before the date. If a rate in the opposite direction is more recent, then its
+    /// inverse is used instead. The rate between a currency and itself is always 1.
+    pub fn get(&self, from: &str, to: &str, date: NaiveDate) -> Option<FxRate> {
+        if from == to {
+            return Some(FxRate {
+                from: from.into(),
+                to: to.into(),
+                date,
+                rate: Decimal::ONE,
+            });
+        }
+        let direct = self.get_latest(from, to, date);
+        let inverse = self
+            .get_latest(to, from, date)
+            .and_then(|rate| rate.invert());
+        match (direct, inverse) {
+            (Some(direct), Some(inverse)) if inverse.date > direct.date => Some(inverse),
+            (Some(direct), _) => Some(direct),
+            (None, inverse) => inverse,
+        }
+    }
+
+    fn get_latest(&self, from: &str, to: &str, date: NaiveDate) -> Option<FxRate> {
+        let rates = self
+            .pair_to_rates
+            .get(&(from.to_string(), to.to_string()))?;
+        rates.range(..=date).next_back().map(|(date, rate)| FxRate {
+            from: from.into(),
+            to: to.into(),
+            date: *date,
+            rate: *rate,
+        })
+    }
+}
+
+impl FromIterator<FxRate> for FxRates {
+    fn from_iter<I: IntoIterator<Item = FxRate>>(rates: I) -> Self {
+        let mut fx_rates = FxRates::default();
+        for rate in rates {
+            fx_rates.insert(rate);
+        }
+        fx_rates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{FxRate, FxRates, JPY, USD};
+    use crate::parse::ParseOptions;
+    use crate::validate::Reason;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn rate(from: &str, to: &str, date: NaiveDate, rate: &str) -> FxRate {
+        FxRate::new(from, to, date, rate.parse().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn from_str() {
+        assert_eq!(
+            Ok(rate(JPY, USD, date(2023, 3, 27), "0.0076")),
+            FxRate::from_str(
+                "jpy",
+                "USD",
+                "2023/03/27",
+                "0.0076",
+                &ParseOptions::default()
+            )
+        );
+        let invalid =
+            FxRate::from_str(JPY, "XYZ", "2023/03/27", "1", &ParseOptions::default()).unwrap_err();
+        assert_eq!("to", invalid.field);
+        assert_eq!(Reason::UnknownCurrency, invalid.reason);
+        let invalid =
+            FxRate::from_str(JPY, USD, "2023/03/27", "0", &ParseOptions::default()).unwrap_err();
+        assert_eq!("rate", invalid.field);
+    }
+
+    #[test]
+    fn get_most_recent_rate_on_or_before_date() {
+        let rates: FxRates = [
+            rate(JPY, USD, date(2023, 3, 1), "0.0073"),
+            rate(JPY, USD, date(2023, 3, 15), "0.0075"),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(None, rates.get(JPY, USD, date(2023, 2, 28)));
+        assert_eq!(
+            Some(rate(JPY, USD, date(2023, 3, 1), "0.0073")),
+            rates.get(JPY, USD, date(2023, 3, 14))
+        );
+        assert_eq!(
+            Some(rate(JPY, USD, date(2023, 3, 15), "0.0075")),
+            rates.get(JPY, USD, date(2023, 6, 30))
+        );
+        assert_eq!(
+            Some(rate(USD, USD, date(2023, 1, 1), "1")),
+            rates.get(USD, USD, date(2023, 1, 1))
+        );
+    }
+
+    #[test]
+    fn get_inverse_of_more_recent_rate() {
+        let rates: FxRates = [
+            rate(JPY, USD, date(2023, 3, 1), "0.0073"),
+            rate(USD, JPY, date(2023, 3, 15), "125"),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(
+            Some(rate(JPY, USD, date(2023, 3, 1), "0.0073")),
+            rates.get(JPY, USD, date(2023, 3, 14))
+        );
+        assert_eq!(
+            Some(rate(JPY, USD, date(2023, 3, 15), "0.008")),
+            rates.get(JPY, USD, date(2023, 3, 15))
+        );
+        assert_eq!(
+            Some(rate(USD, JPY, date(2023, 3, 15), "125")),
+            rates.get(USD, JPY, date(2023, 3, 15))
+        );
+        assert_eq!(None, rates.get(USD, "EUR", date(2023, 3, 15)));
+        assert_eq!(
+            Decimal::ONE.checked_div(Decimal::new(73, 4)),
+            rates.get(USD, JPY, date(2023, 3, 1)).map(|rate| rate.rate)
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use crate::model::{Lot, Portfolio, PortfolioSummary, PortfolioSummaryError};
+use chrono::{Datelike, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::num::ParseIntError;
+
+// whether shares were held long enough for their gain to be taxed as a long-term capital gain
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HoldingPeriod {
+    ShortTerm,
+    LongTerm,
+}
+
+impl HoldingPeriod {
+    /// The holding period of shares acquired and disposed of on the given dates. Shares are held
+    /// long-term if they are disposed of after the first anniversary of their acquisition (where
+    /// the anniversary of February 29 is February 28).
+    pub fn between(date_acquired: NaiveDate, date_disposed: NaiveDate) -> HoldingPeriod {
+        match date_acquired.checked_add_months(Months::new(12)) {
+            Some(anniversary) if date_disposed > anniversary => HoldingPeriod::LongTerm,
+            Some(_) => HoldingPeriod::ShortTerm,
+            // the anniversary is beyond any representable date
+            None => HoldingPeriod::ShortTerm,
+        }
+    }
+}
+
+// the year that a lot was acquired in, which is represented as a string (e.g. "2023") so that it
+// may key a summary that is stored as a document
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct AcquisitionYear(pub i32);
+
+impl Display for AcquisitionYear {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<AcquisitionYear> for String {
+    fn from(year: AcquisitionYear) -> Self {
+        year.to_string()
+    }
+}
+
+impl TryFrom<String> for AcquisitionYear {
+    type Error = ParseIntError;
+
+    fn try_from(year: String) -> Result<Self, Self::Error> {
+        year.parse().map(AcquisitionYear)
+    }
+}
+
+impl Lot {
+    /// The holding period of the lot, were it to be sold on the given date
+    pub fn holding_period(&self, as_of: NaiveDate) -> HoldingPeriod {
+        HoldingPeriod::between(self.date_acquired, as_of)
+    }
+
+    pub fn acquisition_year(&self) -> AcquisitionYear {
+        AcquisitionYear(self.date_acquired.year())
+    }
+}
+
+impl Portfolio {
+    /// Summarizes the lots by their holding period, were they to be sold on the given date
+    pub fn get_summary_by_holding_period(
+        &self,
+        as_of: NaiveDate,
+    ) -> Result<PortfolioSummary<HoldingPeriod>, PortfolioSummaryError> {
+        let summary = self.get_summary_by(|lot| lot.holding_period(as_of))?;
+        Ok(PortfolioSummary {
+            as_of: Some(as_of),
+            ..summary
+        })
+    }
+
+    pub fn get_summary_by_acquisition_year(
+        &self,
+    ) -> Result<PortfolioSummary<AcquisitionYear>, PortfolioSummaryError> {
+        self.get_summary_by(Lot::acquisition_year)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{
+        AcquisitionYear, Currency, GroupSummary, HoldingPeriod, Lot, Portfolio, USD,
+    };
+    use crate::unit_test_util::factory::new_usd_unchecked;
+    use chrono::NaiveDate;
+    use mongodb::bson;
+    use rust_decimal::Decimal;
+    use std::collections::HashMap;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn holding_period_between() {
+        let acquired = date(2022, 3, 27);
+        assert_eq!(
+            HoldingPeriod::ShortTerm,
+            HoldingPeriod::between(acquired, date(2023, 3, 27))
+        );
+        assert_eq!(
+            HoldingPeriod::LongTerm,
+            HoldingPeriod::between(acquired, date(2023, 3, 28))
+        );
+        assert_eq!(
+            HoldingPeriod::LongTerm,
+            HoldingPeriod::between(date(2020, 2, 29), date(2021, 3, 1))
+        );
+        assert_eq!(
+            HoldingPeriod::ShortTerm,
+            HoldingPeriod::between(date(2020, 2, 29), date(2021, 2, 28))
+        );
+    }
+
+    #[test]
+    fn portfolio_get_summary_by_holding_period_and_acquisition_year() {
+        let lot = |date_acquired, cost_basis| {
+            Lot::new(
+                "Taxable",
+                "VOO",
+                date_acquired,
+                Decimal::ONE,
+                Currency::new(Decimal::from(cost_basis), USD).unwrap(),
+            )
+            .unwrap()
+        };
+        let portfolio = Portfolio::new(
+            1,
+            vec![
+                lot(date(2022, 3, 1), 100),
+                lot(date(2022, 6, 1), 200),
+                lot(date(2023, 1, 3), 400),
+            ],
+        );
+        let summary = |cost: &str| GroupSummary::new(vec![new_usd_unchecked(cost)]).unwrap();
+
+        let by_holding_period = portfolio
+            .get_summary_by_holding_period(date(2023, 3, 27))
+            .unwrap();
+        assert_eq!(
+            HashMap::from([
+                (HoldingPeriod::LongTerm, summary("100")),
+                (HoldingPeriod::ShortTerm, summary("600")),
+            ]),
+            by_holding_period.group_to_summary
+        );
+        assert_eq!(Some(date(2023, 3, 27)), by_holding_period.as_of);
+
+        let by_year = portfolio.get_summary_by_acquisition_year().unwrap();
+        assert_eq!(
+            HashMap::from([
+                (AcquisitionYear(2022), summary("300")),
+                (AcquisitionYear(2023), summary("400")),
+            ]),
+            by_year.group_to_summary
+        );
+        // the summary may be stored as a document, whose keys are strings
+        let document = bson::to_document(&by_year).unwrap();
+        assert_eq!(by_year, bson::from_document(document).unwrap());
+    }
+}
@@ -0,0 +1,376 @@
+use crate::model::sale::relieve;
+use crate::model::{
+    CorporateAction, CorporateActionError, CorporateActionId, Lot, Portfolio, RealizedGain,
+    ReliefMethod, Sale, SaleError, Transaction,
+};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+
+/// The lots that remain open once transactions have been replayed, by account and symbol, along
+/// with the gains realized by the disposals
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Ledger {
+    // the open lots of each account and symbol, ordered by when they were acquired
+    account_symbol_to_lots: BTreeMap<(String, String), Vec<Lot>>,
+    realized_gains: Vec<RealizedGain>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LedgerError {
+    // the transaction disposes of more shares than are held
+    InsufficientQuantity {
+        // the index of the transaction within those replayed
+        index: usize,
+        held: Decimal,
+    },
+    // the total quantity held overflowed
+    Overflow {
+        index: usize,
+    },
+    // the gain realized by the disposal could not be computed
+    SaleError {
+        index: usize,
+        cause: SaleError,
+    },
+    // the corporate action could not be applied to a lot, where the cause is boxed as it is much
+    // larger than the other errors
+    CorporateActionError {
+        action: CorporateActionId,
+        cause: Box<CorporateActionError>,
+    },
+}
+
+impl Ledger {
+    /// Replays the transactions in order of date, where the acquisitions of a date precede its
+    /// disposals, and transactions are otherwise replayed in the order given. Shares that are
+    /// disposed of are relieved per the method of the disposal, or from the lots that were
+    /// acquired first if it has none. The lot acquired by a transaction has the (1-based) position
+    /// of the transaction as its id, so that a disposal may relieve that specific lot.
+    ///
+    /// Each corporate action is applied, in the same manner as Portfolio::apply_corporate_actions,
+    /// once the transactions prior to its effective date have been replayed, so that the quantities
+    /// of the transactions as of the effective date are of the shares in their changed form. Any
+    /// gains realized by cash paid in lieu of fractional shares are among the realized gains.
+    pub fn replay(
+        transactions: &[Transaction],
+        corporate_actions: &[CorporateAction],
+    ) -> Result<Ledger, LedgerError> {
+        let mut order: Vec<usize> = (0..transactions.len()).collect();
+        order.sort_by_key(|index| {
+            let transaction = &transactions[*index];
+            (transaction.date, !transaction.kind.is_acquisition())
+        });
+        let mut actions: Vec<&CorporateAction> = corporate_actions.iter().collect();
+        actions.sort_by_key(|action| action.effective_date);
+        let mut actions = actions.into_iter().peekable();
+        let mut ledger = Ledger::default();
+        for index in order {
+            let transaction = &transactions[index];
+            while let Some(action) =
+                actions.next_if(|action| action.effective_date <= transaction.date)
+            {
+                ledger.apply_corporate_action(action)?;
+            }
+            ledger.apply(index, transaction)?;
+        }
+        for action in actions {
+            ledger.apply_corporate_action(action)?;
+        }
+        Ok(ledger)
+    }
+
+    /// The open lots, ordered by account, then by symbol, and then by when they were acquired
+    pub fn lots(&self) -> impl Iterator<Item = &Lot> {
+        self.account_symbol_to_lots.values().flatten()
+    }
+
+    /// The gains realized by the disposals, in the order that they were replayed
+    pub fn realized_gains(&self) -> &[RealizedGain] {
+        &self.realized_gains
+    }
+
+    pub fn into_lots(self) -> Vec<Lot> {
+        self.account_symbol_to_lots
+            .into_values()
+            .flatten()
+            .collect()
+    }
+
+    /// The portfolio of the open lots, which retain their ids
+    pub fn into_portfolio(self, id: u32) -> Portfolio {
+        let mut portfolio = Portfolio {
+            lots: self.into_lots(),
+            ..Portfolio::new(id, Vec::new())
+        };
+        portfolio.assign_lot_ids();
+        portfolio
+    }
+
+    // applies the action to the lots of its symbol, which move to the new symbol, if any
+    fn apply_corporate_action(&mut self, action: &CorporateAction) -> Result<(), LedgerError> {
+        let keys: Vec<(String, String)> = self
+            .account_symbol_to_lots
+            .keys()
+            .filter(|(_, symbol)| *symbol == action.symbol)
+            .cloned()
+            .collect();
+        for key in keys {
+            let mut lots = self.account_symbol_to_lots.remove(&key).unwrap_or_default();
+            let gains =
+                action
+                    .apply(&mut lots)
+                    .map_err(|cause| LedgerError::CorporateActionError {
+                        action: action.id(),
+                        cause: Box::new(cause),
+                    })?;
+            self.realized_gains.extend(gains);
+            for lot in lots {
+                let open = self
+                    .account_symbol_to_lots
+                    .entry((lot.account.clone(), lot.symbol.clone()))
+                    .or_default();
+                let position = open.partition_point(|open| open.date_acquired <= lot.date_acquired);
+                open.insert(position, lot);
+            }
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, index: usize, transaction: &Transaction) -> Result<(), LedgerError> {
+        let key = (transaction.account.clone(), transaction.symbol.clone());
+        if transaction.kind.is_acquisition() {
+            let lot = Lot {
+                id: u32::try_from(index + 1).ok(),
+                account: transaction.account.clone(),
+                symbol: transaction.symbol.clone(),
+                date_acquired: transaction.acquisition_date(),
+                quantity: transaction.quantity,
+                cost_basis: transaction.price.clone(),
+            };
+            let lots = self.account_symbol_to_lots.entry(key).or_default();
+            // a lot transferred in may have been acquired before those already held
+            let position = lots.partition_point(|open| open.date_acquired <= lot.date_acquired);
+            lots.insert(position, lot);
+            return Ok(());
+        }
+        // shares that are redeemed in order to pay a fee are sold in the same manner
+        let sale = Sale {
+            account: transaction.account.clone(),
+            symbol: transaction.symbol.clone(),
+            date: transaction.date,
+            quantity: transaction.quantity,
+            price: transaction.price.clone(),
+            method: transaction.method.unwrap_or(ReliefMethod::Fifo),
+        };
+        let lots = self.account_symbol_to_lots.entry(key.clone()).or_default();
+        let gains = relieve(lots, &sale).map_err(|cause| match cause {
+            SaleError::InsufficientQuantity { held } => {
+                LedgerError::InsufficientQuantity { index, held }
+            }
+            SaleError::Overflow => LedgerError::Overflow { index },
+            cause => LedgerError::SaleError { index, cause },
+        })?;
+        if lots.is_empty() {
+            self.account_symbol_to_lots.remove(&key);
+        }
+        self.realized_gains.extend(gains);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{
+        CorporateAction, Currency, Ledger, LedgerError, Lot, ReliefMethod, SaleError, Transaction,
+        TransactionKind, USD,
+    };
+    use chrono::{Datelike, NaiveDate};
+    use rust_decimal::Decimal;
+
+    fn usd(amount: i64) -> Currency {
+        Currency::new(Decimal::from(amount), USD).unwrap()
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, 3, day).unwrap()
+    }
+
+    fn transaction(
+        account: &str,
+        day: u32,
+        kind: TransactionKind,
+        quantity: i64,
+        price: i64,
+    ) -> Transaction {
+        Transaction::new(
+            account,
+            "VOO",
+            date(day),
+            kind,
+            Decimal::from(quantity),
+            Currency::new(Decimal::from(price), USD).unwrap(),
+            None,
+            None,
+        )
+        .unwrap()
+    }
+
+    // the lot acquired by the transaction at the (1-based) position
+    fn lot(position: u32, account: &str, day: u32, quantity: i64, price: i64) -> Lot {
+        Lot {
+            id: Some(position),
+            ..Lot::new(
+                account,
+                "VOO",
+                date(day),
+                Decimal::from(quantity),
+                Currency::new(Decimal::from(price), USD).unwrap(),
+            )
+            .unwrap()
+        }
+    }
+
+    #[test]
+    fn replay_relieves_first_acquired() {
+        let transactions = [
+            transaction("Taxable", 1, TransactionKind::Buy, 5, 300),
+            transaction("Taxable", 2, TransactionKind::DividendReinvestment, 1, 310),
+            transaction("IRA", 3, TransactionKind::Buy, 2, 320),
+            transaction("Taxable", 4, TransactionKind::Sell, 4, 330),
+            transaction("Taxable", 5, TransactionKind::Fee, 1, 340),
+        ];
+        let ledger = Ledger::replay(&transactions, &[]).unwrap();
+        let gains: Vec<(u32, Decimal, Currency)> = ledger
+            .realized_gains()
+            .iter()
+            .map(|gain| (gain.date_acquired.day(), gain.quantity, gain.gain.clone()))
+            .collect();
+        assert_eq!(
+            vec![(1, Decimal::from(4), usd(120)), (1, Decimal::ONE, usd(40))],
+            gains
+        );
+        assert_eq!(
+            vec![lot(3, "IRA", 3, 2, 320), lot(2, "Taxable", 2, 1, 310)],
+            ledger.into_lots()
+        );
+    }
+
+    #[test]
+    fn replay_in_order_of_date() {
+        // acquisitions precede disposals of the same date
+        let transactions = [
+            transaction("Taxable", 2, TransactionKind::Sell, 3, 330),
+            transaction("Taxable", 2, TransactionKind::Buy, 2, 320),
+            transaction("Taxable", 1, TransactionKind::Buy, 2, 300),
+        ];
+        let ledger = Ledger::replay(&transactions, &[]).unwrap();
+        assert_eq!(
+            vec![&lot(2, "Taxable", 2, 1, 320)],
+            ledger.lots().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn replay_transfer_in_as_of_date_acquired() {
+        let mut transfer_in = transaction("Taxable", 3, TransactionKind::TransferIn, 2, 100);
+        transfer_in.date_acquired = Some(date(1));
+        let transactions = [
+            transaction("Taxable", 2, TransactionKind::Buy, 2, 300),
+            transfer_in,
+            transaction("Taxable", 4, TransactionKind::Sell, 3, 330),
+        ];
+        let ledger = Ledger::replay(&transactions, &[]).unwrap();
+        assert_eq!(vec![lot(1, "Taxable", 2, 1, 300)], ledger.into_lots());
+    }
+
+    #[test]
+    fn replay_with_corporate_actions() {
+        // a 2-for-1 split between the acquisitions, after which quantities are of split shares
+        let split = CorporateAction::new("VOO", date(3), Decimal::TWO, None, None).unwrap();
+        let transactions = [
+            transaction("Taxable", 1, TransactionKind::Buy, 2, 300),
+            transaction("Taxable", 5, TransactionKind::Buy, 1, 160),
+            transaction("Taxable", 6, TransactionKind::Sell, 3, 170),
+        ];
+        let ledger = Ledger::replay(&transactions, &[split]).unwrap();
+        let gains: Vec<(u32, Decimal, Currency)> = ledger
+            .realized_gains()
+            .iter()
+            .map(|gain| (gain.date_acquired.day(), gain.quantity, gain.gain.clone()))
+            .collect();
+        assert_eq!(vec![(1, Decimal::from(3), usd(60))], gains);
+        assert_eq!(
+            vec![lot(1, "Taxable", 1, 1, 150), lot(2, "Taxable", 5, 1, 160)],
+            ledger.into_lots()
+        );
+
+        // a change of symbol after the last transaction applies to every open lot
+        let change = CorporateAction::new("VOO", date(7), Decimal::ONE, Some("VTI"), None).unwrap();
+        let ledger = Ledger::replay(&transactions[..2], &[change]).unwrap();
+        let symbols: Vec<&str> = ledger.lots().map(|lot| lot.symbol.as_str()).collect();
+        assert_eq!(vec!["VTI", "VTI"], symbols);
+    }
+
+    #[test]
+    fn replay_per_method_of_disposal() {
+        let sell = |quantity, method| Transaction {
+            method: Some(method),
+            ..transaction("Taxable", 4, TransactionKind::Sell, quantity, 330)
+        };
+        let transactions = [
+            transaction("Taxable", 1, TransactionKind::Buy, 2, 300),
+            transaction("Taxable", 2, TransactionKind::Buy, 2, 320),
+            transaction("Taxable", 3, TransactionKind::Buy, 2, 310),
+            sell(1, ReliefMethod::Lifo),
+            sell(1, ReliefMethod::SpecificLot { lot_id: 2 }),
+        ];
+        let ledger = Ledger::replay(&transactions, &[]).unwrap();
+        let relieved: Vec<Option<u32>> = ledger
+            .realized_gains()
+            .iter()
+            .map(|gain| gain.lot_id)
+            .collect();
+        assert_eq!(vec![Some(3), Some(2)], relieved);
+
+        // the lots retain their ids within the portfolio, whose next id follows them
+        let mut portfolio = ledger.into_portfolio(1);
+        assert_eq!(
+            vec![
+                lot(1, "Taxable", 1, 2, 300),
+                lot(2, "Taxable", 2, 1, 320),
+                lot(3, "Taxable", 3, 1, 310),
+            ],
+            portfolio.lots
+        );
+        let added = portfolio.add_lots(vec![lot(1, "IRA", 5, 1, 300)]);
+        assert_eq!(Some(4), added[0].id);
+
+        let transactions = [
+            transaction("Taxable", 1, TransactionKind::Buy, 2, 300),
+            sell(1, ReliefMethod::SpecificLot { lot_id: 5 }),
+        ];
+        assert_eq!(
+            Err(LedgerError::SaleError {
+                index: 1,
+                cause: SaleError::LotNotFound { lot_id: 5 }
+            }),
+            Ledger::replay(&transactions, &[])
+        );
+    }
+
+    #[test]
+    fn replay_with_insufficient_quantity() {
+        let transactions = [
+            transaction("Taxable", 1, TransactionKind::Buy, 2, 300),
+            transaction("IRA", 1, TransactionKind::Buy, 5, 300),
+            transaction("Taxable", 2, TransactionKind::Sell, 3, 330),
+        ];
+        assert_eq!(
+            Err(LedgerError::InsufficientQuantity {
+                index: 2,
+                held: Decimal::TWO
+            }),
+            Ledger::replay(&transactions, &[])
+        );
+    }
+}
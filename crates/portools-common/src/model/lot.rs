@@ -1,14 +1,18 @@
 use crate::model::{Currency, CurrencyError};
+use crate::parse::ParseOptions;
 use crate::validate::{trim_and_validate_len, validate_positive, Invalid};
 use chrono::NaiveDate;
 use rust_decimal::Decimal;
-use rusty_money::{iso, FormattableCurrency, Money};
 use serde::{Deserialize, Serialize};
 
 // a Lot is an amount of securities purchased on a particular date
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[non_exhaustive]
 pub struct Lot {
+    // identifies the lot within its portfolio. Assigned when the lot is added to a Portfolio
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u32>,
+
     // name of the brokerage account within the lot is held
     pub account: String,
 
@@ -27,32 +31,32 @@ pub struct Lot {
 }
 
 impl Lot {
-    const MIN_ACCOUNT_LEN: usize = 1;
-    const MAX_ACCOUNT_LEN: usize = 100;
+    pub(crate) const MIN_ACCOUNT_LEN: usize = 1;
+    pub(crate) const MAX_ACCOUNT_LEN: usize = 100;
 
-    const MIN_SYMBOL_LEN: usize = 1;
-    const MAX_SYMBOL_LEN: usize = 5;
+    pub(crate) const MIN_SYMBOL_LEN: usize = 1;
+    pub(crate) const MAX_SYMBOL_LEN: usize = 5;
 
     const DATE_FORMAT: &'static str = "%Y/%m/%d";
 
+    /// Converts the fields of a lot, as they appear within a file, to a Lot whose cost basis is
+    /// in the given currency. An invalid currency is reported for the "currency" field.
     pub fn from_str(
         account: &str,
         symbol: &str,
         date: &str,
         quantity: &str,
         cost_basis_amount: &str,
+        currency: &str,
+        options: &ParseOptions,
     ) -> Result<Lot, Invalid> {
-        let date = NaiveDate::parse_from_str(date, Lot::DATE_FORMAT)
-            .map_err(|error| Invalid::parse_date_error("date", error))?;
-
-        let quantity = quantity
-            .parse()
-            .map_err(|error| Invalid::parse_decimal_error("quantity", error))?;
-
-        let cost_basis = Money::from_str(cost_basis_amount, iso::USD)
-            .map_err(|error| Invalid::parse_money_error("cost_basis", error))?;
-        let cost_basis = Currency::new(*cost_basis.amount(), cost_basis.currency().code())?;
-
+        let date = options.parse_date("date", date)?;
+        let quantity = options.parse_decimal("quantity", quantity)?;
+        let cost_basis_amount = options.parse_amount("cost_basis", cost_basis_amount)?;
+        let cost_basis = Currency::new(cost_basis_amount, currency).map_err(|cause| Invalid {
+            field: "currency".into(),
+            ..cause
+        })?;
         Lot::new(account, symbol, date, quantity, cost_basis)
     }
 
@@ -74,6 +78,7 @@ impl Lot {
         validate_positive("quantity", &quantity)?;
         validate_positive("cost_basis", &cost_basis.amount)?;
         Ok(Lot {
+            id: None,
             account,
             symbol,
             date_acquired,
@@ -82,20 +87,55 @@ impl Lot {
         })
     }
 
+    // returns whether the lots have the same values, disregarding their ids
+    pub fn has_same_values(&self, other: &Lot) -> bool {
+        self.account == other.account
+            && self.symbol == other.symbol
+            && self.date_acquired == other.date_acquired
+            && self.quantity == other.quantity
+            && self.cost_basis == other.cost_basis
+    }
+
     pub fn get_total_cost(&self) -> Result<Currency, CurrencyError<Decimal>> {
         self.cost_basis.multiply(&self.quantity)
     }
 
-    #[cfg(test)]
-    fn date_acquired_string(&self) -> String {
+    // the date acquired, in the first of the formats accepted by from_str by default
+    pub fn date_acquired_string(&self) -> String {
         format!("{}", self.date_acquired.format(Lot::DATE_FORMAT))
     }
 }
 
+// the values of a Lot as provided by a client, prior to validation
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LotSpec {
+    pub account: String,
+    pub symbol: String,
+    pub date_acquired: NaiveDate,
+    pub quantity: Decimal,
+    pub cost_basis: Currency,
+}
+
+impl TryFrom<LotSpec> for Lot {
+    type Error = Invalid;
+
+    fn try_from(spec: LotSpec) -> Result<Self, Self::Error> {
+        let cost_basis = Currency::new(spec.cost_basis.amount, &spec.cost_basis.symbol)?;
+        Lot::new(
+            &spec.account,
+            &spec.symbol,
+            spec.date_acquired,
+            spec.quantity,
+            cost_basis,
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::model::currency::Currency;
-    use crate::model::{Lot, USD};
+    use crate::model::{Lot, LotSpec, USD};
+    use crate::parse::ParseOptions;
     use crate::unit_test_util::factory::{new_lot_from_spec, new_usd_unchecked};
     use crate::unit_test_util::fixture;
     use crate::validate::Reason::{ParseDateError, ParseDecimalError, ParseMoneyError};
@@ -113,6 +153,8 @@ mod tests {
             &expected.date_acquired_string(),
             &expected.quantity.to_string(),
             &expected.cost_basis.amount.to_string(),
+            USD,
+            &ParseOptions::default(),
         );
         assert_ok_eq(&fixture::lot(), &lot)
     }
@@ -120,13 +162,15 @@ mod tests {
     #[test]
     fn from_str_with_date_with_invalid_format() {
         let fixture = fixture::lot();
-        let date_acquired = format!("{}", fixture.date_acquired.format("%Y-%m-%d"));
+        let date_acquired = format!("{}", fixture.date_acquired.format("%d.%m.%Y"));
         let lot = Lot::from_str(
             &fixture.account,
             &fixture.symbol,
             &date_acquired,
             &fixture.quantity.to_string(),
             &fixture.cost_basis.amount.to_string(),
+            USD,
+            &ParseOptions::default(),
         );
         assert_parse_date_error("date", lot);
     }
@@ -141,6 +185,8 @@ mod tests {
             &fixture.date_acquired_string(),
             quantity,
             &fixture.cost_basis.amount.to_string(),
+            USD,
+            &ParseOptions::default(),
         );
         assert_format_err("quantity", lot);
     }
@@ -155,10 +201,53 @@ mod tests {
             &fixture.date_acquired_string(),
             &fixture.quantity.to_string(),
             cost_basis,
+            USD,
+            &ParseOptions::default(),
         );
         assert_parse_money_error("cost_basis", lot);
     }
 
+    #[test]
+    fn from_str_with_parse_options() {
+        let fixture = fixture::lot();
+        let options = ParseOptions {
+            date_formats: vec!["%d.%m.%Y".into()],
+            decimal_separator: ',',
+            ..Default::default()
+        };
+        let lot = Lot::from_str(
+            &fixture.account,
+            &fixture.symbol,
+            &format!("{}", fixture.date_acquired.format("%d.%m.%Y")),
+            &fixture.quantity.to_string().replace('.', ","),
+            &format!("{} €", fixture.cost_basis.amount).replace('.', ","),
+            USD,
+            &options,
+        );
+        assert_ok_eq(&fixture, &lot);
+    }
+
+    #[test]
+    fn from_str_with_invalid_currency() {
+        let fixture = fixture::lot();
+        let lot = Lot::from_str(
+            &fixture.account,
+            &fixture.symbol,
+            &fixture.date_acquired_string(),
+            &fixture.quantity.to_string(),
+            &fixture.cost_basis.amount.to_string(),
+            "",
+            &ParseOptions::default(),
+        );
+        assert_err_eq(
+            Invalid {
+                field: "currency".into(),
+                reason: Reason::MustHaveLongerLen,
+            },
+            lot,
+        );
+    }
+
     #[test]
     fn new_valid() {
         assert_new_from_spec(fixture::lot(), fixture::lot());
@@ -291,6 +380,49 @@ mod tests {
         assert_new_from_spec_is_err(lot_spec, expected_error);
     }
 
+    #[test]
+    fn try_from_lot_spec() {
+        let expected = fixture::lot();
+        let spec = LotSpec {
+            account: " Taxable ".into(),
+            symbol: expected.symbol.clone(),
+            date_acquired: expected.date_acquired,
+            quantity: expected.quantity,
+            cost_basis: expected.cost_basis.clone(),
+        };
+        assert_ok_eq(&expected, &Lot::try_from(spec));
+    }
+
+    #[test]
+    fn try_from_lot_spec_with_zero_quantity() {
+        let expected = fixture::lot();
+        let spec = LotSpec {
+            account: expected.account.clone(),
+            symbol: expected.symbol.clone(),
+            date_acquired: expected.date_acquired,
+            quantity: Decimal::ZERO,
+            cost_basis: expected.cost_basis,
+        };
+        let expected_error = Invalid {
+            field: "quantity".into(),
+            reason: Reason::MustBePositive,
+        };
+        assert_err_eq(expected_error, Lot::try_from(spec));
+    }
+
+    #[test]
+    fn has_same_values_ignores_id() {
+        let lot = Lot {
+            id: Some(7),
+            ..fixture::lot()
+        };
+        assert!(lot.has_same_values(&fixture::lot()));
+        assert!(!lot.has_same_values(&Lot {
+            symbol: "VTI".into(),
+            ..fixture::lot()
+        }));
+    }
+
     #[test]
     fn get_total_cost_basic() {
         let lot = Lot {
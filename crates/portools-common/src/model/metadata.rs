@@ -0,0 +1,89 @@
+use crate::model::{add_to_totals, Currency, Portfolio, PortfolioSummaryError};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+// describes a portfolio, without including its lots
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PortfolioMetadata {
+    pub id: u32,
+    pub num_lots: usize,
+    pub last_updated: Option<DateTime<Utc>>,
+
+    // the total cost of the lots for each currency that they were acquired in, ordered by the
+    // symbol of the currency
+    pub total_cost: Vec<Currency>,
+}
+
+impl Portfolio {
+    pub fn get_metadata(&self) -> Result<PortfolioMetadata, PortfolioSummaryError> {
+        let mut total_cost: Vec<Currency> = Vec::new();
+        for lot in &self.lots {
+            let lot_total_cost = lot
+                .get_total_cost()
+                .map_err(|cause| PortfolioSummaryError::LotTotalCostError { cause })?;
+            add_to_totals(&mut total_cost, &lot_total_cost)
+                .map_err(|cause| PortfolioSummaryError::SummaryCostError { cause })?;
+        }
+        Ok(PortfolioMetadata {
+            id: self.id,
+            num_lots: self.lots.len(),
+            last_updated: self.last_updated,
+            total_cost,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{Currency, Lot, Portfolio, PortfolioMetadata, JPY, USD};
+    use crate::unit_test_util::fixture;
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn get_metadata_with_empty_lots() {
+        let portfolio = Portfolio::new(1, Vec::new());
+        assert_eq!(
+            PortfolioMetadata {
+                id: 1,
+                num_lots: 0,
+                last_updated: None,
+                total_cost: Vec::new(),
+            },
+            portfolio.get_metadata().unwrap()
+        );
+    }
+
+    #[test]
+    fn get_metadata_with_multiple_currencies() {
+        let lots = vec![
+            Lot {
+                quantity: Decimal::from(2),
+                cost_basis: Currency::new(Decimal::from(10), USD).unwrap(),
+                ..fixture::lot()
+            },
+            Lot {
+                quantity: Decimal::from(3),
+                cost_basis: Currency::new(Decimal::from(1000), JPY).unwrap(),
+                ..fixture::lot()
+            },
+            Lot {
+                quantity: Decimal::from(1),
+                cost_basis: Currency::new(Decimal::from(5), USD).unwrap(),
+                ..fixture::lot()
+            },
+        ];
+        let portfolio = Portfolio::new(1, lots);
+        assert_eq!(
+            PortfolioMetadata {
+                id: 1,
+                num_lots: 3,
+                last_updated: None,
+                total_cost: vec![
+                    Currency::new(Decimal::from(3000), JPY).unwrap(),
+                    Currency::new(Decimal::from(25), USD).unwrap(),
+                ],
+            },
+            portfolio.get_metadata().unwrap()
+        );
+    }
+}
@@ -1,9 +1,43 @@
+mod corporate_action;
+pub use corporate_action::*;
+
 mod currency;
 pub use currency::*;
 
+mod diff;
+pub use diff::*;
+
+mod fx;
+pub use fx::*;
+
+mod holding;
+pub use holding::*;
+
+mod ledger;
+pub use ledger::*;
+
 mod lot;
 pub use lot::*;
 
+mod metadata;
+pub use metadata::*;
+
+mod query;
+pub use query::*;
+
+mod sale;
+pub use sale::*;
+
+mod transaction;
+pub use transaction::*;
+
+mod version;
+pub use version::*;
+
+mod wash_sale;
+pub use wash_sale::*;
+
+use chrono::{DateTime, NaiveDate, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -14,9 +48,98 @@ use std::hash::Hash;
 pub struct Portfolio {
     pub id: u32,
     pub lots: Vec<Lot>,
+
+    // the id to assign to the next lot that is added to this portfolio
+    #[serde(default = "Portfolio::first_lot_id")]
+    pub next_lot_id: u32,
+
+    // incremented by each modification of the portfolio, where 0 means that it has never been
+    // stored
+    #[serde(default)]
+    pub version: u32,
+
+    // when the portfolio was last modified, as recorded by the Dao
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_updated: Option<DateTime<Utc>>,
+
+    // the corporate actions that have been applied to the lots, which are not applied again
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub corporate_actions: Vec<CorporateActionId>,
 }
 
 impl Portfolio {
+    pub const FIRST_LOT_ID: u32 = 1;
+
+    fn first_lot_id() -> u32 {
+        Portfolio::FIRST_LOT_ID
+    }
+
+    // creates a portfolio with the given lots, assigning each of them a new id
+    pub fn new(id: u32, lots: Vec<Lot>) -> Portfolio {
+        let mut portfolio = Portfolio {
+            id,
+            lots: Vec::with_capacity(lots.len()),
+            next_lot_id: Portfolio::FIRST_LOT_ID,
+            version: 0,
+            last_updated: None,
+            corporate_actions: Vec::new(),
+        };
+        portfolio.add_lots(lots);
+        portfolio
+    }
+
+    // adds the lots, assigning each of them a new id. Returns the added lots
+    pub fn add_lots(&mut self, lots: Vec<Lot>) -> &[Lot] {
+        let start = self.lots.len();
+        for lot in lots {
+            self.lots.push(Lot {
+                id: Some(self.next_lot_id),
+                ..lot
+            });
+            self.next_lot_id += 1;
+        }
+        &self.lots[start..]
+    }
+
+    // assigns a new id to each lot that has none, i.e. that was stored before lots were assigned
+    // ids. Returns whether any lot was assigned an id
+    pub fn assign_lot_ids(&mut self) -> bool {
+        if let Some(max_lot_id) = self.lots.iter().filter_map(|lot| lot.id).max() {
+            self.next_lot_id = self.next_lot_id.max(max_lot_id + 1);
+        }
+        let mut assigned = false;
+        for lot in self.lots.iter_mut().filter(|lot| lot.id.is_none()) {
+            lot.id = Some(self.next_lot_id);
+            self.next_lot_id += 1;
+            assigned = true;
+        }
+        assigned
+    }
+
+    pub fn get_lot(&self, lot_id: u32) -> Option<&Lot> {
+        self.lots.iter().find(|lot| lot.id == Some(lot_id))
+    }
+
+    // replaces the lot having the same id as the given lot. Returns whether such a lot existed
+    pub fn update_lot(&mut self, lot: Lot) -> bool {
+        match self
+            .lots
+            .iter_mut()
+            .find(|l| l.id.is_some() && l.id == lot.id)
+        {
+            Some(existing) => {
+                *existing = lot;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove_lot(&mut self, lot_id: u32) -> Option<Lot> {
+        let index = self.lots.iter().position(|lot| lot.id == Some(lot_id))?;
+        Some(self.lots.remove(index))
+    }
+
     pub fn get_summary_by_symbol(&self) -> Result<PortfolioSummary<String>, PortfolioSummaryError> {
         let by_symbol = |lot: &Lot| -> String { lot.symbol.clone() };
         self.get_summary_by(by_symbol)
@@ -24,46 +147,81 @@ impl Portfolio {
 
     pub fn get_summary_by<T: Eq + Hash + Debug>(
         &self,
-        classifier: fn(lot: &Lot) -> T,
+        classifier: impl Fn(&Lot) -> T,
+    ) -> Result<PortfolioSummary<T>, PortfolioSummaryError> {
+        self.summarize(classifier, |lot| {
+            lot.get_total_cost()
+                .map_err(|cause| PortfolioSummaryError::LotTotalCostError { cause })
+        })
+    }
+
+    /// Summarizes the cost of the lots in the currency of the given symbol. The total cost of each
+    /// lot is converted at the rate as of the given date, or as of the date that the lot was
+    /// acquired if no date is given.
+    pub fn get_summary_by_in_currency<T: Eq + Hash + Debug>(
+        &self,
+        classifier: impl Fn(&Lot) -> T,
+        symbol: &str,
+        rates: &FxRates,
+        date: Option<NaiveDate>,
+    ) -> Result<PortfolioSummary<T>, PortfolioSummaryError> {
+        self.summarize(classifier, |lot| {
+            let lot_total_cost = lot
+                .get_total_cost()
+                .map_err(|cause| PortfolioSummaryError::LotTotalCostError { cause })?;
+            let date = date.unwrap_or(lot.date_acquired);
+            let rate = rates
+                .get(&lot_total_cost.symbol, symbol, date)
+                .ok_or_else(|| PortfolioSummaryError::MissingFxRate {
+                    from: lot_total_cost.symbol.clone(),
+                    to: symbol.into(),
+                    date,
+                })?;
+            lot_total_cost
+                .convert(&rate)
+                .map_err(|cause| PortfolioSummaryError::ConversionError { cause })
+        })
+    }
+
+    fn summarize<T: Eq + Hash + Debug>(
+        &self,
+        classifier: impl Fn(&Lot) -> T,
+        get_cost: impl Fn(&Lot) -> Result<Currency, PortfolioSummaryError>,
     ) -> Result<PortfolioSummary<T>, PortfolioSummaryError> {
-        if self.lots.is_empty() {
-            return Ok(PortfolioSummary {
-                id: self.id,
-                group_to_summary: HashMap::new(),
-            });
-        }
-        let currency_symbol = &self.lots[0].cost_basis.symbol;
         let mut group_to_summary = HashMap::<T, GroupSummary>::new();
         for lot in self.lots.iter() {
-            let class = classifier(lot);
+            let cost = get_cost(lot)?;
             let entry = group_to_summary
-                .entry(class)
-                .or_insert_with(|| GroupSummary {
-                    cost: Currency {
-                        amount: Decimal::ZERO,
-                        symbol: currency_symbol.into(),
-                    },
-                });
-            let lot_total_cost = &lot
-                .get_total_cost()
-                .map_err(|cause| PortfolioSummaryError::LotTotalCostError { cause })?;
-            let new_cast = entry
-                .cost
-                .add(lot_total_cost)
+                .entry(classifier(lot))
+                .or_insert_with(|| GroupSummary { cost: Vec::new() });
+            add_to_totals(&mut entry.cost, &cost)
                 .map_err(|cause| PortfolioSummaryError::SummaryCostError { cause })?;
-            entry.cost = new_cast;
         }
         Ok(PortfolioSummary {
             id: self.id,
             group_to_summary,
+            as_of: None,
         })
     }
 }
 
 #[derive(Debug)]
 pub enum PortfolioSummaryError {
-    LotTotalCostError { cause: CurrencyError<Decimal> },
-    SummaryCostError { cause: CurrencyError<Currency> },
+    LotTotalCostError {
+        cause: CurrencyError<Decimal>,
+    },
+    SummaryCostError {
+        cause: CurrencyError<Currency>,
+    },
+    // there is no rate for converting the cost of a lot to the currency of the summary
+    MissingFxRate {
+        from: String,
+        to: String,
+        date: NaiveDate,
+    },
+    ConversionError {
+        cause: CurrencyError<Decimal>,
+    },
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -80,16 +238,20 @@ pub enum AssetClass {
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[non_exhaustive]
 pub struct GroupSummary {
-    pub cost: Currency,
+    // the total cost of the group's lots for each currency that they were acquired in, ordered by
+    // the symbol of the currency
+    pub cost: Vec<Currency>,
 }
 
 impl GroupSummary {
-    pub fn new(cost: Currency) -> Result<GroupSummary, GroupSummaryError> {
-        if cost.amount.is_sign_negative() {
-            Err(GroupSummaryError::NegativeCost { cost })
-        } else {
-            Ok(GroupSummary { cost })
+    pub fn new(mut cost: Vec<Currency>) -> Result<GroupSummary, GroupSummaryError> {
+        if let Some(negative) = cost.iter().find(|cost| cost.amount.is_sign_negative()) {
+            return Err(GroupSummaryError::NegativeCost {
+                cost: negative.clone(),
+            });
         }
+        cost.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        Ok(GroupSummary { cost })
     }
 }
 
@@ -102,12 +264,19 @@ pub enum GroupSummaryError {
 pub struct PortfolioSummary<T: Hash + Eq> {
     pub id: u32,
     pub group_to_summary: HashMap<T, GroupSummary>,
+    // the date as of which the lots were grouped, for groups that change over time (e.g. holding
+    // periods)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub as_of: Option<NaiveDate>,
 }
 
 #[cfg(test)]
 mod tests {
     use crate::model::currency::Currency;
-    use crate::model::{GroupSummary, Lot, Portfolio, PortfolioSummary};
+    use crate::model::{
+        FxRate, FxRates, GroupSummary, Lot, Portfolio, PortfolioSummary, PortfolioSummaryError,
+        JPY, USD,
+    };
     use crate::unit_test_util::fixture;
     use chrono::NaiveDate;
     use rust_decimal::Decimal;
@@ -116,16 +285,14 @@ mod tests {
     #[test]
     fn portfolio_get_allocation_by_symbol_with_empty_lots() {
         let id = 1;
-        let portfolio = Portfolio {
-            id,
-            lots: Vec::new(),
-        };
+        let portfolio = Portfolio::new(id, Vec::new());
 
         let allocation = portfolio.get_summary_by_symbol().unwrap();
         assert_eq!(
             PortfolioSummary {
                 id,
-                group_to_summary: HashMap::new()
+                group_to_summary: HashMap::new(),
+                as_of: None,
             },
             allocation
         )
@@ -135,10 +302,7 @@ mod tests {
     fn portfolio_get_allocation_by_symbol_with_one_lot() {
         let id = 1;
         let lot = fixture::lot();
-        let portfolio = Portfolio {
-            id,
-            lots: vec![lot.clone()],
-        };
+        let portfolio = Portfolio::new(id, vec![lot.clone()]);
 
         let allocation = portfolio.get_summary_by_symbol().unwrap();
         assert_eq!(
@@ -147,9 +311,10 @@ mod tests {
                 group_to_summary: HashMap::from([(
                     lot.symbol.clone(),
                     GroupSummary {
-                        cost: lot.get_total_cost().unwrap()
+                        cost: vec![lot.get_total_cost().unwrap()]
                     }
-                )])
+                )]),
+                as_of: None,
             },
             allocation
         )
@@ -175,10 +340,7 @@ mod tests {
         )
         .unwrap();
 
-        let portfolio = Portfolio {
-            id,
-            lots: vec![lot_1.clone(), lot_2.clone()],
-        };
+        let portfolio = Portfolio::new(id, vec![lot_1.clone(), lot_2.clone()]);
         let allocation = portfolio.get_summary_by_symbol().unwrap();
         assert_eq!(
             PortfolioSummary {
@@ -186,13 +348,14 @@ mod tests {
                 group_to_summary: HashMap::from([(
                     lot_1.symbol.clone(),
                     GroupSummary {
-                        cost: lot_1
+                        cost: vec![lot_1
                             .get_total_cost()
                             .unwrap()
                             .add(&lot_2.get_total_cost().unwrap())
-                            .unwrap()
+                            .unwrap()]
                     }
-                )])
+                )]),
+                as_of: None,
             },
             allocation
         )
@@ -218,10 +381,7 @@ mod tests {
         )
         .unwrap();
 
-        let portfolio = Portfolio {
-            id,
-            lots: vec![lot_1.clone(), lot_2.clone()],
-        };
+        let portfolio = Portfolio::new(id, vec![lot_1.clone(), lot_2.clone()]);
         let allocation = portfolio.get_summary_by_symbol().unwrap();
         assert_eq!(
             PortfolioSummary {
@@ -230,16 +390,17 @@ mod tests {
                     (
                         lot_1.symbol.clone(),
                         GroupSummary {
-                            cost: lot_1.get_total_cost().unwrap()
+                            cost: vec![lot_1.get_total_cost().unwrap()]
                         }
                     ),
                     (
                         lot_2.symbol.clone(),
                         GroupSummary {
-                            cost: lot_2.get_total_cost().unwrap()
+                            cost: vec![lot_2.get_total_cost().unwrap()]
                         }
                     ),
-                ])
+                ]),
+                as_of: None,
             },
             allocation
         );
@@ -273,10 +434,7 @@ mod tests {
         )
         .unwrap();
 
-        let portfolio = Portfolio {
-            id,
-            lots: vec![lot_1.clone(), lot_2.clone(), lot_3.clone()],
-        };
+        let portfolio = Portfolio::new(id, vec![lot_1.clone(), lot_2.clone(), lot_3.clone()]);
         let allocation = portfolio.get_summary_by_symbol().unwrap();
         assert_eq!(
             PortfolioSummary {
@@ -285,22 +443,202 @@ mod tests {
                     (
                         lot_1.symbol.clone(),
                         GroupSummary {
-                            cost: lot_1
+                            cost: vec![lot_1
                                 .get_total_cost()
                                 .unwrap()
                                 .add(&lot_3.get_total_cost().unwrap())
-                                .unwrap()
+                                .unwrap()]
                         }
                     ),
                     (
                         lot_2.symbol.clone(),
                         GroupSummary {
-                            cost: lot_2.get_total_cost().unwrap()
+                            cost: vec![lot_2.get_total_cost().unwrap()]
                         }
                     ),
-                ])
+                ]),
+                as_of: None,
             },
             allocation
         );
     }
+
+    #[test]
+    fn portfolio_get_allocation_by_symbol_with_multiple_currencies() {
+        let id = 1;
+        let lot_1 = Lot {
+            cost_basis: Currency::new("100.00".parse().unwrap(), USD).unwrap(),
+            ..fixture::lot()
+        };
+        let lot_2 = Lot {
+            cost_basis: Currency::new("15000".parse().unwrap(), JPY).unwrap(),
+            ..fixture::lot()
+        };
+        let lot_3 = Lot {
+            cost_basis: Currency::new("50.00".parse().unwrap(), USD).unwrap(),
+            ..fixture::lot()
+        };
+
+        let portfolio = Portfolio::new(id, vec![lot_1.clone(), lot_2.clone(), lot_3.clone()]);
+        let allocation = portfolio.get_summary_by_symbol().unwrap();
+        assert_eq!(
+            PortfolioSummary {
+                id,
+                group_to_summary: HashMap::from([(
+                    lot_1.symbol.clone(),
+                    GroupSummary::new(vec![
+                        lot_2.get_total_cost().unwrap(),
+                        lot_1
+                            .get_total_cost()
+                            .unwrap()
+                            .add(&lot_3.get_total_cost().unwrap())
+                            .unwrap(),
+                    ])
+                    .unwrap()
+                )]),
+                as_of: None,
+            },
+            allocation
+        );
+    }
+
+    #[test]
+    fn portfolio_get_summary_by_symbol_in_currency() {
+        let id = 1;
+        let lot_1 = Lot {
+            date_acquired: NaiveDate::from_ymd_opt(2023, 3, 1).unwrap(),
+            cost_basis: Currency::new("15000".parse().unwrap(), JPY).unwrap(),
+            quantity: Decimal::from(2),
+            ..fixture::lot()
+        };
+        let lot_2 = Lot {
+            date_acquired: NaiveDate::from_ymd_opt(2023, 3, 15).unwrap(),
+            cost_basis: Currency::new("100.00".parse().unwrap(), USD).unwrap(),
+            quantity: Decimal::from(1),
+            ..fixture::lot()
+        };
+        let rates: FxRates = [
+            FxRate::new(
+                JPY,
+                USD,
+                NaiveDate::from_ymd_opt(2023, 3, 1).unwrap(),
+                "0.0073".parse().unwrap(),
+            )
+            .unwrap(),
+            FxRate::new(
+                JPY,
+                USD,
+                NaiveDate::from_ymd_opt(2023, 6, 1).unwrap(),
+                "0.0070".parse().unwrap(),
+            )
+            .unwrap(),
+        ]
+        .into_iter()
+        .collect();
+        let portfolio = Portfolio::new(id, vec![lot_1.clone(), lot_2]);
+        let summary = |date| {
+            portfolio
+                .get_summary_by_in_currency(|lot| lot.symbol.clone(), USD, &rates, date)
+                .unwrap()
+                .group_to_summary[&lot_1.symbol]
+                .clone()
+        };
+
+        // at the rates as of when the lots were acquired
+        assert_eq!(
+            GroupSummary::new(vec![Currency::new("319.00".parse().unwrap(), USD).unwrap()])
+                .unwrap(),
+            summary(None)
+        );
+        // at the rates as of the given date
+        assert_eq!(
+            GroupSummary::new(vec![Currency::new("310.00".parse().unwrap(), USD).unwrap()])
+                .unwrap(),
+            summary(NaiveDate::from_ymd_opt(2023, 6, 30))
+        );
+        assert!(matches!(
+            portfolio.get_summary_by_in_currency(|lot| lot.symbol.clone(), "EUR", &rates, None),
+            Err(PortfolioSummaryError::MissingFxRate { from, to, .. }) if from == JPY && to == "EUR"
+        ));
+    }
+
+    #[test]
+    fn portfolio_new_assigns_lot_ids() {
+        let portfolio = Portfolio::new(1, vec![fixture::lot(), fixture::lot()]);
+        let ids: Vec<Option<u32>> = portfolio.lots.iter().map(|lot| lot.id).collect();
+        assert_eq!(vec![Some(1), Some(2)], ids);
+        assert_eq!(3, portfolio.next_lot_id);
+    }
+
+    #[test]
+    fn portfolio_add_lots_does_not_reuse_ids() {
+        let mut portfolio = Portfolio::new(1, vec![fixture::lot(), fixture::lot()]);
+        portfolio.remove_lot(2).unwrap();
+        let added = portfolio.add_lots(vec![fixture::lot()]);
+        assert_eq!(Some(3), added[0].id);
+        let ids: Vec<Option<u32>> = portfolio.lots.iter().map(|lot| lot.id).collect();
+        assert_eq!(vec![Some(1), Some(3)], ids);
+    }
+
+    #[test]
+    fn portfolio_assign_lot_ids_to_lots_without_ids() {
+        // as stored before lots were assigned ids
+        let mut portfolio = Portfolio {
+            id: 1,
+            lots: vec![
+                fixture::lot(),
+                Lot {
+                    id: Some(4),
+                    ..fixture::lot()
+                },
+                fixture::lot(),
+            ],
+            next_lot_id: Portfolio::FIRST_LOT_ID,
+            version: 0,
+            last_updated: None,
+            corporate_actions: Vec::new(),
+        };
+        assert!(portfolio.assign_lot_ids());
+        let ids: Vec<Option<u32>> = portfolio.lots.iter().map(|lot| lot.id).collect();
+        assert_eq!(vec![Some(5), Some(4), Some(6)], ids);
+        assert_eq!(7, portfolio.next_lot_id);
+
+        assert!(!portfolio.assign_lot_ids());
+        assert_eq!(7, portfolio.next_lot_id);
+    }
+
+    #[test]
+    fn portfolio_update_lot() {
+        let mut portfolio = Portfolio::new(1, vec![fixture::lot(), fixture::lot()]);
+        let updated = Lot {
+            id: Some(2),
+            quantity: Decimal::from(100),
+            ..fixture::lot()
+        };
+        assert!(portfolio.update_lot(updated.clone()));
+        assert_eq!(Some(&updated), portfolio.get_lot(2));
+        assert_eq!(
+            fixture::lot().quantity,
+            portfolio.get_lot(1).unwrap().quantity
+        );
+    }
+
+    #[test]
+    fn portfolio_update_lot_not_found() {
+        let mut portfolio = Portfolio::new(1, vec![fixture::lot()]);
+        let updated = Lot {
+            id: Some(2),
+            ..fixture::lot()
+        };
+        assert!(!portfolio.update_lot(updated));
+        assert!(!portfolio.update_lot(fixture::lot()));
+    }
+
+    #[test]
+    fn portfolio_remove_lot() {
+        let mut portfolio = Portfolio::new(1, vec![fixture::lot()]);
+        assert_eq!(None, portfolio.remove_lot(2));
+        assert_eq!(Some(1), portfolio.remove_lot(1).unwrap().id);
+        assert!(portfolio.lots.is_empty());
+    }
 }
@@ -0,0 +1,211 @@
+use crate::model::Lot;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+// criteria for selecting, ordering and paginating the lots of a portfolio
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LotQuery {
+    pub account: Option<String>,
+    pub symbol: Option<String>,
+
+    // only lots acquired strictly after this date
+    pub acquired_after: Option<NaiveDate>,
+
+    // only lots acquired strictly before this date
+    pub acquired_before: Option<NaiveDate>,
+
+    // when None, the lots remain in the order that they are stored in
+    pub sort: Option<LotSortField>,
+    pub order: SortOrder,
+
+    // the number of matching lots to skip
+    pub offset: usize,
+
+    // the maximum number of lots to return, or None for all of them
+    pub limit: Option<usize>,
+}
+
+// a range of the results of a query
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Page {
+    // the number of results to skip
+    pub offset: usize,
+
+    // the maximum number of results to return, or None for all of them
+    pub limit: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LotSortField {
+    Id,
+    Account,
+    Symbol,
+    DateAcquired,
+    Quantity,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl LotQuery {
+    pub fn matches(&self, lot: &Lot) -> bool {
+        // each criterion that is None matches every lot
+        self.account.iter().all(|a| a == &lot.account)
+            && self.symbol.iter().all(|s| s == &lot.symbol)
+            && self.acquired_after.iter().all(|d| &lot.date_acquired > d)
+            && self.acquired_before.iter().all(|d| &lot.date_acquired < d)
+    }
+
+    // selects, orders and paginates the given lots. Lots that compare as equal by the sort field
+    // are ordered by their ids
+    pub fn apply(&self, lots: &[Lot]) -> Vec<Lot> {
+        let mut matching: Vec<&Lot> = lots.iter().filter(|lot| self.matches(lot)).collect();
+        if let Some(sort) = self.sort {
+            matching.sort_by(|a, b| {
+                let ordering = sort.compare(a, b).then_with(|| a.id.cmp(&b.id));
+                match self.order {
+                    SortOrder::Asc => ordering,
+                    SortOrder::Desc => ordering.reverse(),
+                }
+            });
+        }
+        matching
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect()
+    }
+}
+
+impl LotSortField {
+    fn compare(&self, a: &Lot, b: &Lot) -> Ordering {
+        match self {
+            LotSortField::Id => a.id.cmp(&b.id),
+            LotSortField::Account => a.account.cmp(&b.account),
+            LotSortField::Symbol => a.symbol.cmp(&b.symbol),
+            LotSortField::DateAcquired => a.date_acquired.cmp(&b.date_acquired),
+            LotSortField::Quantity => a.quantity.cmp(&b.quantity),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{Lot, LotQuery, LotSortField, Portfolio, SortOrder};
+    use crate::unit_test_util::fixture;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+
+    fn lots() -> Vec<Lot> {
+        let lots = vec![
+            Lot {
+                account: "IRA".into(),
+                symbol: "BND".into(),
+                date_acquired: NaiveDate::from_ymd_opt(2023, 3, 28).unwrap(),
+                quantity: Decimal::from(2),
+                ..fixture::lot()
+            },
+            Lot {
+                account: "Taxable".into(),
+                symbol: "VOO".into(),
+                date_acquired: NaiveDate::from_ymd_opt(2023, 3, 27).unwrap(),
+                quantity: Decimal::from(3),
+                ..fixture::lot()
+            },
+            Lot {
+                account: "IRA".into(),
+                symbol: "VOO".into(),
+                date_acquired: NaiveDate::from_ymd_opt(2023, 3, 29).unwrap(),
+                quantity: Decimal::from(1),
+                ..fixture::lot()
+            },
+        ];
+        Portfolio::new(1, lots).lots
+    }
+
+    fn ids(lots: &[Lot]) -> Vec<u32> {
+        lots.iter().filter_map(|lot| lot.id).collect()
+    }
+
+    #[test]
+    fn apply_default() {
+        let lots = lots();
+        assert_eq!(lots, LotQuery::default().apply(&lots));
+    }
+
+    #[test]
+    fn apply_with_filters() {
+        let lots = lots();
+        let query = LotQuery {
+            account: Some("IRA".into()),
+            ..Default::default()
+        };
+        assert_eq!(vec![1, 3], ids(&query.apply(&lots)));
+
+        let query = LotQuery {
+            account: Some("IRA".into()),
+            symbol: Some("VOO".into()),
+            ..Default::default()
+        };
+        assert_eq!(vec![3], ids(&query.apply(&lots)));
+
+        let query = LotQuery {
+            acquired_after: NaiveDate::from_ymd_opt(2023, 3, 27),
+            acquired_before: NaiveDate::from_ymd_opt(2023, 3, 29),
+            ..Default::default()
+        };
+        assert_eq!(vec![1], ids(&query.apply(&lots)));
+    }
+
+    #[test]
+    fn apply_with_sort() {
+        let lots = lots();
+        let query = LotQuery {
+            sort: Some(LotSortField::DateAcquired),
+            ..Default::default()
+        };
+        assert_eq!(vec![2, 1, 3], ids(&query.apply(&lots)));
+
+        let query = LotQuery {
+            sort: Some(LotSortField::Quantity),
+            order: SortOrder::Desc,
+            ..Default::default()
+        };
+        assert_eq!(vec![2, 1, 3], ids(&query.apply(&lots)));
+
+        // ties are broken by id
+        let query = LotQuery {
+            sort: Some(LotSortField::Account),
+            ..Default::default()
+        };
+        assert_eq!(vec![1, 3, 2], ids(&query.apply(&lots)));
+    }
+
+    #[test]
+    fn apply_with_pagination() {
+        let lots = lots();
+        let query = LotQuery {
+            sort: Some(LotSortField::DateAcquired),
+            offset: 1,
+            limit: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(vec![1], ids(&query.apply(&lots)));
+
+        let query = LotQuery {
+            offset: 5,
+            ..Default::default()
+        };
+        assert!(query.apply(&lots).is_empty());
+    }
+}
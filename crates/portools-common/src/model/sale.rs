@@ -0,0 +1,426 @@
+use crate::model::{Currency, CurrencyError, HoldingPeriod, Lot, Portfolio};
+use crate::validate::{trim_and_validate_len, validate_positive, Invalid, Reason};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+
+// how the lots that shares are sold from are selected
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReliefMethod {
+    // first in, first out: the lots acquired first
+    Fifo,
+    // last in, first out: the lots acquired last
+    Lifo,
+    // highest in, first out: the lots with the highest cost basis
+    Hifo,
+    // the lots that realize the least tax: short-term losses, then long-term losses, then
+    // long-term gains and then short-term gains, where the greatest losses and the least gains are
+    // realized first
+    LowestGain,
+    // only the lot with the given id
+    SpecificLot { lot_id: u32 },
+}
+
+impl ReliefMethod {
+    pub const NAMES: [&'static str; 5] = ["fifo", "lifo", "hifo", "lowest_gain", "specific_lot"];
+
+    /// Parses the name of a method, ignoring case and treating spaces and hyphens as underscores
+    /// (e.g. "Lowest Gain"). The specific lot method requires the id of the lot.
+    pub fn from_str(
+        field: &str,
+        value: &str,
+        lot_id: Option<u32>,
+    ) -> Result<ReliefMethod, Invalid> {
+        let name = value.trim().to_ascii_lowercase().replace([' ', '-'], "_");
+        match name.as_str() {
+            "fifo" => Ok(ReliefMethod::Fifo),
+            "lifo" => Ok(ReliefMethod::Lifo),
+            "hifo" => Ok(ReliefMethod::Hifo),
+            "lowest_gain" => Ok(ReliefMethod::LowestGain),
+            "specific_lot" => lot_id
+                .map(|lot_id| ReliefMethod::SpecificLot { lot_id })
+                .ok_or_else(|| Invalid::required_str("lot_id")),
+            _ => Err(Invalid {
+                field: field.into(),
+                reason: Reason::NotOneOf {
+                    values: ReliefMethod::NAMES
+                        .iter()
+                        .map(|name| name.to_string())
+                        .collect(),
+                },
+            }),
+        }
+    }
+}
+
+// a sale of shares of a security held within an account
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Sale {
+    pub account: String,
+    pub symbol: String,
+    pub date: NaiveDate,
+    pub quantity: Decimal,
+
+    // the per-share proceeds of the sale
+    pub price: Currency,
+    pub method: ReliefMethod,
+}
+
+impl Sale {
+    pub fn new(
+        account: &str,
+        symbol: &str,
+        date: NaiveDate,
+        quantity: Decimal,
+        price: Currency,
+        method: ReliefMethod,
+    ) -> Result<Sale, Invalid> {
+        let account = trim_and_validate_len(
+            "account",
+            account,
+            Lot::MIN_ACCOUNT_LEN,
+            Lot::MAX_ACCOUNT_LEN,
+        )?;
+        let symbol =
+            trim_and_validate_len("symbol", symbol, Lot::MIN_SYMBOL_LEN, Lot::MAX_SYMBOL_LEN)?;
+        validate_positive("quantity", &quantity)?;
+        validate_positive("price", &price.amount)?;
+        Ok(Sale {
+            account,
+            symbol,
+            date,
+            quantity,
+            price,
+            method,
+        })
+    }
+
+    // whether shares of the lot may be sold by this sale
+    fn may_relieve(&self, lot: &Lot) -> bool {
+        lot.account == self.account && lot.symbol == self.symbol && lot.date_acquired <= self.date
+    }
+}
+
+// the gain (or loss, if negative) realized by selling shares of a lot
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RealizedGain {
+    // the id of the lot that the shares were sold from, if it has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lot_id: Option<u32>,
+    pub account: String,
+    pub symbol: String,
+    pub date_acquired: NaiveDate,
+    pub date_sold: NaiveDate,
+    pub quantity: Decimal,
+    pub proceeds: Currency,
+    pub cost: Currency,
+    pub gain: Currency,
+    pub holding_period: HoldingPeriod,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SaleError {
+    // more shares are sold than are held, by the account or within the specific lot
+    InsufficientQuantity { held: Decimal },
+    // the specific lot is not one of the account's lots of the symbol
+    LotNotFound { lot_id: u32 },
+    // the total quantity held overflowed
+    Overflow,
+    ProceedsError { cause: CurrencyError<Decimal> },
+    LotTotalCostError { cause: CurrencyError<Decimal> },
+    GainError { cause: CurrencyError<Currency> },
+}
+
+impl Portfolio {
+    /// Sells shares from the lots of the account and symbol that were acquired on or before the
+    /// date of the sale, selecting lots per the sale's relief method. A lot that is partially sold
+    /// remains, with the shares that were not sold. Returns the gain realized from each lot, in
+    /// the order that they were relieved. The portfolio is unchanged if the sale fails.
+    pub fn sell(&mut self, sale: &Sale) -> Result<Vec<RealizedGain>, SaleError> {
+        relieve(&mut self.lots, sale)
+    }
+}
+
+// Relieves the shares of the sale from the lots, as described by Portfolio::sell
+pub(crate) fn relieve(lots: &mut Vec<Lot>, sale: &Sale) -> Result<Vec<RealizedGain>, SaleError> {
+    let plan = plan_relief(lots, sale)?;
+    let gains = plan
+        .iter()
+        .map(|(index, quantity)| realize(&lots[*index], *quantity, sale))
+        .collect::<Result<Vec<RealizedGain>, SaleError>>()?;
+    for (index, quantity) in &plan {
+        lots[*index].quantity -= *quantity;
+    }
+    lots.retain(|lot| !lot.quantity.is_zero());
+    Ok(gains)
+}
+
+// the index of each lot to relieve, in order, along with the quantity relieved from it
+fn plan_relief(lots: &[Lot], sale: &Sale) -> Result<Vec<(usize, Decimal)>, SaleError> {
+    let mut candidates: Vec<usize> = match sale.method {
+        ReliefMethod::SpecificLot { lot_id } => {
+            let index = lots
+                .iter()
+                .position(|lot| lot.id == Some(lot_id) && sale.may_relieve(lot))
+                .ok_or(SaleError::LotNotFound { lot_id })?;
+            vec![index]
+        }
+        _ => (0..lots.len())
+            .filter(|index| sale.may_relieve(&lots[*index]))
+            .collect(),
+    };
+    match sale.method {
+        ReliefMethod::Fifo => candidates.sort_by_key(|index| lots[*index].date_acquired),
+        ReliefMethod::Lifo => candidates.sort_by_key(|index| Reverse(lots[*index].date_acquired)),
+        ReliefMethod::Hifo => {
+            candidates.sort_by_key(|index| Reverse(lots[*index].cost_basis.amount))
+        }
+        ReliefMethod::LowestGain => candidates.sort_by_key(|index| {
+            let lot = &lots[*index];
+            let gain = sale.price.amount.saturating_sub(lot.cost_basis.amount);
+            let holding_period = HoldingPeriod::between(lot.date_acquired, sale.date);
+            let rank = match (gain.is_sign_negative(), holding_period) {
+                (true, HoldingPeriod::ShortTerm) => 0,
+                (true, HoldingPeriod::LongTerm) => 1,
+                (false, HoldingPeriod::LongTerm) => 2,
+                (false, HoldingPeriod::ShortTerm) => 3,
+            };
+            (rank, gain)
+        }),
+        ReliefMethod::SpecificLot { .. } => {}
+    }
+    let held = candidates.iter().try_fold(Decimal::ZERO, |held, index| {
+        held.checked_add(lots[*index].quantity)
+            .ok_or(SaleError::Overflow)
+    })?;
+    if held < sale.quantity {
+        return Err(SaleError::InsufficientQuantity { held });
+    }
+    let mut plan = Vec::new();
+    let mut remaining = sale.quantity;
+    for index in candidates {
+        if remaining.is_zero() {
+            break;
+        }
+        let quantity = remaining.min(lots[index].quantity);
+        plan.push((index, quantity));
+        remaining -= quantity;
+    }
+    Ok(plan)
+}
+
+fn realize(lot: &Lot, quantity: Decimal, sale: &Sale) -> Result<RealizedGain, SaleError> {
+    // the shares sold, split from the rest of the lot
+    let sold = Lot {
+        quantity,
+        ..lot.clone()
+    };
+    let proceeds = sale
+        .price
+        .multiply(&quantity)
+        .map_err(|cause| SaleError::ProceedsError { cause })?;
+    let cost = sold
+        .get_total_cost()
+        .map_err(|cause| SaleError::LotTotalCostError { cause })?;
+    let gain = proceeds
+        .subtract(&cost)
+        .map_err(|cause| SaleError::GainError { cause })?;
+    Ok(RealizedGain {
+        lot_id: sold.id,
+        account: sold.account,
+        symbol: sold.symbol,
+        date_acquired: sold.date_acquired,
+        date_sold: sale.date,
+        quantity,
+        proceeds,
+        cost,
+        gain,
+        holding_period: HoldingPeriod::between(lot.date_acquired, sale.date),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{
+        Currency, CurrencyError, HoldingPeriod, Lot, Portfolio, RealizedGain, ReliefMethod, Sale,
+        SaleError, JPY, USD,
+    };
+    use crate::unit_test_util::factory::new_usd_unchecked;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn lot(account: &str, date_acquired: NaiveDate, quantity: i64, cost_basis: &str) -> Lot {
+        Lot::new(
+            account,
+            "VOO",
+            date_acquired,
+            Decimal::from(quantity),
+            new_usd_unchecked(cost_basis),
+        )
+        .unwrap()
+    }
+
+    // lots 1 through 4, of which lot 1 is of another account
+    fn portfolio() -> Portfolio {
+        Portfolio::new(
+            1,
+            vec![
+                lot("IRA", date(2021, 1, 4), 10, "300"),
+                lot("Taxable", date(2021, 1, 4), 2, "350"),
+                lot("Taxable", date(2022, 1, 3), 2, "400"),
+                lot("Taxable", date(2023, 1, 3), 2, "380"),
+            ],
+        )
+    }
+
+    fn sale(quantity: &str, price: &str, method: ReliefMethod) -> Sale {
+        Sale::new(
+            "Taxable",
+            "VOO",
+            date(2023, 3, 27),
+            quantity.parse().unwrap(),
+            new_usd_unchecked(price),
+            method,
+        )
+        .unwrap()
+    }
+
+    // the ids of the lots relieved, along with the quantity relieved from each
+    fn relieved(gains: &[RealizedGain]) -> Vec<(u32, Decimal)> {
+        gains
+            .iter()
+            .map(|gain| (gain.lot_id.unwrap(), gain.quantity))
+            .collect()
+    }
+
+    #[test]
+    fn sell_fifo_splitting_partially_sold_lot() {
+        let mut portfolio = portfolio();
+        let gains = portfolio
+            .sell(&sale("3", "390", ReliefMethod::Fifo))
+            .unwrap();
+        assert_eq!(vec![(2, Decimal::TWO), (3, Decimal::ONE)], relieved(&gains));
+        assert_eq!(
+            RealizedGain {
+                lot_id: Some(2),
+                account: "Taxable".into(),
+                symbol: "VOO".into(),
+                date_acquired: date(2021, 1, 4),
+                date_sold: date(2023, 3, 27),
+                quantity: Decimal::TWO,
+                proceeds: new_usd_unchecked("780"),
+                cost: new_usd_unchecked("700"),
+                gain: new_usd_unchecked("80"),
+                holding_period: HoldingPeriod::LongTerm,
+            },
+            gains[0]
+        );
+        assert_eq!(new_usd_unchecked("-10"), gains[1].gain);
+        assert_eq!(None, portfolio.get_lot(2));
+        let remaining = portfolio.get_lot(3).unwrap();
+        assert_eq!(Decimal::ONE, remaining.quantity);
+        assert_eq!(new_usd_unchecked("400"), remaining.cost_basis);
+        assert_eq!(3, portfolio.lots.len());
+    }
+
+    #[test]
+    fn sell_lifo_and_hifo() {
+        let mut portfolio = portfolio();
+        let gains = portfolio
+            .sell(&sale("3", "390", ReliefMethod::Lifo))
+            .unwrap();
+        assert_eq!(vec![(4, Decimal::TWO), (3, Decimal::ONE)], relieved(&gains));
+
+        let mut portfolio = self::portfolio();
+        let gains = portfolio
+            .sell(&sale("3", "390", ReliefMethod::Hifo))
+            .unwrap();
+        assert_eq!(vec![(3, Decimal::TWO), (4, Decimal::ONE)], relieved(&gains));
+    }
+
+    #[test]
+    fn sell_lowest_gain() {
+        // lot 4 is a short-term loss, lot 3 a long-term loss and lot 2 a long-term gain
+        let mut portfolio = portfolio();
+        let gains = portfolio
+            .sell(&sale("5", "370", ReliefMethod::LowestGain))
+            .unwrap();
+        assert_eq!(
+            vec![(4, Decimal::TWO), (3, Decimal::TWO), (2, Decimal::ONE)],
+            relieved(&gains)
+        );
+        assert_eq!(HoldingPeriod::ShortTerm, gains[0].holding_period);
+        assert_eq!(new_usd_unchecked("-20"), gains[0].gain);
+        assert_eq!(HoldingPeriod::LongTerm, gains[1].holding_period);
+    }
+
+    #[test]
+    fn sell_specific_lot() {
+        let mut portfolio = portfolio();
+        let gains = portfolio
+            .sell(&sale("1.5", "390", ReliefMethod::SpecificLot { lot_id: 4 }))
+            .unwrap();
+        assert_eq!(vec![(4, "1.5".parse().unwrap())], relieved(&gains));
+        assert_eq!(
+            "0.5".parse::<Decimal>().unwrap(),
+            portfolio.get_lot(4).unwrap().quantity
+        );
+
+        // lot 1 is of another account
+        assert_eq!(
+            Err(SaleError::LotNotFound { lot_id: 1 }),
+            portfolio.sell(&sale("1", "390", ReliefMethod::SpecificLot { lot_id: 1 }))
+        );
+        assert_eq!(
+            Err(SaleError::InsufficientQuantity { held: Decimal::TWO }),
+            portfolio.sell(&sale("3", "390", ReliefMethod::SpecificLot { lot_id: 3 }))
+        );
+    }
+
+    #[test]
+    fn sell_failure_leaves_portfolio_unchanged() {
+        let mut portfolio = portfolio();
+        assert_eq!(
+            Err(SaleError::InsufficientQuantity {
+                held: Decimal::from(6)
+            }),
+            portfolio.sell(&sale("7", "390", ReliefMethod::Fifo))
+        );
+        let yen = Sale {
+            price: Currency::new(Decimal::from(50000), JPY).unwrap(),
+            ..sale("1", "390", ReliefMethod::Fifo)
+        };
+        assert_eq!(
+            Err(SaleError::GainError {
+                cause: CurrencyError::SymbolMismatch {
+                    left: JPY.into(),
+                    right: USD.into()
+                }
+            }),
+            portfolio.sell(&yen)
+        );
+        assert_eq!(self::portfolio(), portfolio);
+    }
+
+    #[test]
+    fn sell_only_lots_acquired_on_or_before_date() {
+        let mut portfolio = portfolio();
+        let early = Sale {
+            date: date(2022, 12, 31),
+            ..sale("5", "390", ReliefMethod::Fifo)
+        };
+        assert_eq!(
+            Err(SaleError::InsufficientQuantity {
+                held: Decimal::from(4)
+            }),
+            portfolio.sell(&early)
+        );
+    }
+}
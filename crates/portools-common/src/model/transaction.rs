@@ -0,0 +1,380 @@
+use crate::model::{Currency, Lot, ReliefMethod};
+use crate::parse::ParseOptions;
+use crate::validate::{trim_and_validate_len, validate_positive, Invalid, Reason};
+use chrono::NaiveDate;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+// the kinds of activity that change the shares held within an account
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionKind {
+    Buy,
+    Sell,
+    // shares purchased with a dividend
+    DividendReinvestment,
+    // shares moved into the account from elsewhere, retaining their original cost basis
+    TransferIn,
+    // shares redeemed in order to pay a fee (e.g. an account maintenance fee)
+    Fee,
+}
+
+impl TransactionKind {
+    pub const ALL: [TransactionKind; 5] = [
+        TransactionKind::Buy,
+        TransactionKind::Sell,
+        TransactionKind::DividendReinvestment,
+        TransactionKind::TransferIn,
+        TransactionKind::Fee,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TransactionKind::Buy => "buy",
+            TransactionKind::Sell => "sell",
+            TransactionKind::DividendReinvestment => "dividend_reinvestment",
+            TransactionKind::TransferIn => "transfer_in",
+            TransactionKind::Fee => "fee",
+        }
+    }
+
+    /// Parses the name of a kind, ignoring case and treating spaces and hyphens as underscores
+    /// (e.g. "Dividend Reinvestment")
+    pub fn from_str(field: &str, value: &str) -> Result<TransactionKind, Invalid> {
+        let name = value.trim().to_ascii_lowercase().replace([' ', '-'], "_");
+        TransactionKind::ALL
+            .into_iter()
+            .find(|kind| kind.name() == name)
+            .ok_or_else(|| Invalid {
+                field: field.into(),
+                reason: Reason::NotOneOf {
+                    values: TransactionKind::ALL
+                        .iter()
+                        .map(|kind| kind.name().to_string())
+                        .collect(),
+                },
+            })
+    }
+
+    /// Whether shares are acquired, as opposed to disposed of
+    pub fn is_acquisition(&self) -> bool {
+        match self {
+            TransactionKind::Buy
+            | TransactionKind::DividendReinvestment
+            | TransactionKind::TransferIn => true,
+            TransactionKind::Sell | TransactionKind::Fee => false,
+        }
+    }
+}
+
+// a Transaction is an acquisition or disposal of shares of a security within an account
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Transaction {
+    // name of the brokerage account within which the transaction occurred
+    pub account: String,
+
+    // the symbol of the security
+    pub symbol: String,
+
+    // the date of the trade
+    pub date: NaiveDate,
+
+    pub kind: TransactionKind,
+
+    // the number of shares acquired or disposed of
+    pub quantity: Decimal,
+
+    // the per-share price, which is the cost basis of shares acquired
+    pub price: Currency,
+
+    // for a transfer in, the date that the shares were originally acquired, if other than the date
+    // of the transfer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date_acquired: Option<NaiveDate>,
+
+    // for a disposal, how the lots that the shares are disposed of from are selected, if other
+    // than first in, first out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<ReliefMethod>,
+}
+
+impl Transaction {
+    /// Converts the fields of a transaction, as they appear within a file, to a Transaction whose
+    /// price is in the given currency. An invalid currency is reported for the "currency" field.
+    /// A lot id without a method selects that specific lot.
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_str(
+        account: &str,
+        symbol: &str,
+        date: &str,
+        kind: &str,
+        quantity: &str,
+        price: &str,
+        currency: &str,
+        date_acquired: Option<&str>,
+        method: Option<&str>,
+        lot_id: Option<&str>,
+        options: &ParseOptions,
+    ) -> Result<Transaction, Invalid> {
+        let date = options.parse_date("date", date)?;
+        let kind = TransactionKind::from_str("kind", kind)?;
+        let quantity = options.parse_decimal("quantity", quantity)?;
+        let price_amount = options.parse_amount("price", price)?;
+        let price = Currency::new(price_amount, currency).map_err(|cause| Invalid {
+            field: "currency".into(),
+            ..cause
+        })?;
+        let date_acquired = match date_acquired {
+            Some(value) if !value.trim().is_empty() => {
+                Some(options.parse_date("date_acquired", value)?)
+            }
+            _ => None,
+        };
+        let lot_id = match lot_id {
+            Some(value) if !value.trim().is_empty() => {
+                Some(parse_lot_id("lot_id", value, options)?)
+            }
+            _ => None,
+        };
+        let method = match method {
+            Some(value) if !value.trim().is_empty() => {
+                Some(ReliefMethod::from_str("method", value, lot_id)?)
+            }
+            _ => lot_id.map(|lot_id| ReliefMethod::SpecificLot { lot_id }),
+        };
+        Transaction::new(
+            account,
+            symbol,
+            date,
+            kind,
+            quantity,
+            price,
+            date_acquired,
+            method,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        account: &str,
+        symbol: &str,
+        date: NaiveDate,
+        kind: TransactionKind,
+        quantity: Decimal,
+        price: Currency,
+        date_acquired: Option<NaiveDate>,
+        method: Option<ReliefMethod>,
+    ) -> Result<Transaction, Invalid> {
+        let account = trim_and_validate_len(
+            "account",
+            account,
+            Lot::MIN_ACCOUNT_LEN,
+            Lot::MAX_ACCOUNT_LEN,
+        )?;
+        let symbol =
+            trim_and_validate_len("symbol", symbol, Lot::MIN_SYMBOL_LEN, Lot::MAX_SYMBOL_LEN)?;
+        validate_positive("quantity", &quantity)?;
+        validate_positive("price", &price.amount)?;
+        Ok(Transaction {
+            account,
+            symbol,
+            date,
+            kind,
+            quantity,
+            price,
+            // only shares transferred in were acquired prior to the transaction
+            date_acquired: date_acquired.filter(|_| kind == TransactionKind::TransferIn),
+            // only shares that are disposed of are relieved from lots
+            method: method.filter(|_| !kind.is_acquisition()),
+        })
+    }
+
+    /// The date that the shares acquired by the transaction are considered to have been acquired
+    pub fn acquisition_date(&self) -> NaiveDate {
+        self.date_acquired.unwrap_or(self.date)
+    }
+}
+
+// parses the id of a lot, which is a whole number
+fn parse_lot_id(field: &str, value: &str, options: &ParseOptions) -> Result<u32, Invalid> {
+    let id = options.parse_decimal(field, value)?;
+    id.fract()
+        .is_zero()
+        .then(|| id.to_u32())
+        .flatten()
+        .ok_or_else(|| {
+            Invalid::parse_decimal_error(field, rust_decimal::Error::ConversionTo("u32".into()))
+        })
+}
+
+// the transactions of a portfolio, which its lots are derived from
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PortfolioTransactions {
+    // the id of the portfolio
+    pub id: u32,
+    // the version of the portfolio whose lots were derived from the transactions, which are no
+    // longer those of the portfolio once it is of any other version. Transactions stored before
+    // they were versioned have a version of 0.
+    #[serde(default)]
+    pub version: u32,
+    pub transactions: Vec<Transaction>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{Currency, ReliefMethod, Transaction, TransactionKind, USD};
+    use crate::parse::ParseOptions;
+    use crate::unit_test_util::fixture;
+    use crate::validate::Reason;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+    use test_util::assertion::assert_ok_eq;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn kind_from_str() {
+        assert_ok_eq(
+            &TransactionKind::DividendReinvestment,
+            &TransactionKind::from_str("kind", " Dividend Reinvestment"),
+        );
+        assert_ok_eq(
+            &TransactionKind::TransferIn,
+            &TransactionKind::from_str("kind", "transfer-in"),
+        );
+        let invalid = TransactionKind::from_str("kind", "gift").unwrap_err();
+        assert_eq!("kind", invalid.field);
+        assert_eq!("not_one_of", invalid.reason.code());
+        assert_eq!(
+            "kind is not one of buy, sell, dividend_reinvestment, transfer_in, fee",
+            invalid.to_string()
+        );
+    }
+
+    #[test]
+    fn from_str_valid() {
+        let transaction = Transaction::from_str(
+            "Taxable",
+            "VOO",
+            "2023/03/27",
+            "transfer in",
+            "1.5",
+            "$100.47",
+            "usd",
+            Some("2020/01/02"),
+            None,
+            None,
+            &ParseOptions::default(),
+        );
+        assert_ok_eq(
+            &Transaction {
+                account: "Taxable".into(),
+                symbol: "VOO".into(),
+                date: date(2023, 3, 27),
+                kind: TransactionKind::TransferIn,
+                quantity: "1.5".parse().unwrap(),
+                price: Currency::new("100.47".parse().unwrap(), USD).unwrap(),
+                date_acquired: Some(date(2020, 1, 2)),
+                method: None,
+            },
+            &transaction,
+        );
+        assert_eq!(date(2020, 1, 2), transaction.unwrap().acquisition_date());
+    }
+
+    #[test]
+    fn from_str_invalid() {
+        let from_str = |quantity: &str, currency: &str, date_acquired: Option<&str>| {
+            Transaction::from_str(
+                "Taxable",
+                "VOO",
+                "2023/03/27",
+                "buy",
+                quantity,
+                "1",
+                currency,
+                date_acquired,
+                None,
+                None,
+                &ParseOptions::default(),
+            )
+            .unwrap_err()
+        };
+        assert_eq!("quantity", from_str("0", USD, None).field);
+        let invalid = from_str("1", "XYZ", None);
+        assert_eq!("currency", invalid.field);
+        assert_eq!(Reason::UnknownCurrency, invalid.reason);
+        assert_eq!(
+            "date_acquired",
+            from_str("1", USD, Some("2023-13-01")).field
+        );
+    }
+
+    #[test]
+    fn new_ignores_date_acquired_unless_transfer_in() {
+        let transaction = Transaction::new(
+            "Taxable",
+            "VOO",
+            date(2023, 3, 27),
+            TransactionKind::Buy,
+            Decimal::ONE,
+            fixture::currency(),
+            Some(date(2020, 1, 2)),
+            Some(ReliefMethod::Lifo),
+        )
+        .unwrap();
+        assert_eq!(None, transaction.date_acquired);
+        assert_eq!(date(2023, 3, 27), transaction.acquisition_date());
+        // nor is a method of relief for an acquisition
+        assert_eq!(None, transaction.method);
+    }
+
+    #[test]
+    fn from_str_with_method() {
+        let from_str = |method: Option<&str>, lot_id: Option<&str>| {
+            Transaction::from_str(
+                "Taxable",
+                "VOO",
+                "2023/03/27",
+                "sell",
+                "1",
+                "1",
+                USD,
+                None,
+                method,
+                lot_id,
+                &ParseOptions::default(),
+            )
+            .map(|transaction| transaction.method)
+        };
+        assert_ok_eq(&None, &from_str(Some(" "), None));
+        assert_ok_eq(
+            &Some(ReliefMethod::LowestGain),
+            &from_str(Some("Lowest Gain"), None),
+        );
+        assert_ok_eq(
+            &Some(ReliefMethod::SpecificLot { lot_id: 3 }),
+            &from_str(Some("specific_lot"), Some("3")),
+        );
+        // a lot id alone selects that specific lot
+        assert_ok_eq(
+            &Some(ReliefMethod::SpecificLot { lot_id: 3 }),
+            &from_str(None, Some("3")),
+        );
+
+        let invalid = from_str(Some("specific_lot"), None).unwrap_err();
+        assert_eq!("lot_id", invalid.field);
+        assert_eq!(Reason::Required, invalid.reason);
+        assert_eq!("lot_id", from_str(None, Some("1.5")).unwrap_err().field);
+        let invalid = from_str(Some("average"), None).unwrap_err();
+        assert_eq!("method", invalid.field);
+        assert_eq!(
+            "method is not one of fifo, lifo, hifo, lowest_gain, specific_lot",
+            invalid.to_string()
+        );
+    }
+}
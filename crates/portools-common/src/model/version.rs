@@ -0,0 +1,119 @@
+use crate::model::{CorporateActionId, Lot, Portfolio};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+// an immutable snapshot of the lots of a portfolio, as of one of its versions
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PortfolioVersion {
+    pub portfolio_id: u32,
+    pub version: u32,
+    pub created: DateTime<Utc>,
+
+    // the name of the file that the lots were uploaded from, if one was provided
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+
+    // the version whose lots were restored in order to create this version, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restored_from: Option<u32>,
+
+    pub next_lot_id: u32,
+    pub lots: Vec<Lot>,
+
+    // the corporate actions that had been applied to the lots
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub corporate_actions: Vec<CorporateActionId>,
+}
+
+// describes a version, without including its lots
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PortfolioVersionInfo {
+    pub version: u32,
+    pub created: DateTime<Utc>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restored_from: Option<u32>,
+
+    pub num_lots: usize,
+}
+
+impl PortfolioVersion {
+    // a snapshot of the portfolio, as it is now
+    pub fn new(portfolio: &Portfolio, source: Option<String>) -> PortfolioVersion {
+        PortfolioVersion {
+            portfolio_id: portfolio.id,
+            version: portfolio.version,
+            created: Utc::now(),
+            source,
+            restored_from: None,
+            next_lot_id: portfolio.next_lot_id,
+            lots: portfolio.lots.clone(),
+            corporate_actions: portfolio.corporate_actions.clone(),
+        }
+    }
+
+    // Creates the portfolio that results from restoring this version over the current portfolio.
+    //
+    // Since ids of lots are never reused by a portfolio, the restored portfolio continues from
+    // the greater of the two next lot ids. Any lot of the version that has no id is assigned one
+    pub fn restore(&self, current: &Portfolio) -> Portfolio {
+        let mut portfolio = Portfolio {
+            id: current.id,
+            lots: self.lots.clone(),
+            next_lot_id: self.next_lot_id.max(current.next_lot_id),
+            version: current.version + 1,
+            last_updated: None,
+            // those applied to the lots of the version, rather than to the current lots
+            corporate_actions: self.corporate_actions.clone(),
+        };
+        portfolio.assign_lot_ids();
+        portfolio
+    }
+
+    pub fn get_info(&self) -> PortfolioVersionInfo {
+        PortfolioVersionInfo {
+            version: self.version,
+            created: self.created,
+            source: self.source.clone(),
+            restored_from: self.restored_from,
+            num_lots: self.lots.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{Portfolio, PortfolioVersion};
+    use crate::unit_test_util::fixture;
+
+    #[test]
+    fn restore() {
+        let mut old = Portfolio::new(1, vec![fixture::lot(), fixture::lot()]);
+        old.version = 2;
+        let old_version = PortfolioVersion::new(&old, Some("old.csv".into()));
+
+        let mut current = old.clone();
+        current.add_lots(vec![fixture::lot()]);
+        current.remove_lot(1);
+        current.version = 5;
+
+        let restored = old_version.restore(&current);
+        assert_eq!(old.lots, restored.lots);
+        assert_eq!(6, restored.version);
+        assert_eq!(4, restored.next_lot_id);
+    }
+
+    #[test]
+    fn get_info() {
+        let mut portfolio = Portfolio::new(1, vec![fixture::lot()]);
+        portfolio.version = 3;
+        let info = PortfolioVersion::new(&portfolio, Some("lots.csv".into())).get_info();
+        assert_eq!(3, info.version);
+        assert_eq!(Some("lots.csv".to_string()), info.source);
+        assert_eq!(None, info.restored_from);
+        assert_eq!(1, info.num_lots);
+    }
+}
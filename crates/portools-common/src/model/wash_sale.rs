@@ -0,0 +1,497 @@
+use crate::model::{
+    CorporateAction, Currency, CurrencyError, Ledger, LedgerError, RealizedGain, Transaction,
+    TransactionKind,
+};
+use chrono::{Days, NaiveDate};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+// the number of days before and after a sale at a loss within which a purchase of substantially
+// identical shares disallows the loss
+const WASH_SALE_DAYS: u64 = 30;
+
+// groups of symbols that are substantially identical to one another (e.g. share classes of the same
+// fund), such that purchasing any symbol of a group replaces shares of another that were sold
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SubstantiallyIdentical(pub Vec<Vec<String>>);
+
+impl SubstantiallyIdentical {
+    /// Whether the symbols are the same, or are within the same group
+    pub fn contains(&self, symbol: &str, other: &str) -> bool {
+        symbol == other
+            || self.0.iter().any(|group| {
+                group.iter().any(|member| member == symbol)
+                    && group.iter().any(|member| member == other)
+            })
+    }
+}
+
+// a loss that is disallowed, as substantially identical shares were purchased within 30 days
+// before or after the sale
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WashSale {
+    // the loss realized by the sale
+    pub loss: RealizedGain,
+
+    // the number of shares sold that were replaced, which is at most the number sold
+    pub quantity: Decimal,
+
+    // the portion of the loss that is disallowed, as a positive amount
+    pub disallowed_loss: Currency,
+
+    // the purchases that replaced the shares sold, in order of date
+    pub replacements: Vec<BasisAdjustment>,
+}
+
+// an adjustment to the cost basis of shares purchased to replace shares sold at a loss
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BasisAdjustment {
+    // the index of the purchase within the transactions
+    pub index: usize,
+    pub account: String,
+    pub symbol: String,
+    pub date_acquired: NaiveDate,
+
+    // the number of the purchased shares that replaced shares sold
+    pub quantity: Decimal,
+
+    // the per-share amount that is added to the cost basis of the replacement shares
+    pub cost_basis_adjustment: Currency,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum WashSaleError {
+    LedgerError { cause: LedgerError },
+    AdjustmentError { cause: CurrencyError<Decimal> },
+    // the number of shares that purchased or sold shares became per the corporate actions overflowed
+    Overflow,
+}
+
+/// Finds the sales among the transactions whose losses are disallowed, as shares of the same
+/// symbol, or of one that is substantially identical, were purchased (or acquired by reinvesting a
+/// dividend) within 30 days before or after the sale. Purchases within any account of the
+/// portfolio replace the shares sold, including those within tax-advantaged accounts such as IRAs.
+/// Losses are matched to the purchases in order of date, where each purchased share replaces at
+/// most one share sold, and shares sold together never replace one another. The losses are those
+/// realized per the cost basis of the shares sold, before any adjustment, where the transactions
+/// are replayed along with the corporate actions. The shares of a purchase and of a sale are
+/// compared as of after every corporate action, so that a split between them is accounted for,
+/// whereas the quantity and adjustment of each replacement are per share purchased.
+pub fn find_wash_sales(
+    transactions: &[Transaction],
+    corporate_actions: &[CorporateAction],
+    identical: &SubstantiallyIdentical,
+) -> Result<Vec<WashSale>, WashSaleError> {
+    let ledger = Ledger::replay(transactions, corporate_actions)
+        .map_err(|cause| WashSaleError::LedgerError { cause })?;
+    let mut actions: Vec<&CorporateAction> = corporate_actions.iter().collect();
+    actions.sort_by_key(|action| action.effective_date);
+    let shares = Shares { actions };
+    // each purchase, in order of date
+    let mut purchases = transactions
+        .iter()
+        .enumerate()
+        .filter(|(_, transaction)| is_purchase(transaction))
+        .map(|(index, transaction)| {
+            let (symbol, per_share) = shares.as_of_last(&transaction.symbol, transaction.date)?;
+            let remaining = transaction
+                .quantity
+                .checked_mul(per_share)
+                .ok_or(WashSaleError::Overflow)?;
+            Ok(Purchase {
+                index,
+                symbol,
+                per_share,
+                remaining,
+            })
+        })
+        .collect::<Result<Vec<Purchase>, WashSaleError>>()?;
+    purchases.sort_by_key(|purchase| transactions[purchase.index].date);
+
+    let gains = ledger.realized_gains();
+    let mut wash_sales = Vec::new();
+    let mut start = 0;
+    while start < gains.len() {
+        // the gains realized by the same sale, which were realized consecutively
+        let end = gains[start..]
+            .iter()
+            .position(|gain| !is_same_sale(gain, &gains[start]))
+            .map_or(gains.len(), |len| start + len);
+        let sale = &gains[start..end];
+        start = end;
+        for gain in sale {
+            exclude_shares_sold(&shares, &mut purchases, gain)?;
+        }
+        for loss in sale.iter().filter(|gain| gain.gain.amount < Decimal::ZERO) {
+            let (quantity, replacements) =
+                replace(transactions, &shares, &mut purchases, loss, identical)?;
+            if replacements.is_empty() {
+                continue;
+            }
+            let disallowed_loss = loss
+                .gain
+                .negate()
+                .multiply(&quantity)
+                .and_then(|loss_replaced| loss_replaced.divide(&loss.quantity))
+                .map_err(|cause| WashSaleError::AdjustmentError { cause })?;
+            wash_sales.push(WashSale {
+                loss: loss.clone(),
+                quantity,
+                disallowed_loss,
+                replacements,
+            });
+        }
+    }
+    Ok(wash_sales)
+}
+
+// a purchase, whose shares are counted as of after every corporate action
+struct Purchase {
+    // the index of the purchase within the transactions
+    index: usize,
+    // the symbol of the shares after every corporate action
+    symbol: String,
+    // the number of shares, after every corporate action, per share purchased
+    per_share: Decimal,
+    // the number of shares that have yet to replace shares sold
+    remaining: Decimal,
+}
+
+// the corporate actions, in order of effective date, which shares held as of a date are subject to
+struct Shares<'a> {
+    actions: Vec<&'a CorporateAction>,
+}
+
+impl Shares<'_> {
+    // The symbol of shares held as of the date, after every corporate action, along with the
+    // number of shares that each of them became
+    fn as_of_last(
+        &self,
+        symbol: &str,
+        date: NaiveDate,
+    ) -> Result<(String, Decimal), WashSaleError> {
+        let mut symbol = symbol.to_string();
+        let mut per_share = Decimal::ONE;
+        for action in self.actions.iter() {
+            if action.effective_date <= date || action.symbol != symbol {
+                continue;
+            }
+            per_share = per_share
+                .checked_mul(action.ratio)
+                .ok_or(WashSaleError::Overflow)?;
+            if let Some(new_symbol) = &action.new_symbol {
+                symbol = new_symbol.clone();
+            }
+        }
+        Ok((symbol, per_share))
+    }
+}
+
+fn is_same_sale(gain: &RealizedGain, other: &RealizedGain) -> bool {
+    gain.account == other.account
+        && gain.symbol == other.symbol
+        && gain.date_sold == other.date_sold
+}
+
+fn is_purchase(transaction: &Transaction) -> bool {
+    matches!(
+        transaction.kind,
+        TransactionKind::Buy | TransactionKind::DividendReinvestment
+    )
+}
+
+// Removes the shares sold from the purchase of the lot that they were sold from, so that shares
+// sold together do not replace one another
+fn exclude_shares_sold(
+    shares: &Shares,
+    purchases: &mut [Purchase],
+    gain: &RealizedGain,
+) -> Result<(), WashSaleError> {
+    // the lot acquired by a transaction is identified by its (1-based) position
+    let purchase = purchases
+        .iter_mut()
+        .find(|purchase| u32::try_from(purchase.index + 1).ok() == gain.lot_id);
+    if let Some(purchase) = purchase {
+        let (_, per_share) = shares.as_of_last(&gain.symbol, gain.date_sold)?;
+        let sold = gain
+            .quantity
+            .checked_mul(per_share)
+            .ok_or(WashSaleError::Overflow)?;
+        purchase.remaining -= sold.min(purchase.remaining);
+    }
+    Ok(())
+}
+
+// Matches the shares sold at a loss to the earliest purchases of substantially identical shares
+// within the window of the sale, which have yet to replace other shares sold. Returns the number
+// of the shares sold that were replaced, along with the replacements.
+fn replace(
+    transactions: &[Transaction],
+    shares: &Shares,
+    purchases: &mut [Purchase],
+    loss: &RealizedGain,
+    identical: &SubstantiallyIdentical,
+) -> Result<(Decimal, Vec<BasisAdjustment>), WashSaleError> {
+    let start = loss
+        .date_sold
+        .checked_sub_days(Days::new(WASH_SALE_DAYS))
+        .unwrap_or(NaiveDate::MIN);
+    let end = loss
+        .date_sold
+        .checked_add_days(Days::new(WASH_SALE_DAYS))
+        .unwrap_or(NaiveDate::MAX);
+    let (symbol, per_share_sold) = shares.as_of_last(&loss.symbol, loss.date_sold)?;
+    let adjustment_error = |cause| WashSaleError::AdjustmentError { cause };
+    // the loss per share, after every corporate action
+    let loss_per_share = loss
+        .gain
+        .negate()
+        .divide(&loss.quantity)
+        .and_then(|loss_per_share| loss_per_share.divide(&per_share_sold))
+        .map_err(adjustment_error)?;
+    let mut unreplaced = loss
+        .quantity
+        .checked_mul(per_share_sold)
+        .ok_or(WashSaleError::Overflow)?;
+    let mut replaced = Decimal::ZERO;
+    let mut replacements = Vec::new();
+    for purchase in purchases.iter_mut() {
+        if unreplaced.is_zero() {
+            break;
+        }
+        let transaction = &transactions[purchase.index];
+        if purchase.remaining.is_zero()
+            || transaction.date < start
+            || transaction.date > end
+            || !identical.contains(&symbol, &purchase.symbol)
+        {
+            continue;
+        }
+        let quantity = unreplaced.min(purchase.remaining);
+        purchase.remaining -= quantity;
+        unreplaced -= quantity;
+        replaced += quantity;
+        replacements.push(BasisAdjustment {
+            index: purchase.index,
+            account: transaction.account.clone(),
+            symbol: transaction.symbol.clone(),
+            date_acquired: transaction.date,
+            quantity: quantity / purchase.per_share,
+            cost_basis_adjustment: loss_per_share
+                .multiply(&purchase.per_share)
+                .map_err(adjustment_error)?,
+        });
+    }
+    Ok((replaced / per_share_sold, replacements))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{
+        find_wash_sales, CorporateAction, Currency, ReliefMethod, SubstantiallyIdentical,
+        Transaction, TransactionKind, USD,
+    };
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+
+    fn usd(amount: i64) -> Currency {
+        Currency::new(Decimal::from(amount), USD).unwrap()
+    }
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, month, day).unwrap()
+    }
+
+    fn transaction(
+        account: &str,
+        symbol: &str,
+        date: NaiveDate,
+        kind: TransactionKind,
+        quantity: i64,
+        price: i64,
+    ) -> Transaction {
+        Transaction::new(
+            account,
+            symbol,
+            date,
+            kind,
+            Decimal::from(quantity),
+            usd(price),
+            None,
+            None,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn contains() {
+        let identical = SubstantiallyIdentical(vec![vec!["VOO".into(), "IVV".into()]]);
+        assert!(identical.contains("VOO", "VOO"));
+        assert!(identical.contains("BND", "BND"));
+        assert!(identical.contains("IVV", "VOO"));
+        assert!(!identical.contains("VOO", "VTI"));
+    }
+
+    #[test]
+    fn find_replacements_across_accounts() {
+        let transactions = [
+            transaction("Taxable", "VOO", date(1, 3), TransactionKind::Buy, 10, 400),
+            // shares sold together do not replace one another
+            transaction("Taxable", "VOO", date(3, 1), TransactionKind::Buy, 2, 380),
+            transaction(
+                "Taxable",
+                "VOO",
+                date(3, 15),
+                TransactionKind::Sell,
+                12,
+                350,
+            ),
+            transaction("IRA", "VOO", date(4, 10), TransactionKind::Buy, 3, 340),
+            transaction(
+                "Taxable",
+                "VOO",
+                date(4, 14),
+                TransactionKind::DividendReinvestment,
+                10,
+                345,
+            ),
+            // more than 30 days after the sale
+            transaction("Taxable", "VOO", date(4, 15), TransactionKind::Buy, 1, 345),
+        ];
+        let wash_sales = find_wash_sales(&transactions, &[], &Default::default()).unwrap();
+        assert_eq!(2, wash_sales.len());
+
+        // each share sold from the first lot lost 50, and is replaced first by those of the IRA
+        let first = &wash_sales[0];
+        assert_eq!(date(1, 3), first.loss.date_acquired);
+        assert_eq!(Decimal::from(10), first.quantity);
+        assert_eq!(usd(500), first.disallowed_loss);
+        let replacements: Vec<(usize, &str, Decimal, Currency)> = first
+            .replacements
+            .iter()
+            .map(|replacement| {
+                (
+                    replacement.index,
+                    replacement.account.as_str(),
+                    replacement.quantity,
+                    replacement.cost_basis_adjustment.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (3, "IRA", Decimal::from(3), usd(50)),
+                (4, "Taxable", Decimal::from(7), usd(50)),
+            ],
+            replacements
+        );
+
+        // the remaining reinvested shares replace those of the second lot
+        let second = &wash_sales[1];
+        assert_eq!(date(3, 1), second.loss.date_acquired);
+        assert_eq!(Decimal::TWO, second.quantity);
+        assert_eq!(usd(60), second.disallowed_loss);
+        assert_eq!(1, second.replacements.len());
+        assert_eq!(4, second.replacements[0].index);
+        assert_eq!(usd(30), second.replacements[0].cost_basis_adjustment);
+    }
+
+    #[test]
+    fn find_partial_replacement_by_substantially_identical() {
+        let transactions = [
+            transaction("Taxable", "VOO", date(1, 3), TransactionKind::Buy, 4, 400),
+            transaction("Roth IRA", "IVV", date(2, 20), TransactionKind::Buy, 1, 390),
+            transaction("Taxable", "VOO", date(3, 15), TransactionKind::Sell, 4, 370),
+            // not substantially identical
+            transaction("Taxable", "VTI", date(3, 16), TransactionKind::Buy, 4, 200),
+        ];
+        assert_eq!(
+            Ok(vec![]),
+            find_wash_sales(&transactions, &[], &Default::default())
+        );
+        let identical = SubstantiallyIdentical(vec![vec!["VOO".into(), "IVV".into()]]);
+        let wash_sales = find_wash_sales(&transactions, &[], &identical).unwrap();
+        assert_eq!(1, wash_sales.len());
+        assert_eq!(Decimal::ONE, wash_sales[0].quantity);
+        assert_eq!(usd(30), wash_sales[0].disallowed_loss);
+        assert_eq!("IVV", wash_sales[0].replacements[0].symbol);
+    }
+
+    #[test]
+    fn gains_are_not_wash_sales() {
+        let transactions = [
+            transaction("Taxable", "VOO", date(3, 1), TransactionKind::Buy, 2, 300),
+            transaction("Taxable", "VOO", date(3, 15), TransactionKind::Sell, 1, 320),
+            transaction("Taxable", "VOO", date(3, 20), TransactionKind::Buy, 1, 310),
+        ];
+        assert_eq!(
+            Ok(vec![]),
+            find_wash_sales(&transactions, &[], &Default::default())
+        );
+    }
+
+    #[test]
+    fn exclude_lot_sold_among_purchases_on_same_day() {
+        let transactions = [
+            transaction("Taxable", "VOO", date(3, 1), TransactionKind::Buy, 5, 300),
+            transaction("Taxable", "VOO", date(3, 1), TransactionKind::Buy, 5, 400),
+            Transaction::new(
+                "Taxable",
+                "VOO",
+                date(3, 15),
+                TransactionKind::Sell,
+                Decimal::from(5),
+                usd(350),
+                None,
+                Some(ReliefMethod::SpecificLot { lot_id: 2 }),
+            )
+            .unwrap(),
+        ];
+        // the shares of the first lot, which were not sold, replace those sold from the second
+        let wash_sales = find_wash_sales(&transactions, &[], &Default::default()).unwrap();
+        assert_eq!(1, wash_sales.len());
+        assert_eq!(Some(2), wash_sales[0].loss.lot_id);
+        assert_eq!(Decimal::from(5), wash_sales[0].quantity);
+        assert_eq!(usd(250), wash_sales[0].disallowed_loss);
+        assert_eq!(1, wash_sales[0].replacements.len());
+        assert_eq!(0, wash_sales[0].replacements[0].index);
+    }
+
+    #[test]
+    fn find_replacements_across_split() {
+        let transactions = [
+            transaction("Taxable", "VOO", date(1, 3), TransactionKind::Buy, 10, 400),
+            transaction("Roth IRA", "VOO", date(3, 1), TransactionKind::Buy, 5, 390),
+            transaction(
+                "Taxable",
+                "VOO",
+                date(3, 15),
+                TransactionKind::Sell,
+                20,
+                180,
+            ),
+        ];
+        let split = CorporateAction {
+            symbol: "VOO".into(),
+            effective_date: date(3, 10),
+            ratio: Decimal::TWO,
+            new_symbol: None,
+            cash_in_lieu: None,
+        };
+        let wash_sales = find_wash_sales(&transactions, &[split], &Default::default()).unwrap();
+        assert_eq!(1, wash_sales.len());
+
+        // each of the 20 shares sold lost 20, and the 5 shares purchased before the split replace
+        // 10 of them
+        let wash_sale = &wash_sales[0];
+        assert_eq!(Decimal::from(20), wash_sale.loss.quantity);
+        assert_eq!(Decimal::from(10), wash_sale.quantity);
+        assert_eq!(usd(200), wash_sale.disallowed_loss);
+        assert_eq!(1, wash_sale.replacements.len());
+        let replacement = &wash_sale.replacements[0];
+        assert_eq!(1, replacement.index);
+        assert_eq!(Decimal::from(5), replacement.quantity);
+        assert_eq!(usd(40), replacement.cost_basis_adjustment);
+    }
+}
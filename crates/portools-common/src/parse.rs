@@ -0,0 +1,248 @@
+use crate::validate::{Invalid, Reason};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use rusty_money::MoneyError;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Options for parsing the dates and numbers of lots, as they appear within the files exported
+/// by brokers
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ParseOptions {
+    // the formats that dates may have (per chrono::format::strftime), which are tried in order.
+    // A date that has more than one of the formats, but is a different date per each of them
+    // (e.g. 03/04/2023 per %m/%d/%Y and %d/%m/%Y), is rejected as ambiguous
+    pub date_formats: Vec<String>,
+
+    // the character separating the whole and fractional parts of numbers
+    pub decimal_separator: char,
+
+    // the characters that may separate the whole part of numbers into groups of three digits,
+    // which are ignored. Whichever of them is the decimal separator is not taken to be a thousands
+    // separator
+    pub thousands_separators: Vec<char>,
+
+    // the symbols that may precede or follow amounts (e.g. "$"), which are ignored
+    pub currency_symbols: Vec<String>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            date_formats: vec!["%Y/%m/%d".into(), "%Y-%m-%d".into(), "%m/%d/%Y".into()],
+            decimal_separator: '.',
+            thousands_separators: vec![','],
+            currency_symbols: ["$", "€", "£", "¥"].map(String::from).to_vec(),
+        }
+    }
+}
+
+impl ParseOptions {
+    pub fn parse_date(&self, field: &str, value: &str) -> Result<NaiveDate, Invalid> {
+        let value = value.trim();
+        let mut parsed: Option<(&str, NaiveDate)> = None;
+        let mut last_error = None;
+        for format in &self.date_formats {
+            match (NaiveDate::parse_from_str(value, format), parsed) {
+                (Ok(date), None) => parsed = Some((format, date)),
+                (Ok(date), Some((first_format, first_date))) if date != first_date => {
+                    return Err(Invalid {
+                        field: field.into(),
+                        reason: Reason::AmbiguousDate {
+                            formats: vec![first_format.into(), format.into()],
+                        },
+                    });
+                }
+                (Ok(_), Some(_)) => {}
+                (Err(error), _) => last_error = Some(error),
+            }
+        }
+        match (parsed, last_error) {
+            (Some((_, date)), _) => Ok(date),
+            (None, Some(error)) => Err(Invalid::parse_date_error(field, error)),
+            (None, None) => Err(Invalid::required_str("date_formats")),
+        }
+    }
+
+    /// Parses a number, such as a quantity. Negative numbers may be enclosed in parentheses,
+    /// as is common within financial statements.
+    pub fn parse_decimal(&self, field: &str, value: &str) -> Result<Decimal, Invalid> {
+        self.parse_number(value)
+            .map_err(|error| Invalid::parse_decimal_error(field, error))
+    }
+
+    /// Parses an amount of money, which may additionally be preceded or followed by one of the
+    /// currency symbols
+    pub fn parse_amount(&self, field: &str, value: &str) -> Result<Decimal, Invalid> {
+        let mut value = value.to_string();
+        for symbol in &self.currency_symbols {
+            value = value.replace(symbol.as_str(), "");
+        }
+        self.parse_number(&value)
+            .map_err(|_| Invalid::parse_money_error(field, MoneyError::InvalidAmount))
+    }
+
+    fn parse_number(&self, value: &str) -> Result<Decimal, rust_decimal::Error> {
+        let value = value.trim();
+        let (value, negative) = match value.strip_prefix('(').and_then(|v| v.strip_suffix(')')) {
+            Some(value) => (value, true),
+            None => (value, false),
+        };
+        let (whole, fraction) = match value.split_once(self.decimal_separator) {
+            Some((whole, fraction)) => (whole, Some(fraction)),
+            None => (value, None),
+        };
+        let digits = whole.trim_start_matches(['-', '+']);
+        let sign = &whole[..whole.len() - digits.len()];
+        let mut value = format!("{sign}{}", self.remove_thousands_separators(digits)?);
+        if let Some(fraction) = fraction {
+            if !fraction.chars().all(|c| c.is_ascii_digit()) {
+                return Err(rust_decimal::Error::ErrorString(format!(
+                    "invalid fractional part: {fraction}"
+                )));
+            }
+            value = format!("{value}.{fraction}");
+        }
+        let number = Decimal::from_str(&value)?;
+        Ok(if negative { -number } else { number })
+    }
+
+    // the digits of the whole part of a number, without its thousands separators, provided that
+    // they separate groups of three digits (e.g. "1,234" but not "1,5")
+    fn remove_thousands_separators(&self, whole: &str) -> Result<String, rust_decimal::Error> {
+        let is_separator =
+            |c: char| c != self.decimal_separator && self.thousands_separators.contains(&c);
+        let groups: Vec<&str> = whole.split(is_separator).collect();
+        let valid = groups.iter().enumerate().all(|(i, group)| {
+            let len_valid = match (i, groups.len()) {
+                // without separators, any number of digits
+                (_, 1) => true,
+                (0, _) => (1..=3).contains(&group.len()),
+                _ => group.len() == 3,
+            };
+            len_valid && group.chars().all(|c| c.is_ascii_digit())
+        });
+        if valid {
+            Ok(groups.concat())
+        } else {
+            Err(rust_decimal::Error::ErrorString(format!(
+                "invalid whole part: {whole}"
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::ParseOptions;
+    use crate::validate::Reason;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn parse_date_tries_each_format() {
+        let options = ParseOptions::default();
+        for value in ["2023/03/27", "2023-03-27", "03/27/2023", " 3/27/2023 "] {
+            assert_eq!(Ok(date(2023, 3, 27)), options.parse_date("date", value));
+        }
+        let invalid = options.parse_date("date", "27.03.2023").unwrap_err();
+        assert_eq!("parse_date_error", invalid.reason.code());
+    }
+
+    #[test]
+    fn parse_date_detects_ambiguity() {
+        let options = ParseOptions {
+            date_formats: vec!["%m/%d/%Y".into(), "%d/%m/%Y".into()],
+            ..Default::default()
+        };
+        // only valid as MM/DD/YYYY or as DD/MM/YYYY
+        assert_eq!(
+            Ok(date(2023, 3, 27)),
+            options.parse_date("date", "03/27/2023")
+        );
+        assert_eq!(
+            Ok(date(2023, 3, 27)),
+            options.parse_date("date", "27/03/2023")
+        );
+        // the same date either way
+        assert_eq!(
+            Ok(date(2023, 3, 3)),
+            options.parse_date("date", "03/03/2023")
+        );
+
+        let invalid = options.parse_date("date", "03/04/2023").unwrap_err();
+        assert_eq!("date", invalid.field);
+        assert_eq!(
+            Reason::AmbiguousDate {
+                formats: vec!["%m/%d/%Y".into(), "%d/%m/%Y".into()]
+            },
+            invalid.reason
+        );
+    }
+
+    #[test]
+    fn parse_decimal_with_separators() {
+        let options = ParseOptions::default();
+        assert_eq!(
+            Ok(Decimal::new(123456, 2)),
+            options.parse_decimal("quantity", "1,234.56")
+        );
+        assert_eq!(
+            Ok(Decimal::new(-1234567, 0)),
+            options.parse_decimal("quantity", "(1,234,567)")
+        );
+        assert_eq!(
+            Ok(Decimal::new(-5, 1)),
+            options.parse_decimal("quantity", "-.5")
+        );
+        // the separators must separate groups of three digits
+        for value in ["1,5", "1,23,456", "1234,567", ",123", "1.234.56", "1 234"] {
+            let invalid = options.parse_decimal("quantity", value).unwrap_err();
+            assert_eq!("parse_decimal_error", invalid.reason.code(), "{value}");
+        }
+
+        let european = ParseOptions {
+            decimal_separator: ',',
+            thousands_separators: vec!['.', ' '],
+            ..Default::default()
+        };
+        assert_eq!(
+            Ok(Decimal::new(123456, 2)),
+            european.parse_decimal("quantity", "1.234,56")
+        );
+        assert_eq!(
+            Ok(Decimal::new(-1234, 0)),
+            european.parse_decimal("quantity", "(1 234)")
+        );
+        for value in ["1,2,3", "0.5", "1,234.56"] {
+            let invalid = european.parse_decimal("quantity", value).unwrap_err();
+            assert_eq!("parse_decimal_error", invalid.reason.code(), "{value}");
+        }
+    }
+
+    #[test]
+    fn parse_amount_with_currency_symbols() {
+        let options = ParseOptions::default();
+        assert_eq!(
+            Ok(Decimal::new(123456, 2)),
+            options.parse_amount("cost_basis", "$1,234.56")
+        );
+        assert_eq!(
+            Ok(Decimal::new(-10047, 2)),
+            options.parse_amount("cost_basis", "($100.47)")
+        );
+        assert_eq!(
+            Ok(Decimal::new(10047, 2)),
+            options.parse_amount("cost_basis", "100.47 €")
+        );
+        let invalid = options.parse_amount("cost_basis", "USD").unwrap_err();
+        assert_eq!("parse_money_error", invalid.reason.code());
+        // currency symbols are not expected of numbers other than amounts
+        assert!(options.parse_decimal("quantity", "$1").is_err());
+    }
+}
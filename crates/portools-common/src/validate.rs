@@ -1,6 +1,7 @@
 use chrono::ParseError;
 use rust_decimal::Decimal;
 use rusty_money::MoneyError;
+use std::fmt::{Display, Formatter};
 
 #[derive(Debug, PartialEq)]
 pub struct Invalid {
@@ -42,6 +43,12 @@ impl Invalid {
     }
 }
 
+impl Display for Invalid {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.field, self.reason)
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Reason {
     Required,
@@ -51,6 +58,52 @@ pub enum Reason {
     ParseDecimalError { cause: rust_decimal::Error },
     ParseDateError { cause: ParseError },
     ParseMoneyError { cause: MoneyError },
+    // the date is a different date per each of the formats
+    AmbiguousDate { formats: Vec<String> },
+    // the currency is not one of those of ISO 4217
+    UnknownCurrency,
+    // the value is not one of those expected
+    NotOneOf { values: Vec<String> },
+}
+
+impl Reason {
+    /// Returns a stable, machine readable code for this reason, suitable for inclusion
+    /// in API responses.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Reason::Required => "required",
+            Reason::MustBePositive => "must_be_positive",
+            Reason::MustHaveLongerLen => "must_have_longer_len",
+            Reason::MustHaveShorterLen => "must_have_shorter_len",
+            Reason::ParseDecimalError { .. } => "parse_decimal_error",
+            Reason::ParseDateError { .. } => "parse_date_error",
+            Reason::ParseMoneyError { .. } => "parse_money_error",
+            Reason::AmbiguousDate { .. } => "ambiguous_date",
+            Reason::UnknownCurrency => "unknown_currency",
+            Reason::NotOneOf { .. } => "not_one_of",
+        }
+    }
+}
+
+impl Display for Reason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Reason::Required => write!(f, "is required"),
+            Reason::MustBePositive => write!(f, "must be positive"),
+            Reason::MustHaveLongerLen => write!(f, "is too short"),
+            Reason::MustHaveShorterLen => write!(f, "is too long"),
+            Reason::ParseDecimalError { cause } => write!(f, "is not a valid decimal: {cause}"),
+            Reason::ParseDateError { cause } => write!(f, "is not a valid date: {cause}"),
+            Reason::ParseMoneyError { cause } => write!(f, "is not a valid amount: {cause}"),
+            Reason::AmbiguousDate { formats } => write!(
+                f,
+                "is ambiguous between the date formats {}",
+                formats.join(" and ")
+            ),
+            Reason::UnknownCurrency => write!(f, "is not an ISO 4217 currency code"),
+            Reason::NotOneOf { values } => write!(f, "is not one of {}", values.join(", ")),
+        }
+    }
 }
 
 pub fn validate_positive(field: &str, value: &Decimal) -> Result<(), Invalid> {
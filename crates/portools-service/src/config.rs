@@ -1,14 +1,79 @@
+use crate::profile::ImportProfiles;
+use portools_common::model::SubstantiallyIdentical;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // the limits remain at the top level of the configuration file, as they preceded the rest
+    #[serde(flatten)]
+    pub limits: Limits,
+    pub import_profiles: ImportProfiles,
+    pub wash_sales: WashSaleConfig,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Limits {
     pub portfolio: PortfolioLimits,
+    pub fx_rates: UploadLimits,
+    pub transactions: UploadLimits,
+    pub corporate_actions: UploadLimits,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WashSaleConfig {
+    // groups of symbols whose purchases replace one another's sales (e.g. [["VOO", "IVV", "SPY"]])
+    pub substantially_identical: SubstantiallyIdentical,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct PortfolioLimits {
     pub max_file_size: usize,
     pub max_num_lots: usize,
+    // the maximum number of CSV errors reported for a single upload
+    pub max_num_errors: usize,
+    // the maximum size of a compressed upload once decompressed, which guards against uploads
+    // that decompress to far more than they were sent as
+    pub max_decompressed_size: usize,
+}
+
+// the limits of an upload that is buffered in its entirety before it is converted
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UploadLimits {
+    pub max_file_size: usize,
+    // the maximum number of CSV errors reported for a single upload
+    pub max_num_errors: usize,
+}
+
+impl Config {
+    /// Validates the configuration, once loaded
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        // at least one error must be reported for an upload to be rejected with a reason
+        let limits = &self.limits;
+        for (name, max_num_errors) in [
+            ("portfolio", limits.portfolio.max_num_errors),
+            ("fx_rates", limits.fx_rates.max_num_errors),
+            ("transactions", limits.transactions.max_num_errors),
+            ("corporate_actions", limits.corporate_actions.max_num_errors),
+        ] {
+            if max_num_errors == 0 {
+                return Err(ConfigError::ZeroLimit {
+                    name: format!("{name}.max_num_errors"),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ConfigError {
+    // the named limit must be at least one
+    ZeroLimit { name: String },
 }
 
 impl Default for PortfolioLimits {
@@ -16,6 +81,17 @@ impl Default for PortfolioLimits {
         PortfolioLimits {
             max_file_size: 10_000,
             max_num_lots: 10_000,
+            max_num_errors: 100,
+            max_decompressed_size: 100_000,
+        }
+    }
+}
+
+impl Default for UploadLimits {
+    fn default() -> Self {
+        UploadLimits {
+            max_file_size: 100_000,
+            max_num_errors: 100,
         }
     }
 }
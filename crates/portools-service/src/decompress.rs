@@ -0,0 +1,155 @@
+use flate2::read::GzDecoder;
+use std::io::{Cursor, Read};
+use zip::ZipArchive;
+
+#[derive(Debug, PartialEq)]
+pub enum DecompressError {
+    // the decompressed content would exceed the maximum size
+    TooLarge { max_size: usize },
+    Malformed { cause: String },
+    // the archive does not contain any CSV files
+    NoCsvFiles,
+}
+
+/// Decompresses gzip compressed content. Since a small input may decompress to an enormous
+/// output, decompression stops as soon as the output exceeds max_size.
+pub fn gunzip(compressed: &[u8], max_size: usize) -> Result<Vec<u8>, DecompressError> {
+    read_bounded(GzDecoder::new(compressed), max_size)
+}
+
+/// Extracts the CSV files (by their extension) within a zip archive, along with their names, in
+/// the order that they appear within the archive. The combined size of the extracted files is
+/// bounded by max_size, regardless of the sizes that the archive claims for them.
+pub fn unzip_csvs(
+    archive: &[u8],
+    max_size: usize,
+) -> Result<Vec<(String, Vec<u8>)>, DecompressError> {
+    let mut archive = ZipArchive::new(Cursor::new(archive)).map_err(malformed)?;
+    let mut csvs = Vec::new();
+    let mut remaining = max_size;
+    for index in 0..archive.len() {
+        let file = archive.by_index(index).map_err(malformed)?;
+        let name = file.name().to_string();
+        if file.is_dir() || !name.to_ascii_lowercase().ends_with(".csv") {
+            continue;
+        }
+        let csv = read_bounded(file, remaining).map_err(|error| match error {
+            DecompressError::TooLarge { .. } => DecompressError::TooLarge { max_size },
+            error => error,
+        })?;
+        remaining -= csv.len();
+        csvs.push((name, csv));
+    }
+    if csvs.is_empty() {
+        return Err(DecompressError::NoCsvFiles);
+    }
+    Ok(csvs)
+}
+
+// reads at most one byte beyond max_size, which is enough to tell that it was exceeded
+fn read_bounded(reader: impl Read, max_size: usize) -> Result<Vec<u8>, DecompressError> {
+    let mut output = Vec::new();
+    reader
+        .take(max_size as u64 + 1)
+        .read_to_end(&mut output)
+        .map_err(malformed)?;
+    if output.len() > max_size {
+        return Err(DecompressError::TooLarge { max_size });
+    }
+    Ok(output)
+}
+
+fn malformed(cause: impl ToString) -> DecompressError {
+    DecompressError::Malformed {
+        cause: cause.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::decompress::{gunzip, unzip_csvs, DecompressError};
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::{Cursor, Write};
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    fn gzip(content: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(content).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files {
+            writer.start_file(*name, FileOptions::default()).unwrap();
+            writer.write_all(content).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn gunzip_within_max_size() {
+        let content = b"account,symbol\n".repeat(10);
+        assert_eq!(Ok(content.clone()), gunzip(&gzip(&content), content.len()));
+    }
+
+    #[test]
+    fn gunzip_exceeding_max_size() {
+        // highly compressible, as in a zip bomb
+        let compressed = gzip(&[0; 100_000]);
+        assert!(compressed.len() < 1_000);
+        assert_eq!(
+            Err(DecompressError::TooLarge { max_size: 1_000 }),
+            gunzip(&compressed, 1_000)
+        );
+    }
+
+    #[test]
+    fn gunzip_malformed() {
+        assert!(matches!(
+            gunzip(b"not gzip", 1_000),
+            Err(DecompressError::Malformed { .. })
+        ));
+    }
+
+    #[test]
+    fn unzip_csvs_skips_other_files() {
+        let archive = zip(&[
+            ("ira.csv", b"a"),
+            ("README.txt", b"b"),
+            ("taxable/brokerage.CSV", b"c"),
+        ]);
+        assert_eq!(
+            Ok(vec![
+                ("ira.csv".to_string(), b"a".to_vec()),
+                ("taxable/brokerage.CSV".to_string(), b"c".to_vec())
+            ]),
+            unzip_csvs(&archive, 1_000)
+        );
+    }
+
+    #[test]
+    fn unzip_csvs_exceeding_max_size() {
+        // each file is within the maximum, but not all of them combined
+        let archive = zip(&[("a.csv", &[0; 600]), ("b.csv", &[0; 600])]);
+        assert_eq!(
+            Err(DecompressError::TooLarge { max_size: 1_000 }),
+            unzip_csvs(&archive, 1_000)
+        );
+    }
+
+    #[test]
+    fn unzip_csvs_without_csv_files() {
+        let archive = zip(&[("README.txt", b"b")]);
+        assert_eq!(
+            Err(DecompressError::NoCsvFiles),
+            unzip_csvs(&archive, 1_000)
+        );
+        assert!(matches!(
+            unzip_csvs(b"not zip", 1_000),
+            Err(DecompressError::Malformed { .. })
+        ));
+    }
+}
@@ -1,85 +1,573 @@
-use actix_web::web::{Buf, Bytes};
+use crate::profile::{HeaderAliases, ImportProfile};
+use actix_web::web::Bytes;
 use csv::StringRecord;
-use portools_common::model::Lot;
+use portools_common::model::{CorporateAction, FxRate, Lot, Transaction, USD};
+use portools_common::parse::ParseOptions;
 use portools_common::validate::Invalid;
-use std::collections::HashMap;
+use std::collections::VecDeque;
+
+const ACCOUNT: &str = "account";
+const SYMBOL: &str = "symbol";
+const DATE_ACQUIRED: &str = "date_acquired";
+const QUANTITY: &str = "quantity";
+const COST_PER_SHARE: &str = "cost_per_share";
+const CURRENCY: &str = "currency";
+
+// the columns of a CSV of FX rates, which are named after the fields of FxRate
+const FX_RATE_COLUMNS: [&str; 4] = ["from", "to", "date", "rate"];
+
+// the columns of a CSV of transactions, which are named after the fields of Transaction, where the
+// last four are optional. The lot_id is that of the specific lot that a disposal relieves.
+const TRANSACTION_COLUMNS: [&str; 10] = [
+    "account",
+    "symbol",
+    "date",
+    "kind",
+    "quantity",
+    "price",
+    "currency",
+    "date_acquired",
+    "method",
+    "lot_id",
+];
+
+// the columns of a CSV of corporate actions, which are named after the fields of CorporateAction,
+// where the last three are optional
+const CORPORATE_ACTION_COLUMNS: [&str; 6] = [
+    "symbol",
+    "effective_date",
+    "ratio",
+    "new_symbol",
+    "cash_in_lieu",
+    "currency",
+];
 
 #[derive(Debug, PartialEq)]
 pub enum CsvError {
-    RecordError { row: usize, cause: String },
-    RecordInvalid { row: usize, cause: Invalid },
-    MissingHeader { name: String },
-    HeaderError { cause: String },
+    RecordError {
+        row: usize,
+        cause: String,
+    },
+    RecordInvalid {
+        row: usize,
+        // the CSV column containing the invalid value
+        column: String,
+        // the invalid value, if one was present
+        value: Option<String>,
+        cause: Invalid,
+    },
+    MissingHeader {
+        name: String,
+    },
+    HeaderError {
+        cause: String,
+    },
 }
 
 pub fn csv_to_lot(csv: Bytes) -> Result<Vec<Lot>, CsvError> {
-    let mut rdr = csv::Reader::from_reader(csv.reader());
-    let field_to_index = create_headers_to_index(rdr.headers())?;
-    let mut lots = Vec::new();
-    for (row, record) in rdr.records().enumerate() {
-        match record {
-            Ok(r) => match to_lot(row, &field_to_index, &r) {
-                Ok(lot) => {
-                    lots.push(lot);
-                }
-                Err(error) => {
-                    return Err(error);
+    csv_to_lot_collecting_errors(csv, &ImportProfile::default(), 1)
+        .map_err(|mut errors| errors.swap_remove(0))
+}
+
+/// Converts every row of the CSV to a Lot, per the profile. Rather than stopping at the first
+/// invalid row, errors are collected until max_errors of them have been encountered, so that
+/// callers can report everything that is wrong with the CSV at once.
+pub fn csv_to_lot_collecting_errors(
+    csv: Bytes,
+    profile: &ImportProfile,
+    max_errors: usize,
+) -> Result<Vec<Lot>, Vec<CsvError>> {
+    let mut digester = CsvLotDigester::new(profile, max_errors);
+    digester.digest(&csv);
+    digester.finish()
+}
+
+/// Converts a CSV to Lots incrementally, as chunks of it are received, so that the CSV need not
+/// be buffered in its entirety. Errors are collected in the same manner as
+/// csv_to_lot_collecting_errors.
+pub struct CsvLotDigester<'a> {
+    reader: csv_core::Reader,
+    profile: &'a ImportProfile,
+    max_errors: usize,
+    // the fields of the record being read, along with the end of each of them within fields
+    fields: Vec<u8>,
+    fields_len: usize,
+    ends: Vec<usize>,
+    ends_len: usize,
+    // the number of leading rows skipped so far
+    num_skipped: usize,
+    // whether the header record has been read
+    header_read: bool,
+    // None if the header is missing any of the required columns
+    columns: Option<Columns>,
+    num_columns: usize,
+    row: usize,
+    // the rows that are not yet known to precede the trailing rows of the profile
+    pending: VecDeque<(usize, Result<StringRecord, String>)>,
+    lots: Vec<Lot>,
+    errors: Vec<CsvError>,
+    // whether the remainder of the CSV is ignored, due to the errors encountered so far
+    done: bool,
+}
+
+impl<'a> CsvLotDigester<'a> {
+    pub fn new(profile: &'a ImportProfile, max_errors: usize) -> CsvLotDigester<'a> {
+        CsvLotDigester {
+            reader: csv_core::Reader::new(),
+            profile,
+            max_errors,
+            fields: vec![0; 1024],
+            fields_len: 0,
+            ends: vec![0; 8],
+            ends_len: 0,
+            num_skipped: 0,
+            header_read: false,
+            columns: None,
+            num_columns: 0,
+            row: 0,
+            pending: VecDeque::new(),
+            lots: Vec::new(),
+            errors: Vec::new(),
+            done: false,
+        }
+    }
+
+    /// the number of lots converted so far
+    pub fn num_lots(&self) -> usize {
+        self.lots.len()
+    }
+
+    /// Converts the records that are completed by the next chunk of the CSV
+    pub fn digest(&mut self, chunk: &[u8]) {
+        // an empty input denotes the end of the CSV to the reader
+        if !chunk.is_empty() {
+            self.read(chunk);
+        }
+    }
+
+    /// Converts the final record, if it was not terminated, and returns the result. The
+    /// pending rows are the trailing rows of the profile, and so are skipped.
+    pub fn finish(mut self) -> Result<Vec<Lot>, Vec<CsvError>> {
+        self.read(&[]);
+        if !self.header_read {
+            // the CSV is empty
+            self.on_header(StringRecord::new());
+        }
+        if self.errors.is_empty() {
+            Ok(self.lots)
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    fn read(&mut self, mut input: &[u8]) {
+        use csv_core::ReadRecordResult::*;
+        while !self.done {
+            let (result, num_in, num_out, num_ends) = self.reader.read_record(
+                input,
+                &mut self.fields[self.fields_len..],
+                &mut self.ends[self.ends_len..],
+            );
+            input = &input[num_in..];
+            self.fields_len += num_out;
+            self.ends_len += num_ends;
+            match result {
+                InputEmpty | End => return,
+                OutputFull => self.fields.resize(self.fields.len() * 2, 0),
+                OutputEndsFull => self.ends.resize(self.ends.len() * 2, 0),
+                Record => {
+                    let record = self.take_record();
+                    if self.num_skipped < self.profile.skip_leading_rows {
+                        self.num_skipped += 1;
+                    } else if !self.header_read {
+                        match record {
+                            Ok(record) => self.on_header(record),
+                            Err(cause) => {
+                                // the columns are unknown, so none of the rows can be converted
+                                self.header_read = true;
+                                self.errors.push(CsvError::HeaderError { cause });
+                                self.done = true;
+                            }
+                        }
+                    } else {
+                        let row = self.next_row();
+                        self.pending.push_back((row, record));
+                        if self.pending.len() > self.profile.skip_trailing_rows {
+                            let (row, record) = self.pending.pop_front().expect("row is pending");
+                            self.on_record(row, record);
+                        }
+                    }
                 }
-            },
-            Err(cause) => {
-                return Err(CsvError::RecordError {
-                    row,
-                    cause: cause.to_string(),
-                });
             }
         }
     }
-    Ok(lots)
+
+    fn take_record(&mut self) -> Result<StringRecord, String> {
+        let mut start = 0;
+        let mut record = StringRecord::with_capacity(self.fields_len, self.ends_len);
+        for (i, end) in self.ends[..self.ends_len].iter().enumerate() {
+            let field = std::str::from_utf8(&self.fields[start..*end])
+                .map_err(|error| format!("field {} is not valid UTF-8: {error}", i + 1))?;
+            record.push_field(field);
+            start = *end;
+        }
+        self.fields_len = 0;
+        self.ends_len = 0;
+        Ok(record)
+    }
+
+    fn on_header(&mut self, header: StringRecord) {
+        self.header_read = true;
+        self.num_columns = header.len();
+        match Columns::new(&header, &self.profile.headers) {
+            Ok(columns) => self.columns = Some(columns),
+            Err(errors) => {
+                self.errors.extend(errors);
+                self.errors.truncate(self.max_errors);
+                self.done = true;
+            }
+        }
+    }
+
+    fn on_record(&mut self, row: usize, record: Result<StringRecord, String>) {
+        let lot = match record {
+            Err(cause) => Err(CsvError::RecordError { row, cause }),
+            Ok(record) if record.len() != self.num_columns => Err(CsvError::RecordError {
+                row,
+                cause: format!(
+                    "found record with {} fields, but the header has {} fields",
+                    record.len(),
+                    self.num_columns
+                ),
+            }),
+            Ok(record) => {
+                let columns = self.columns.as_ref().expect("header was valid");
+                to_lot(row, columns, self.profile, &record)
+            }
+        };
+        match lot {
+            Ok(lot) => self.lots.push(lot),
+            Err(error) => self.on_error(error),
+        }
+    }
+
+    fn on_error(&mut self, error: CsvError) {
+        self.errors.push(error);
+        self.done = self.errors.len() >= self.max_errors;
+    }
+
+    fn next_row(&mut self) -> usize {
+        self.row += 1;
+        self.row - 1
+    }
 }
 
-fn create_headers_to_index(
-    headers: Result<&StringRecord, csv::Error>,
-) -> Result<HashMap<String, usize>, CsvError> {
-    let mut field_to_index = HashMap::with_capacity(5);
-    let headers = headers.map_err(|error| CsvError::HeaderError {
-        cause: error.to_string(),
-    })?;
-    for (i, header) in headers.iter().enumerate() {
-        let header = header.trim().to_ascii_lowercase();
-        field_to_index.insert(header, i);
+// a column of the CSV, along with its header
+struct Column {
+    index: usize,
+    header: String,
+}
+
+// the columns of the CSV that lots are converted from
+struct Columns {
+    account: Column,
+    symbol: Column,
+    date_acquired: Column,
+    quantity: Column,
+    cost_per_share: Column,
+    currency: Option<Column>,
+}
+
+impl Columns {
+    fn new(header: &StringRecord, aliases: &HeaderAliases) -> Result<Columns, Vec<CsvError>> {
+        let mut errors = Vec::new();
+        let mut required = |name: &str, aliases: &[String]| {
+            let column = find_column(header, aliases);
+            if column.is_none() {
+                // report the header by the name that the profile expects it to have
+                let name = aliases.first().map_or(name, String::as_str);
+                errors.push(CsvError::MissingHeader { name: name.into() });
+            }
+            column
+        };
+        let account = required(ACCOUNT, &aliases.account);
+        let symbol = required(SYMBOL, &aliases.symbol);
+        let date_acquired = required(DATE_ACQUIRED, &aliases.date_acquired);
+        let quantity = required(QUANTITY, &aliases.quantity);
+        let cost_per_share = required(COST_PER_SHARE, &aliases.cost_per_share);
+        match (account, symbol, date_acquired, quantity, cost_per_share) {
+            (Some(account), Some(symbol), Some(date_acquired), Some(quantity), Some(cost)) => {
+                Ok(Columns {
+                    account,
+                    symbol,
+                    date_acquired,
+                    quantity,
+                    cost_per_share: cost,
+                    currency: find_column(header, &aliases.currency),
+                })
+            }
+            _ => Err(errors),
+        }
     }
-    Ok(field_to_index)
+}
+
+fn find_column(header: &StringRecord, aliases: &[String]) -> Option<Column> {
+    header
+        .iter()
+        .position(|name| {
+            aliases
+                .iter()
+                .any(|alias| alias.trim().eq_ignore_ascii_case(name.trim()))
+        })
+        .map(|index| Column {
+            index,
+            header: header[index].trim().into(),
+        })
+}
+
+/// Converts the lots to a CSV with the same columns that csv_to_lot expects, such that the
+/// lots can be edited and re-uploaded. The currency of each lot is included, so that it is
+/// re-uploaded in the same currency regardless of the import profile's default currency.
+pub fn lots_to_csv(lots: &[Lot]) -> Result<Vec<u8>, csv::Error> {
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+    wtr.write_record([
+        ACCOUNT,
+        SYMBOL,
+        DATE_ACQUIRED,
+        QUANTITY,
+        COST_PER_SHARE,
+        CURRENCY,
+    ])?;
+    for lot in lots {
+        wtr.write_record([
+            lot.account.as_str(),
+            lot.symbol.as_str(),
+            &lot.date_acquired_string(),
+            &lot.quantity.to_string(),
+            &lot.cost_basis.amount.to_string(),
+            lot.cost_basis.symbol.as_str(),
+        ])?;
+    }
+    wtr.into_inner().map_err(|error| error.into_error().into())
 }
 
 fn to_lot(
     row: usize,
-    field_to_index: &HashMap<String, usize>,
+    columns: &Columns,
+    profile: &ImportProfile,
     record: &StringRecord,
 ) -> Result<Lot, CsvError> {
+    let account = get_field(row, &columns.account, record)?;
+    let symbol = get_field(row, &columns.symbol, record)?;
+    let date_acquired = get_field(row, &columns.date_acquired, record)?;
+    let quantity = get_field(row, &columns.quantity, record)?;
+    let cost_per_share = get_field(row, &columns.cost_per_share, record)?;
+    let currency = match &columns.currency {
+        Some(column) => Some(get_field(row, column, record)?),
+        None => None,
+    };
     Lot::from_str(
-        get_field(row, "account", field_to_index, record)?,
-        get_field(row, "symbol", field_to_index, record)?,
-        get_field(row, "date_acquired", field_to_index, record)?,
-        get_field(row, "quantity", field_to_index, record)?,
-        get_field(row, "cost_per_share", field_to_index, record)?,
+        account,
+        symbol,
+        date_acquired,
+        quantity,
+        cost_per_share,
+        currency.unwrap_or(&profile.currency),
+        &profile.options,
     )
-    .map_err(|cause| CsvError::RecordInvalid { row, cause })
+    .map_err(|cause| {
+        // map the field reported by Lot back to the CSV column that it was parsed from
+        let (column, value) = match &cause.field[..] {
+            "account" => (Some(&columns.account), Some(account)),
+            "symbol" => (Some(&columns.symbol), Some(symbol)),
+            "date" => (Some(&columns.date_acquired), Some(date_acquired)),
+            "quantity" => (Some(&columns.quantity), Some(quantity)),
+            "cost_basis" => (Some(&columns.cost_per_share), Some(cost_per_share)),
+            "currency" => (columns.currency.as_ref(), currency),
+            _ => (None, None),
+        };
+        CsvError::RecordInvalid {
+            row,
+            column: column.map_or(cause.field.clone(), |column| column.header.clone()),
+            value: value.map(String::from),
+            cause,
+        }
+    })
+}
+
+/// Converts every row of a CSV with the columns from, to, date and rate to an FxRate, where dates
+/// and rates are parsed per the default ParseOptions. Errors are collected in the same manner as
+/// csv_to_lot_collecting_errors.
+pub fn csv_to_fx_rate(csv: &[u8], max_errors: usize) -> Result<Vec<FxRate>, Vec<CsvError>> {
+    let options = ParseOptions::default();
+    convert_csv(
+        csv,
+        &FX_RATE_COLUMNS,
+        FX_RATE_COLUMNS.len(),
+        max_errors,
+        |values| FxRate::from_str(values[0], values[1], values[2], values[3], &options),
+    )
+}
+
+/// Converts every row of a CSV with the columns account, symbol, date, kind, quantity and price to
+/// a Transaction, where dates and numbers are parsed per the default ParseOptions. The currency
+/// column, which defaults to USD, and the date_acquired, method and lot_id columns are optional.
+/// Errors are collected in the same manner as csv_to_lot_collecting_errors.
+pub fn csv_to_transaction(
+    csv: &[u8],
+    max_errors: usize,
+) -> Result<Vec<Transaction>, Vec<CsvError>> {
+    let options = ParseOptions::default();
+    let num_required = TRANSACTION_COLUMNS.len() - 4;
+    convert_csv(
+        csv,
+        &TRANSACTION_COLUMNS,
+        num_required,
+        max_errors,
+        |values| {
+            let currency = if values[6].is_empty() { USD } else { values[6] };
+            Transaction::from_str(
+                values[0],
+                values[1],
+                values[2],
+                values[3],
+                values[4],
+                values[5],
+                currency,
+                Some(values[7]),
+                Some(values[8]),
+                Some(values[9]),
+                &options,
+            )
+        },
+    )
+}
+
+/// Converts every row of a CSV with the columns symbol, effective_date and ratio to a
+/// CorporateAction, where dates and numbers are parsed per the default ParseOptions. The new_symbol,
+/// cash_in_lieu and currency columns are optional, where the currency of the cash in lieu defaults
+/// to USD. Errors are collected in the same manner as csv_to_lot_collecting_errors.
+pub fn csv_to_corporate_action(
+    csv: &[u8],
+    max_errors: usize,
+) -> Result<Vec<CorporateAction>, Vec<CsvError>> {
+    let options = ParseOptions::default();
+    let num_required = CORPORATE_ACTION_COLUMNS.len() - 3;
+    convert_csv(
+        csv,
+        &CORPORATE_ACTION_COLUMNS,
+        num_required,
+        max_errors,
+        |values| {
+            let currency = if values[5].is_empty() { USD } else { values[5] };
+            CorporateAction::from_str(
+                values[0], values[1], values[2], values[3], values[4], currency, &options,
+            )
+        },
+    )
+}
+
+// Converts every row of a CSV with the named columns, of which only the first num_required must be
+// present, by passing the values of the row to convert in the same order as the names. The value
+// of a column that is not present is empty. The field of an Invalid returned by convert is
+// reported as the column of the same name.
+fn convert_csv<T>(
+    csv: &[u8],
+    names: &[&str],
+    num_required: usize,
+    max_errors: usize,
+    convert: impl Fn(&[&str]) -> Result<T, Invalid>,
+) -> Result<Vec<T>, Vec<CsvError>> {
+    let mut reader = csv::Reader::from_reader(csv);
+    let header = reader.headers().cloned().map_err(|error| {
+        vec![CsvError::HeaderError {
+            cause: error.to_string(),
+        }]
+    })?;
+    let mut columns = Vec::new();
+    let mut errors = Vec::new();
+    for (index, name) in names.iter().enumerate() {
+        let column = find_column(&header, &[name.to_string()]);
+        if column.is_none() && index < num_required {
+            errors.push(CsvError::MissingHeader {
+                name: name.to_string(),
+            });
+        }
+        columns.push(column);
+    }
+    if !errors.is_empty() {
+        errors.truncate(max_errors);
+        return Err(errors);
+    }
+    let mut converted = Vec::new();
+    for (row, record) in reader.records().enumerate() {
+        let result = record
+            .map_err(|error| CsvError::RecordError {
+                row,
+                cause: error.to_string(),
+            })
+            .and_then(|record| convert_record(row, names, &columns, &record, &convert));
+        match result {
+            Ok(value) => converted.push(value),
+            Err(error) => {
+                errors.push(error);
+                if errors.len() >= max_errors {
+                    break;
+                }
+            }
+        }
+    }
+    if errors.is_empty() {
+        Ok(converted)
+    } else {
+        Err(errors)
+    }
+}
+
+fn convert_record<T>(
+    row: usize,
+    names: &[&str],
+    columns: &[Option<Column>],
+    record: &StringRecord,
+    convert: impl Fn(&[&str]) -> Result<T, Invalid>,
+) -> Result<T, CsvError> {
+    let values = columns
+        .iter()
+        .map(|column| match column {
+            Some(column) => get_field(row, column, record),
+            None => Ok(""),
+        })
+        .collect::<Result<Vec<&str>, CsvError>>()?;
+    convert(&values).map_err(|cause| {
+        let column = names
+            .iter()
+            .position(|name| *name == cause.field)
+            .and_then(|index| {
+                columns[index]
+                    .as_ref()
+                    .map(|column| (column, values[index]))
+            });
+        CsvError::RecordInvalid {
+            row,
+            column: column.map_or(cause.field.clone(), |(column, _)| column.header.clone()),
+            value: column.map(|(_, value)| value.to_string()),
+            cause,
+        }
+    })
 }
 
 fn get_field<'a>(
     row: usize,
-    name: &'a str,
-    name_to_index: &'a HashMap<String, usize>,
+    column: &Column,
     record: &'a StringRecord,
 ) -> Result<&'a str, CsvError> {
-    let index = name_to_index
-        .get(name)
-        .ok_or(CsvError::MissingHeader { name: name.into() })?;
     let field_value = record
-        .get(*index)
+        .get(column.index)
         .ok_or(CsvError::RecordInvalid {
             row,
-            cause: Invalid::required_str(name),
+            column: column.header.clone(),
+            value: None,
+            cause: Invalid::required(column.header.clone()),
         })?
         .trim();
     Ok(field_value)
@@ -87,12 +575,18 @@ fn get_field<'a>(
 
 #[cfg(test)]
 mod test {
-    use crate::digest::{csv_to_lot, CsvError};
+    use crate::digest::{
+        csv_to_corporate_action, csv_to_fx_rate, csv_to_lot, csv_to_lot_collecting_errors,
+        csv_to_transaction, lots_to_csv, CsvError, CsvLotDigester, DATE_ACQUIRED,
+    };
+    use crate::profile::{ImportProfile, ImportProfiles};
     use crate::unit_test_util::resource;
-    use actix_web::web::Bytes;
     use chrono::NaiveDate;
-    use portools_common::model::{Currency, Lot};
-    use portools_common::validate::Invalid;
+    use portools_common::model::{
+        CorporateAction, Currency, FxRate, Lot, ReliefMethod, Transaction, TransactionKind,
+    };
+    use portools_common::parse::ParseOptions;
+    use portools_common::validate::{Invalid, Reason};
     use rust_decimal::Decimal;
     use rusty_money::MoneyError::InvalidAmount;
     use test_util::assertion::assert_err_eq;
@@ -105,7 +599,7 @@ mod test {
             new_lot("IRA", "BND", "2023/03/28", 2, 200.26),
             new_lot("IRA", "BND", "2023/03/29", 3, 300.23),
         ];
-        let result = csv_to_lot(Bytes::from(csv));
+        let result = csv_to_lot(csv);
         assert_eq!(expected, result.unwrap());
     }
 
@@ -117,7 +611,7 @@ mod test {
             new_lot("IRA", "BND", "2023/03/28", 2, 200.26),
             new_lot("IRA", "BND", "2023/03/29", 3, 300.23),
         ];
-        let result = csv_to_lot(Bytes::from(csv));
+        let result = csv_to_lot(csv);
         assert_eq!(expected, result.unwrap());
     }
 
@@ -129,7 +623,7 @@ mod test {
             new_lot("IRA", "BND", "2023/03/28", 2, 200.26),
             new_lot("IRA", "BND", "2023/03/29", 3, 300.23),
         ];
-        let result = csv_to_lot(Bytes::from(csv));
+        let result = csv_to_lot(csv);
         assert_eq!(expected, result.unwrap());
     }
 
@@ -141,14 +635,14 @@ mod test {
             new_lot("IRA", "BND", "2023/03/28", 2, 200.26),
             new_lot("IRA", "BND", "2023/03/29", 3, 300.23),
         ];
-        let result = csv_to_lot(Bytes::from(csv));
+        let result = csv_to_lot(csv);
         assert_eq!(expected, result.unwrap());
     }
 
     #[test]
     fn test_missing_header() {
         let csv = resource::load_bytes("missing_header.csv");
-        let result = csv_to_lot(Bytes::from(csv));
+        let result = csv_to_lot(csv);
         assert_err_eq(
             CsvError::MissingHeader {
                 name: "account".into(),
@@ -160,7 +654,7 @@ mod test {
     #[test]
     fn test_missing_quantity_column() {
         let csv = resource::load_bytes("missing_quantity_column.csv");
-        let result = csv_to_lot(Bytes::from(csv));
+        let result = csv_to_lot(csv);
         assert_err_eq(
             CsvError::MissingHeader {
                 name: "quantity".into(),
@@ -172,17 +666,429 @@ mod test {
     #[test]
     fn test_row_with_invalid_value() {
         let csv = resource::load_bytes("row_with_invalid_value.csv");
-        let result = csv_to_lot(Bytes::from(csv));
+        let result = csv_to_lot(csv);
         assert_err_eq(
             CsvError::RecordInvalid {
                 row: 1,
+                column: "cost_per_share".into(),
+                value: Some("NOT_A_NUMBER".into()),
                 cause: Invalid::parse_money_error("cost_basis", InvalidAmount),
             },
             result,
         );
     }
 
-    const DATE_FORMAT: &'static str = "%Y/%m/%d";
+    #[test]
+    fn test_collecting_errors_with_valid() {
+        let csv = resource::load_bytes("valid.csv");
+        let result = csv_to_lot_collecting_errors(csv, &ImportProfile::default(), 10);
+        assert_eq!(3, result.unwrap().len());
+    }
+
+    #[test]
+    fn test_collecting_errors_with_rows_with_invalid_values() {
+        let csv = resource::load_bytes("rows_with_invalid_values.csv");
+        let result = csv_to_lot_collecting_errors(csv, &ImportProfile::default(), 10);
+        assert_err_eq(
+            vec![
+                CsvError::RecordInvalid {
+                    row: 1,
+                    column: "cost_per_share".into(),
+                    value: Some("NOT_A_NUMBER".into()),
+                    cause: Invalid::parse_money_error("cost_basis", InvalidAmount),
+                },
+                CsvError::RecordInvalid {
+                    row: 2,
+                    column: "quantity".into(),
+                    value: Some("-3".into()),
+                    cause: Invalid {
+                        field: "quantity".into(),
+                        reason: Reason::MustBePositive,
+                    },
+                },
+                CsvError::RecordInvalid {
+                    row: 4,
+                    column: "cost_per_share".into(),
+                    value: Some("ALSO_NOT_A_NUMBER".into()),
+                    cause: Invalid::parse_money_error("cost_basis", InvalidAmount),
+                },
+            ],
+            result,
+        );
+    }
+
+    #[test]
+    fn test_collecting_errors_stops_at_max_errors() {
+        let csv = resource::load_bytes("rows_with_invalid_values.csv");
+        let result = csv_to_lot_collecting_errors(csv, &ImportProfile::default(), 2);
+        assert_eq!(2, result.unwrap_err().len());
+    }
+
+    #[test]
+    fn test_collecting_errors_with_missing_quantity_column() {
+        let csv = resource::load_bytes("missing_quantity_column.csv");
+        let result = csv_to_lot_collecting_errors(csv, &ImportProfile::default(), 10);
+        assert_err_eq(
+            vec![CsvError::MissingHeader {
+                name: "quantity".into(),
+            }],
+            result,
+        );
+    }
+
+    #[test]
+    fn test_digester_with_one_byte_chunks() {
+        let csv = resource::load_bytes("valid_with_whitespace.csv");
+        let profile = ImportProfile::default();
+        let mut digester = CsvLotDigester::new(&profile, 10);
+        for byte in csv.chunks(1) {
+            digester.digest(byte);
+        }
+        assert_eq!(
+            csv_to_lot(csv),
+            digester.finish().map_err(|mut e| e.remove(0))
+        );
+    }
+
+    #[test]
+    fn test_digester_counts_lots_as_they_are_digested() {
+        let profile = ImportProfile::default();
+        let mut digester = CsvLotDigester::new(&profile, 10);
+        digester
+            .digest(b"account,symbol,date_acquired,quantity,cost_per_share\nIRA,BND,2023/3/28,2,");
+        assert_eq!(0, digester.num_lots());
+        digester.digest(b"200.26\nIRA,BND,2023/3/29,3,300.23");
+        assert_eq!(1, digester.num_lots());
+        // the last record is not terminated
+        assert_eq!(2, digester.finish().unwrap().len());
+    }
+
+    #[test]
+    fn test_digester_with_wrong_number_of_fields() {
+        let profile = ImportProfile::default();
+        let mut digester = CsvLotDigester::new(&profile, 10);
+        digester
+            .digest(b"account,symbol,date_acquired,quantity,cost_per_share\nIRA,BND,2023/3/28,2\n");
+        match digester.finish() {
+            Err(errors) => assert!(
+                matches!(&errors[..], [CsvError::RecordError { row: 0, .. }]),
+                "{errors:?}"
+            ),
+            Ok(lots) => panic!("expected an error, but got {lots:?}"),
+        }
+    }
+
+    #[test]
+    fn test_digester_with_empty_csv() {
+        let profile = ImportProfile::default();
+        let digester = CsvLotDigester::new(&profile, 1);
+        assert_err_eq(
+            vec![CsvError::MissingHeader {
+                name: "account".into(),
+            }],
+            digester.finish(),
+        );
+    }
+
+    #[test]
+    fn test_digester_with_invalid_header() {
+        let profile = ImportProfile::default();
+        let mut digester = CsvLotDigester::new(&profile, 10);
+        digester.digest(b"account,\xff\nIRA,BND,2023/3/28,2,72.5\n");
+        match digester.finish() {
+            Err(errors) => assert!(
+                matches!(&errors[..], [CsvError::HeaderError { .. }]),
+                "{errors:?}"
+            ),
+            Ok(lots) => panic!("expected an error, but got {lots:?}"),
+        }
+
+        // the header is not read again once the CSV ends
+        let mut digester = CsvLotDigester::new(&profile, 10);
+        digester.digest(b"account,\xff");
+        match digester.finish() {
+            Err(errors) => assert!(
+                matches!(&errors[..], [CsvError::HeaderError { .. }]),
+                "{errors:?}"
+            ),
+            Ok(lots) => panic!("expected an error, but got {lots:?}"),
+        }
+    }
+
+    #[test]
+    fn test_profile_with_aliases_and_trailing_rows() {
+        let csv = resource::load_bytes("fidelity.csv");
+        let profile = ImportProfiles::default().get(Some("fidelity")).unwrap();
+        let expected = vec![
+            new_lot("Taxable", "VOO", "2023/03/27", 1, 100.47),
+            new_lot("IRA", "BND", "2023/03/28", 2, 200.26),
+            new_lot("IRA", "BND", "2023/03/29", 3, 1300.23),
+        ];
+        let result = csv_to_lot_collecting_errors(csv, &profile, 10);
+        assert_eq!(expected, result.unwrap());
+    }
+
+    #[test]
+    fn test_profile_with_leading_rows_and_currency_column() {
+        let csv = resource::load_bytes("schwab_with_currency.csv");
+        let profile = ImportProfiles::default().get(Some("schwab")).unwrap();
+        let result = csv_to_lot_collecting_errors(csv, &profile, 10).unwrap();
+        assert_eq!(2, result.len());
+        assert_eq!(
+            new_lot("Brokerage", "VOO", "2023/03/27", 1, 100.47),
+            result[0]
+        );
+        assert_eq!(
+            Currency::new(Decimal::from(20026), "JPY").unwrap(),
+            result[1].cost_basis
+        );
+    }
+
+    #[test]
+    fn test_unknown_currency() {
+        let profile = ImportProfile::default();
+        let csv = "account,symbol,date_acquired,quantity,cost_per_share,Currency\n\
+                   IRA,BND,2023/03/28,2,200.26,usd\n\
+                   IRA,BND,2023/03/28,2,200.26,XYZ\n";
+        assert_err_eq(
+            vec![CsvError::RecordInvalid {
+                row: 1,
+                column: "Currency".into(),
+                value: Some("XYZ".into()),
+                cause: Invalid {
+                    field: "currency".into(),
+                    reason: Reason::UnknownCurrency,
+                },
+            }],
+            csv_to_lot_collecting_errors(csv.into(), &profile, 10),
+        );
+    }
+
+    #[test]
+    fn test_profile_reports_columns_by_their_headers() {
+        let profile = ImportProfiles::default().get(Some("fidelity")).unwrap();
+        let csv = "Account Name,Symbol,Quantity,Date Acquired\nIRA,BND,2,2023/03/28\n";
+        assert_err_eq(
+            vec![CsvError::MissingHeader {
+                name: "cost basis per share".into(),
+            }],
+            csv_to_lot_collecting_errors(csv.into(), &profile, 10),
+        );
+
+        let csv = "Account Name,Symbol,Quantity,Date Acquired,Cost Basis Per Share\n\
+                   IRA,BND,2,2023/03/28,200.26\n\
+                   footer 1\nfooter 2\nfooter 3\n";
+        let errors = csv_to_lot_collecting_errors(csv.into(), &profile, 10).unwrap_err();
+        assert!(
+            matches!(
+                &errors[..],
+                [CsvError::RecordInvalid { row: 0, column, .. }] if column == "Date Acquired"
+            ),
+            "{errors:?}"
+        );
+    }
+
+    #[test]
+    fn test_parse_options_of_profile() {
+        let csv = "account,symbol,date_acquired,quantity,cost_per_share\n\
+                   IRA,BND,2023-03-28,2,\"$1,200.26\"\n\
+                   IRA,BND,03/29/2023,3,300.23\n";
+        let result = csv_to_lot_collecting_errors(csv.into(), &ImportProfile::default(), 10);
+        assert_eq!(
+            vec![
+                new_lot("IRA", "BND", "2023/03/28", 2, 1200.26),
+                new_lot("IRA", "BND", "2023/03/29", 3, 300.23),
+            ],
+            result.unwrap()
+        );
+
+        let profile = ImportProfile {
+            options: ParseOptions {
+                date_formats: vec!["%m/%d/%Y".into(), "%d/%m/%Y".into()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let csv = "account,symbol,date_acquired,quantity,cost_per_share\n\
+                   IRA,BND,03/04/2023,2,200.26\n";
+        let errors = csv_to_lot_collecting_errors(csv.into(), &profile, 10).unwrap_err();
+        assert!(
+            matches!(
+                &errors[..],
+                [CsvError::RecordInvalid { row: 0, column, cause, .. }]
+                    if column == DATE_ACQUIRED && cause.reason.code() == "ambiguous_date"
+            ),
+            "{errors:?}"
+        );
+    }
+
+    #[test]
+    fn test_csv_to_fx_rate() {
+        let csv = b"From,To,Date,Rate\njpy,USD,2023/03/01,0.0073\nEUR,USD,2023-03-01,one\n";
+        let invalid = csv_to_fx_rate(csv, 10).unwrap_err();
+        assert!(
+            matches!(
+                &invalid[..],
+                [CsvError::RecordInvalid { row: 1, column, value: Some(value), .. }]
+                    if column == "Rate" && value == "one"
+            ),
+            "{invalid:?}"
+        );
+
+        let csv = b"from,to,date,rate\njpy,USD,2023/03/01,0.0073\nEUR,USD,2023-03-01,1.06\n";
+        let date = NaiveDate::from_ymd_opt(2023, 3, 1).unwrap();
+        assert_eq!(
+            Ok(vec![
+                FxRate::new("JPY", "USD", date, "0.0073".parse().unwrap()).unwrap(),
+                FxRate::new("EUR", "USD", date, "1.06".parse().unwrap()).unwrap(),
+            ]),
+            csv_to_fx_rate(csv, 10)
+        );
+
+        assert_eq!(
+            Err(vec![CsvError::MissingHeader {
+                name: "rate".into()
+            }]),
+            csv_to_fx_rate(b"from,to,date\n", 10)
+        );
+    }
+
+    #[test]
+    fn test_csv_to_transaction() {
+        let csv = b"Account,Symbol,Date,Kind,Quantity,Price\nIRA,BND,2023/03/01,buy,2,72.5\n\
+            IRA,BND,2023/03/02,give,1,73\n";
+        let invalid = csv_to_transaction(csv, 10).unwrap_err();
+        assert!(
+            matches!(
+                &invalid[..],
+                [CsvError::RecordInvalid { row: 1, column, value: Some(value), .. }]
+                    if column == "Kind" && value == "give"
+            ),
+            "{invalid:?}"
+        );
+
+        let csv = b"account,symbol,date,kind,quantity,price,currency,date_acquired,method,lot_id\n\
+            IRA,BND,2023/03/01,Transfer In,2,72.5,usd,2020/01/02,,\n\
+            IRA,BND,2023/03/02,sell,1,73,,,specific_lot,1\n";
+        let transaction = |date, kind, quantity, price: &str, date_acquired, method| {
+            Transaction::new(
+                "IRA",
+                "BND",
+                date,
+                kind,
+                Decimal::from(quantity),
+                Currency::new(price.parse().unwrap(), "USD").unwrap(),
+                date_acquired,
+                method,
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            Ok(vec![
+                transaction(
+                    NaiveDate::from_ymd_opt(2023, 3, 1).unwrap(),
+                    TransactionKind::TransferIn,
+                    2,
+                    "72.5",
+                    NaiveDate::from_ymd_opt(2020, 1, 2),
+                    None
+                ),
+                transaction(
+                    NaiveDate::from_ymd_opt(2023, 3, 2).unwrap(),
+                    TransactionKind::Sell,
+                    1,
+                    "73",
+                    None,
+                    Some(ReliefMethod::SpecificLot { lot_id: 1 })
+                ),
+            ]),
+            csv_to_transaction(csv, 10)
+        );
+
+        assert_eq!(
+            Err(vec![
+                CsvError::MissingHeader {
+                    name: "kind".into()
+                },
+                CsvError::MissingHeader {
+                    name: "price".into()
+                }
+            ]),
+            csv_to_transaction(b"account,symbol,date,quantity\n", 10)
+        );
+    }
+
+    #[test]
+    fn test_csv_to_corporate_action() {
+        let csv = b"symbol,effective_date,ratio,new_symbol,cash_in_lieu\n\
+            VTI,2023/06/01,2,,\n\
+            XYZ,2023/06/02,0.1,ABC,12.50\n";
+        let date = |day| NaiveDate::from_ymd_opt(2023, 6, day).unwrap();
+        assert_eq!(
+            Ok(vec![
+                CorporateAction::new("VTI", date(1), Decimal::TWO, None, None).unwrap(),
+                CorporateAction::new(
+                    "XYZ",
+                    date(2),
+                    "0.1".parse().unwrap(),
+                    Some("ABC"),
+                    Some(Currency::new("12.5".parse().unwrap(), "USD").unwrap())
+                )
+                .unwrap(),
+            ]),
+            csv_to_corporate_action(csv, 10)
+        );
+
+        let csv = b"symbol,effective_date,ratio,cash_in_lieu,currency\nVTI,2023/06/01,0,,\n\
+            VTI,2023/06/02,2,1,XYZ\n";
+        let invalid = csv_to_corporate_action(csv, 10).unwrap_err();
+        assert!(
+            matches!(
+                &invalid[..],
+                [
+                    CsvError::RecordInvalid { row: 0, column: ratio, .. },
+                    CsvError::RecordInvalid { row: 1, column: currency, .. },
+                ] if ratio == "ratio" && currency == "currency"
+            ),
+            "{invalid:?}"
+        );
+    }
+
+    #[test]
+    fn test_lots_to_csv() {
+        let lots = vec![
+            new_lot("Taxable", "VOO", "2023/03/27", 1, 100.47),
+            new_lot("IRA, Roth", "BND", "2023/03/28", 2, 200.26),
+        ];
+        let csv = lots_to_csv(&lots).unwrap();
+        assert_eq!(
+            "account,symbol,date_acquired,quantity,cost_per_share,currency\n\
+             Taxable,VOO,2023/03/27,1,100.47,USD\n\
+             \"IRA, Roth\",BND,2023/03/28,2,200.26,USD\n",
+            String::from_utf8(csv).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_lots_to_csv_round_trip() {
+        let csv = resource::load_bytes("valid.csv");
+        let lots = csv_to_lot(csv).unwrap();
+        let round_tripped = csv_to_lot(lots_to_csv(&lots).unwrap().into()).unwrap();
+        assert_eq!(lots, round_tripped);
+    }
+
+    #[test]
+    fn test_lots_to_csv_round_trip_in_different_currencies() {
+        let mut lots = vec![
+            new_lot("Taxable", "VOO", "2023/03/27", 1, 100.47),
+            new_lot("Taxable", "VGK", "2023/03/28", 2, 200.26),
+        ];
+        lots[1].cost_basis = Currency::new("200.26".parse().unwrap(), "EUR").unwrap();
+        let round_tripped = csv_to_lot(lots_to_csv(&lots).unwrap().into()).unwrap();
+        assert_eq!(lots, round_tripped);
+    }
+
+    const DATE_FORMAT: &str = "%Y/%m/%d";
 
     fn new_lot(account: &str, symbol: &str, date: &str, quantity: u32, cost_basis_usd: f64) -> Lot {
         let cost_basis = Currency::new(cost_basis_usd.to_string().parse().unwrap(), "USD").unwrap();
@@ -1,4 +1,6 @@
 pub mod config;
-mod digest;
+pub mod decompress;
+pub mod digest;
+pub mod profile;
 pub mod service;
 mod unit_test_util;
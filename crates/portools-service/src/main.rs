@@ -2,11 +2,12 @@ use actix_web::{web::Data, App, HttpServer};
 use mongodb::Client;
 use std::io;
 
+use portools_common::dao::migration;
 use portools_common::dao::mongo;
 use portools_common::dao::mongo::MongoDao;
 use portools_common::log;
 
-use portools_service::config::Limits;
+use portools_service::config::Config;
 use portools_service::service;
 use portools_service::service::state::State;
 
@@ -26,16 +27,29 @@ async fn main() -> io::Result<()> {
         .unwrap_or_else(|error| {
             panic!("should be able create collections and indexes. error: {error}")
         });
+    migration::migrate(&client).await.unwrap_or_else(|error| {
+        panic!("should be able to migrate stored documents. error: {error}")
+    });
 
-    let limits: Limits = confy::load(APP_NAME, None).unwrap_or_else(|error| {
+    let config: Config = confy::load(APP_NAME, None).unwrap_or_else(|error| {
         panic!(
             "should be able to load configuration from {:?}. error: {error}",
             confy::get_configuration_file_path(APP_NAME, None)
         )
     });
-    tracing::info!("using limits: {:?}", limits);
+    config
+        .validate()
+        .unwrap_or_else(|error| panic!("configuration should be valid. error: {:?}", error));
+    tracing::info!("using limits: {:?}", config.limits);
+    tracing::info!(
+        "using import profiles: {:?}",
+        config.import_profiles.0.keys()
+    );
+    tracing::info!("using wash sales: {:?}", config.wash_sales);
     let app_state = Data::new(State {
-        limits,
+        limits: config.limits,
+        import_profiles: config.import_profiles,
+        wash_sales: config.wash_sales,
         dao: Box::new(MongoDao::new(client)),
     });
     HttpServer::new(move || App::new().configure(|cfg| service::config(cfg, &app_state)))
@@ -0,0 +1,153 @@
+use portools_common::model::USD;
+use portools_common::parse::ParseOptions;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const GENERIC: &str = "generic";
+
+/// Describes how the CSV exported by a particular broker is converted to Lots
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImportProfile {
+    // how the dates and numbers of the CSV are parsed
+    #[serde(flatten)]
+    pub options: ParseOptions,
+
+    // the currency of costs, for CSVs without a currency column
+    pub currency: String,
+
+    // the number of rows preceding the header, such as a title or the time of the export
+    pub skip_leading_rows: usize,
+
+    // the number of rows following the last lot, such as totals or disclaimers
+    pub skip_trailing_rows: usize,
+
+    // follows the other fields, as TOML requires tables to follow values
+    pub headers: HeaderAliases,
+}
+
+/// The headers that each column may have, which are compared case insensitively
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeaderAliases {
+    pub account: Vec<String>,
+    pub symbol: Vec<String>,
+    pub date_acquired: Vec<String>,
+    pub quantity: Vec<String>,
+    pub cost_per_share: Vec<String>,
+
+    // the currency column is optional
+    pub currency: Vec<String>,
+}
+
+/// The import profiles, by name
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ImportProfiles(pub BTreeMap<String, ImportProfile>);
+
+impl Default for ImportProfile {
+    fn default() -> Self {
+        ImportProfile {
+            options: Default::default(),
+            currency: USD.into(),
+            skip_leading_rows: 0,
+            skip_trailing_rows: 0,
+            headers: Default::default(),
+        }
+    }
+}
+
+impl Default for HeaderAliases {
+    fn default() -> Self {
+        HeaderAliases {
+            account: aliases(&["account"]),
+            symbol: aliases(&["symbol"]),
+            date_acquired: aliases(&["date_acquired"]),
+            quantity: aliases(&["quantity"]),
+            cost_per_share: aliases(&["cost_per_share"]),
+            currency: aliases(&["currency"]),
+        }
+    }
+}
+
+impl ImportProfiles {
+    /// The profile of the given name, or the generic profile if no name is given
+    pub fn get(&self, name: Option<&str>) -> Option<ImportProfile> {
+        match name {
+            Some(name) => self.0.get(name).cloned(),
+            None => Some(self.0.get(GENERIC).cloned().unwrap_or_default()),
+        }
+    }
+}
+
+// The built-in profiles, which are written to the configuration file when it is created, so that
+// they may be adjusted as the exports of the brokers change
+impl Default for ImportProfiles {
+    fn default() -> Self {
+        let fidelity = ImportProfile {
+            headers: HeaderAliases {
+                account: aliases(&["account name", "account number", "account"]),
+                date_acquired: aliases(&["date acquired", "acquired"]),
+                cost_per_share: aliases(&["cost basis per share", "average cost basis"]),
+                ..Default::default()
+            },
+            options: us_dates(),
+            skip_trailing_rows: 3,
+            ..Default::default()
+        };
+        let schwab = ImportProfile {
+            headers: HeaderAliases {
+                account: aliases(&["account", "account name"]),
+                date_acquired: aliases(&["open date", "date acquired"]),
+                cost_per_share: aliases(&["cost/share", "cost per share"]),
+                ..Default::default()
+            },
+            options: us_dates(),
+            skip_leading_rows: 1,
+            skip_trailing_rows: 2,
+            ..Default::default()
+        };
+        let vanguard = ImportProfile {
+            headers: HeaderAliases {
+                account: aliases(&["account number", "account name"]),
+                date_acquired: aliases(&["trade date", "date acquired"]),
+                quantity: aliases(&["shares", "quantity"]),
+                cost_per_share: aliases(&["share price", "cost per share"]),
+                ..Default::default()
+            },
+            options: us_dates(),
+            ..Default::default()
+        };
+        ImportProfiles(BTreeMap::from([
+            (GENERIC.into(), ImportProfile::default()),
+            ("fidelity".into(), fidelity),
+            ("schwab".into(), schwab),
+            ("vanguard".into(), vanguard),
+        ]))
+    }
+}
+
+fn us_dates() -> ParseOptions {
+    ParseOptions {
+        date_formats: vec!["%m/%d/%Y".into()],
+        ..Default::default()
+    }
+}
+
+fn aliases(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::profile::{ImportProfile, ImportProfiles, GENERIC};
+
+    #[test]
+    fn get() {
+        let profiles = ImportProfiles::default();
+        assert_eq!(Some(ImportProfile::default()), profiles.get(None));
+        assert_eq!(profiles.get(Some(GENERIC)), profiles.get(None));
+        assert!(profiles.get(Some("fidelity")).is_some());
+        assert_eq!(None, profiles.get(Some("unknown")));
+    }
+}
@@ -0,0 +1,254 @@
+use crate::digest::csv_to_corporate_action;
+use crate::service::response::{AppliedCorporateActions, ErrorDetail, ErrorResponse};
+use crate::service::state::State;
+use crate::service::{record_version, source_filename, transaction, upload};
+use actix_web::web::Data;
+use actix_web::{web, HttpRequest, HttpResponse};
+use portools_common::model::{
+    CorporateAction, CorporateActionError, CurrencyError, Page, Portfolio, PortfolioTransactions,
+    PortfolioVersion, RealizedGain, SaleError,
+};
+use std::collections::HashMap;
+use std::slice;
+
+// the number of times that applying corporate actions to a portfolio is attempted, where each
+// attempt after the first follows a concurrent modification of the portfolio
+const MAX_ATTEMPTS: usize = 3;
+
+// Stores the corporate actions within the CSV, and applies them to the lots of every portfolio. An
+// action that differs from a stored action of the same symbol and effective date is rejected, as
+// the stored action may already have been applied. Each portfolio records the actions that were
+// applied to it, so that an upload that is retried (e.g. after a failure) applies only those that
+// were not. Each portfolio that an action applies to is stored as a new version, and is included
+// in the response along with any gains realized by cash paid in lieu of fractional shares.
+pub async fn add_corporate_actions(
+    payload: web::Payload,
+    req: HttpRequest,
+    data: Data<State>,
+) -> HttpResponse {
+    let limits = &data.limits.corporate_actions;
+    if let Err(response) = upload::check_content_length(&req, limits.max_file_size) {
+        return response;
+    }
+    let csv = match upload::read_payload(payload, limits.max_file_size).await {
+        Ok(csv) => csv,
+        Err(response) => return response,
+    };
+    let actions = match csv_to_corporate_action(&csv, limits.max_num_errors) {
+        Ok(actions) => actions,
+        Err(errors) => {
+            tracing::debug!(?errors, "failed to convert payload to corporate actions");
+            return HttpResponse::BadRequest().json(ErrorResponse::from(&errors[..]));
+        }
+    };
+    // of the actions for the same symbol and effective date, only the last is applied and stored
+    let mut key_to_index = HashMap::new();
+    for (index, action) in actions.iter().enumerate() {
+        key_to_index.insert(action.id(), index);
+    }
+    let mut last_actions: Vec<(usize, &CorporateAction)> = key_to_index
+        .into_values()
+        .map(|index| (index, &actions[index]))
+        .collect();
+    last_actions.sort_by_key(|(index, action)| (action.effective_date, *index));
+
+    // a stored action has already been applied, so it may not be changed, as the lots that it was
+    // applied to would not reflect the change
+    let stored = match stored_corporate_actions(&data).await {
+        Ok(stored) => stored,
+        Err(response) => return response,
+    };
+    let changed: Vec<ErrorDetail> = actions
+        .iter()
+        .enumerate()
+        .filter(|(_, action)| {
+            stored
+                .iter()
+                .any(|stored| stored.id() == action.id() && stored != *action)
+        })
+        .map(|(index, action)| ErrorDetail::corporate_action_changed(index, &action.id()))
+        .collect();
+    if !changed.is_empty() {
+        tracing::debug!(?changed, "corporate actions differ from those stored");
+        return HttpResponse::UnprocessableEntity().json(ErrorResponse::from(changed));
+    }
+
+    let portfolios = match data.dao.list_portfolios(&Page::default()).await {
+        Ok(portfolios) => portfolios,
+        Err(error) => {
+            tracing::error!(?error, "failed to list portfolios");
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    // every portfolio is adjusted before any is stored, so that nothing is stored if an action
+    // fails to apply
+    let mut portfolio_ids = Vec::new();
+    for metadata in portfolios {
+        let mut portfolio = match get_portfolio(metadata.id, &data).await {
+            Ok(Some(portfolio)) => portfolio,
+            // deleted since it was listed
+            Ok(None) => continue,
+            Err(response) => return response,
+        };
+        match apply(&mut portfolio, &last_actions) {
+            Ok(Some(_)) => portfolio_ids.push(portfolio.id),
+            Ok(None) => {}
+            Err(response) => return response,
+        }
+    }
+    // stored before any portfolio, so that an action that was applied to any portfolio may not be
+    // changed, even if the upload fails part way through
+    if let Err(error) = data.dao.put_corporate_actions(&actions).await {
+        tracing::error!(?error, "failed to put corporate actions");
+        return HttpResponse::InternalServerError().finish();
+    }
+
+    let mut applied = Vec::new();
+    for portfolio_id in portfolio_ids {
+        match apply_and_store(portfolio_id, &last_actions, &req, &data).await {
+            Ok(Some(portfolio_applied)) => applied.push(portfolio_applied),
+            Ok(None) => {}
+            Err(response) => {
+                tracing::error!(
+                    portfolio_id,
+                    status = %response.status(),
+                    "failed to apply corporate actions to portfolio"
+                );
+                return response;
+            }
+        }
+    }
+    HttpResponse::Ok().json(applied)
+}
+
+// Applies the actions to the current version of the portfolio and stores it as a new version,
+// re-reading the portfolio and applying them again if it was modified concurrently. Returns None if
+// the portfolio no longer exists, or if none of the actions apply to it.
+async fn apply_and_store(
+    portfolio_id: u32,
+    actions: &[(usize, &CorporateAction)],
+    req: &HttpRequest,
+    data: &Data<State>,
+) -> Result<Option<AppliedCorporateActions>, HttpResponse> {
+    for _ in 0..MAX_ATTEMPTS {
+        let mut portfolio = match get_portfolio(portfolio_id, data).await? {
+            Some(portfolio) => portfolio,
+            None => return Ok(None),
+        };
+        let realized_gains = match apply(&mut portfolio, actions)? {
+            Some(realized_gains) => realized_gains,
+            None => return Ok(None),
+        };
+        // the lots remain derived from any transactions, which are replayed along with the actions
+        let transactions = transaction::derived_transactions(&portfolio, data).await?;
+        portfolio.version += 1;
+        let stored = match transactions {
+            Some(transactions) => {
+                let transactions = PortfolioTransactions {
+                    version: portfolio.version,
+                    ..transactions
+                };
+                data.dao
+                    .put_portfolio_and_transactions(
+                        &portfolio,
+                        &transactions,
+                        portfolio.version - 1,
+                    )
+                    .await
+            }
+            None => {
+                data.dao
+                    .put_portfolio(&portfolio, portfolio.version - 1)
+                    .await
+            }
+        };
+        match stored {
+            Ok(true) => {}
+            Ok(false) => {
+                tracing::debug!(portfolio_id, "portfolio modified concurrently, retrying");
+                continue;
+            }
+            Err(error) => {
+                tracing::error!(?error, "failed to persist portfolio");
+                return Err(HttpResponse::InternalServerError().finish());
+            }
+        }
+        record_version(
+            PortfolioVersion::new(&portfolio, source_filename(req)),
+            data,
+        )
+        .await?;
+        return Ok(Some(AppliedCorporateActions {
+            portfolio_id,
+            version: portfolio.version,
+            realized_gains,
+        }));
+    }
+    Err(HttpResponse::Conflict().finish())
+}
+
+async fn get_portfolio(
+    portfolio_id: u32,
+    data: &Data<State>,
+) -> Result<Option<Portfolio>, HttpResponse> {
+    data.dao.get_portfolio(portfolio_id).await.map_err(|error| {
+        tracing::error!(?error, "failed to get portfolio");
+        HttpResponse::InternalServerError().finish()
+    })
+}
+
+// Responds with every corporate action, ordered by effective date and then by symbol
+pub async fn get_corporate_actions(data: Data<State>) -> HttpResponse {
+    match data.dao.get_corporate_actions().await {
+        Ok(actions) => HttpResponse::Ok().json(actions),
+        Err(error) => {
+            tracing::error!(?error, "failed to get corporate actions");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Every stored corporate action, ordered by effective date
+pub(crate) async fn stored_corporate_actions(
+    data: &Data<State>,
+) -> Result<Vec<CorporateAction>, HttpResponse> {
+    data.dao.get_corporate_actions().await.map_err(|error| {
+        tracing::error!(?error, "failed to get corporate actions");
+        HttpResponse::InternalServerError().finish()
+    })
+}
+
+// Applies the actions, along with their (0-based) index within the upload, to the portfolio in
+// order. Returns the gains realized by cash paid in lieu, or None if no action applies to the
+// portfolio other than those already applied to it.
+fn apply(
+    portfolio: &mut Portfolio,
+    actions: &[(usize, &CorporateAction)],
+) -> Result<Option<Vec<RealizedGain>>, HttpResponse> {
+    let mut realized_gains = None;
+    for (index, action) in actions {
+        let num_applied = portfolio.corporate_actions.len();
+        match portfolio.apply_corporate_actions(slice::from_ref(*action)) {
+            Ok(_) if portfolio.corporate_actions.len() == num_applied => {}
+            Ok(gains) => realized_gains.get_or_insert_with(Vec::new).extend(gains),
+            Err(CorporateActionError::CashInLieuError {
+                cause:
+                    SaleError::GainError {
+                        cause: CurrencyError::SymbolMismatch { left, right },
+                    },
+            }) => {
+                tracing::debug!(index, %left, %right, "cash in lieu and cost basis in different currencies");
+                return Err(
+                    HttpResponse::UnprocessableEntity().json(ErrorResponse::from(vec![
+                        ErrorDetail::cash_in_lieu_currency_mismatch(*index, &left, &right),
+                    ])),
+                );
+            }
+            Err(error) => {
+                tracing::error!(?error, "failed to apply corporate action");
+                return Err(HttpResponse::InternalServerError().finish());
+            }
+        }
+    }
+    Ok(realized_gains)
+}
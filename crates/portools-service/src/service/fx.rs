@@ -0,0 +1,104 @@
+use crate::digest::csv_to_fx_rate;
+use crate::service::response::{ErrorDetail, ErrorResponse};
+use crate::service::state::State;
+use crate::service::upload;
+use actix_web::web::Data;
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::NaiveDate;
+use portools_common::model::{Currency, FxRates, Lot, PortfolioSummaryError};
+
+// Stores the FX rates within the CSV, replacing any for the same currency pair and date
+pub async fn add_fx_rates(
+    payload: web::Payload,
+    req: HttpRequest,
+    data: Data<State>,
+) -> HttpResponse {
+    let limits = &data.limits.fx_rates;
+    if let Err(response) = upload::check_content_length(&req, limits.max_file_size) {
+        return response;
+    }
+    let csv = match upload::read_payload(payload, limits.max_file_size).await {
+        Ok(csv) => csv,
+        Err(response) => return response,
+    };
+    let rates = match csv_to_fx_rate(&csv, limits.max_num_errors) {
+        Ok(rates) => rates,
+        Err(errors) => {
+            tracing::debug!(?errors, "failed to convert payload to FX rates");
+            return HttpResponse::BadRequest().json(ErrorResponse::from(&errors[..]));
+        }
+    };
+    match data.dao.put_fx_rates(&rates).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(error) => {
+            tracing::error!(?error, "failed to put FX rates");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Responds with every FX rate, ordered by currency pair and then by date
+pub async fn get_fx_rates(data: Data<State>) -> HttpResponse {
+    match data.dao.get_fx_rates().await {
+        Ok(rates) => HttpResponse::Ok().json(rates),
+        Err(error) => {
+            tracing::error!(?error, "failed to get FX rates");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Responds with the summary by symbol in the currency of the given symbol. Unlike the other
+// summaries, it is computed upon request, using the stored FX rates.
+pub(crate) async fn summary_by_symbol_in_currency(
+    portfolio_id: u32,
+    currency: &str,
+    date: Option<NaiveDate>,
+    data: &Data<State>,
+) -> HttpResponse {
+    let symbol = match Currency::validate_symbol("currency", currency) {
+        Ok(symbol) => symbol,
+        Err(invalid) => {
+            tracing::debug!(?invalid, "invalid currency");
+            return HttpResponse::BadRequest()
+                .json(ErrorResponse::from(vec![ErrorDetail::from(&invalid)]));
+        }
+    };
+    let portfolio = match data.dao.get_portfolio(portfolio_id).await {
+        Ok(Some(portfolio)) => portfolio,
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(error) => {
+            tracing::error!(?error, "failed to get portfolio");
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    // only the rates from the currencies of the lots are needed
+    let mut currencies: Vec<String> = portfolio
+        .lots
+        .iter()
+        .map(|lot| lot.cost_basis.symbol.clone())
+        .collect();
+    currencies.sort();
+    currencies.dedup();
+    let rates: FxRates = match data.dao.get_fx_rates_to(&currencies, &symbol).await {
+        Ok(rates) => rates.into_iter().collect(),
+        Err(error) => {
+            tracing::error!(?error, "failed to get FX rates");
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let by_symbol = |lot: &Lot| -> String { lot.symbol.clone() };
+    match portfolio.get_summary_by_in_currency(by_symbol, &symbol, &rates, date) {
+        Ok(summary) => HttpResponse::Ok().json(summary),
+        Err(PortfolioSummaryError::MissingFxRate { from, to, date }) => {
+            tracing::debug!(%from, %to, %date, "missing FX rate");
+            HttpResponse::UnprocessableEntity().json(ErrorResponse::from(vec![
+                ErrorDetail::missing_fx_rate(&from, &to, date),
+            ]))
+        }
+        Err(error) => {
+            tracing::error!(?error, "failed to summarize portfolio");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
@@ -1,16 +1,32 @@
-use crate::digest::csv_to_lot;
+use crate::digest::lots_to_csv;
+use crate::profile::ImportProfile;
+use crate::service::response::{DryRunResponse, ErrorDetail, ErrorResponse};
 use crate::service::state::State;
-use crate::service::util::ContentLengthHeaderError;
-use crate::service::util::ContentLengthHeaderError::Malformed;
-use actix_web::web::{Data, Json, Path};
-use actix_web::{error, web, HttpRequest, HttpResponse, Responder};
-use portools_common::model::Portfolio;
+use actix_web::http::header::{Accept, ContentDisposition, IF_MATCH, VARY};
+use actix_web::web::{Data, Json, Path, Query};
+use actix_web::{error, web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use chrono::{NaiveDate, Utc};
+use portools_common::model::{
+    CorporateAction, Lot, LotQuery, LotSpec, Page, Portfolio, PortfolioDiff, PortfolioSummary,
+    PortfolioTransactions, PortfolioVersion,
+};
+use serde::{Deserialize, Serialize};
+use std::hash::Hash;
 use tracing;
 use tracing_actix_web::TracingLogger;
-use ContentLengthHeaderError::Missing;
 
+const APPLICATION_JSON: &str = "application/json";
+const APPLICATION_ZIP: &str = "application/zip";
+const TEXT_CSV: &str = "text/csv";
+
+pub mod corporate_action;
+pub mod fx;
+pub mod response;
 pub mod state;
+pub mod transaction;
+mod upload;
 pub(crate) mod util;
+pub mod version;
 
 pub fn config(cfg: &mut web::ServiceConfig, state: &Data<State>) {
     cfg.service(
@@ -19,18 +35,283 @@ pub fn config(cfg: &mut web::ServiceConfig, state: &Data<State>) {
         web::scope("")
             .wrap(TracingLogger::default())
             .app_data(state.clone())
+            .route("/portfolio", web::get().to(list_portfolios))
             .route("/portfolio/{portfolio_id}", web::get().to(get_portfolio))
-            .route("/portfolio/{portfolio_id}", web::put().to(put_portfolio)),
+            .route("/portfolio/{portfolio_id}", web::put().to(put_portfolio))
+            .route(
+                "/portfolio/{portfolio_id}",
+                web::delete().to(delete_portfolio),
+            )
+            .route(
+                "/portfolio/{portfolio_id}/summary/symbol",
+                web::get().to(get_summary_by_symbol),
+            )
+            .route(
+                "/portfolio/{portfolio_id}/summary/asset_class",
+                web::get().to(get_summary_by_asset_class),
+            )
+            .route(
+                "/portfolio/{portfolio_id}/summary/holding_period",
+                web::get().to(get_summary_by_holding_period),
+            )
+            .route(
+                "/portfolio/{portfolio_id}/summary/acquisition_year",
+                web::get().to(get_summary_by_acquisition_year),
+            )
+            .route("/portfolio/{portfolio_id}/lots", web::post().to(add_lots))
+            .route(
+                "/portfolio/{portfolio_id}/lots/{lot_id}",
+                web::put().to(update_lot),
+            )
+            .route(
+                "/portfolio/{portfolio_id}/lots/{lot_id}",
+                web::delete().to(delete_lot),
+            )
+            .route(
+                "/portfolio/{portfolio_id}/transactions",
+                web::get().to(transaction::get_transactions),
+            )
+            .route(
+                "/portfolio/{portfolio_id}/transactions",
+                web::put().to(transaction::put_transactions),
+            )
+            .route(
+                "/portfolio/{portfolio_id}/transactions/realized_gains",
+                web::get().to(transaction::get_realized_gains),
+            )
+            .route(
+                "/portfolio/{portfolio_id}/transactions/wash_sales",
+                web::get().to(transaction::get_wash_sales),
+            )
+            .route(
+                "/portfolio/{portfolio_id}/versions",
+                web::get().to(version::list_versions),
+            )
+            .route(
+                "/portfolio/{portfolio_id}/versions/{version}",
+                web::get().to(version::get_version),
+            )
+            .route(
+                "/portfolio/{portfolio_id}/versions/{version}/diff/{other_version}",
+                web::get().to(version::diff_versions),
+            )
+            .route(
+                "/portfolio/{portfolio_id}/versions/{version}/restore",
+                web::post().to(version::restore_version),
+            )
+            .route("/fx_rates", web::get().to(fx::get_fx_rates))
+            .route("/fx_rates", web::post().to(fx::add_fx_rates))
+            .route(
+                "/corporate_actions",
+                web::get().to(corporate_action::get_corporate_actions),
+            )
+            .route(
+                "/corporate_actions",
+                web::post().to(corporate_action::add_corporate_actions),
+            ),
     );
 }
 
+pub async fn list_portfolios(page: Query<Page>, data: Data<State>) -> HttpResponse {
+    match data.dao.list_portfolios(&page).await {
+        Ok(portfolios) => HttpResponse::Ok().json(portfolios),
+        Err(error) => {
+            tracing::error!(?error, "failed to list portfolios");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Responds with the portfolio, including only the lots selected by the query
 pub async fn get_portfolio(
     path: Path<u32>,
+    query: Query<LotQuery>,
+    req: HttpRequest,
+    data: Data<State>,
+) -> actix_web::Result<HttpResponse> {
+    let portfolio_id = path.into_inner();
+    let portfolio = match data.dao.query_portfolio(portfolio_id, &query).await {
+        Ok(Some(portfolio)) => portfolio,
+        Ok(None) => return Err(error::ErrorNotFound("portfolio not found")),
+        Err(e) => {
+            tracing::error!("dao.query_portfolio error: {e}");
+            return Err(error::ErrorInternalServerError(e));
+        }
+    };
+    // the representation, and so the ETag, depends upon the Accept header
+    let csv = prefers_csv(&req);
+    let etag = if csv {
+        util::csv_etag(portfolio.version)
+    } else {
+        util::etag(portfolio.version)
+    };
+    if util::if_none_match_matches(&req, &etag) {
+        return Ok(HttpResponse::NotModified()
+            .insert_header(etag)
+            .insert_header((VARY, "Accept"))
+            .finish());
+    }
+    if !csv {
+        return Ok(HttpResponse::Ok()
+            .insert_header(etag)
+            .insert_header((VARY, "Accept"))
+            .json(portfolio));
+    }
+    let csv = lots_to_csv(&portfolio.lots).map_err(|e| {
+        tracing::error!("lots_to_csv error: {e}");
+        error::ErrorInternalServerError(e)
+    })?;
+    Ok(HttpResponse::Ok()
+        .insert_header(etag)
+        .insert_header((VARY, "Accept"))
+        .content_type(TEXT_CSV)
+        .insert_header(ContentDisposition::attachment(format!(
+            "portfolio_{portfolio_id}.csv"
+        )))
+        .body(csv))
+}
+
+// Whether the Accept header of the request prefers CSV to JSON. JSON is the default, and is
+// used for media types that are not supported.
+fn prefers_csv(req: &HttpRequest) -> bool {
+    let Some(accept) = req.get_header::<Accept>() else {
+        return false;
+    };
+    accept
+        .ranked()
+        .iter()
+        .find(|mime| {
+            matches!(mime.essence_str(), TEXT_CSV | APPLICATION_JSON)
+                || mime.type_() == "*"
+                || mime.subtype() == "*"
+        })
+        .map(|mime| mime.essence_str() == TEXT_CSV)
+        .unwrap_or(false)
+}
+
+pub async fn delete_portfolio(path: Path<u32>, data: Data<State>) -> impl Responder {
+    let portfolio_id = path.into_inner();
+    // the derived summaries are deleted by portools-stream upon the resulting change event
+    match data.dao.delete_portfolio(portfolio_id).await {
+        Ok(true) => HttpResponse::NoContent(),
+        Ok(false) => HttpResponse::NotFound(),
+        Err(error) => {
+            tracing::error!(?error, "failed to delete portfolio");
+            HttpResponse::InternalServerError()
+        }
+    }
+}
+
+pub async fn get_summary_by_symbol(
+    path: Path<u32>,
+    params: Query<SummaryParams>,
+    data: Data<State>,
+) -> actix_web::Result<HttpResponse> {
+    let portfolio_id = path.into_inner();
+    if let Some(currency) = &params.currency {
+        return Ok(
+            fx::summary_by_symbol_in_currency(portfolio_id, currency, params.date, &data).await,
+        );
+    }
+    let summary = data
+        .dao
+        .get_summary_by_symbol(portfolio_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("dao.get_summary_by_symbol error: {e}");
+            error::ErrorInternalServerError(e)
+        })?;
+    summary_response(portfolio_id, summary, &data).await
+}
+
+pub async fn get_summary_by_asset_class(
+    path: Path<u32>,
+    data: Data<State>,
+) -> actix_web::Result<HttpResponse> {
+    let portfolio_id = path.into_inner();
+    let summary = data
+        .dao
+        .get_summary_by_asset_class(portfolio_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("dao.get_summary_by_asset_class error: {e}");
+            error::ErrorInternalServerError(e)
+        })?;
+    summary_response(portfolio_id, summary, &data).await
+}
+
+// Responds with the summary by holding period as of the given date, or as of today if no date is
+// given. The summary stored by portools-stream is as of when the portfolio was last modified, so
+// it is only used if that was today. Otherwise, the summary is computed upon request.
+pub async fn get_summary_by_holding_period(
+    path: Path<u32>,
+    params: Query<HoldingPeriodParams>,
     data: Data<State>,
-) -> actix_web::Result<Json<Portfolio>> {
+) -> actix_web::Result<HttpResponse> {
     let portfolio_id = path.into_inner();
+    let as_of = params.as_of.unwrap_or_else(|| Utc::now().date_naive());
+    if params.as_of.is_none() {
+        let summary = data
+            .dao
+            .get_summary_by_holding_period(portfolio_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("dao.get_summary_by_holding_period error: {e}");
+                error::ErrorInternalServerError(e)
+            })?;
+        // a summary as of an earlier date is stale, as the holding periods of lots may have
+        // changed since it was computed
+        let is_stale = matches!(&summary, Some(summary) if summary.as_of != Some(as_of));
+        if !is_stale {
+            return summary_response(portfolio_id, summary, &data).await;
+        }
+    }
+    let portfolio = match data.dao.get_portfolio(portfolio_id).await {
+        Ok(Some(portfolio)) => portfolio,
+        Ok(None) => return Err(error::ErrorNotFound("portfolio not found")),
+        Err(e) => {
+            tracing::error!("dao.get_portfolio error: {e}");
+            return Err(error::ErrorInternalServerError(e));
+        }
+    };
+    match portfolio.get_summary_by_holding_period(as_of) {
+        Ok(summary) => Ok(HttpResponse::Ok().json(summary)),
+        Err(error) => {
+            tracing::error!(?error, "failed to summarize portfolio");
+            Ok(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+pub async fn get_summary_by_acquisition_year(
+    path: Path<u32>,
+    data: Data<State>,
+) -> actix_web::Result<HttpResponse> {
+    let portfolio_id = path.into_inner();
+    let summary = data
+        .dao
+        .get_summary_by_acquisition_year(portfolio_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("dao.get_summary_by_acquisition_year error: {e}");
+            error::ErrorInternalServerError(e)
+        })?;
+    summary_response(portfolio_id, summary, &data).await
+}
+
+// Responds with the summary if it exists. Otherwise, distinguishes between a portfolio that
+// does not exist (404) and a portfolio whose summary has not yet been computed by
+// portools-stream (202)
+async fn summary_response<T: Hash + Eq + Serialize>(
+    portfolio_id: u32,
+    summary: Option<PortfolioSummary<T>>,
+    data: &Data<State>,
+) -> actix_web::Result<HttpResponse> {
+    if let Some(summary) = summary {
+        return Ok(HttpResponse::Ok().json(summary));
+    }
     match data.dao.get_portfolio(portfolio_id).await {
-        Ok(Some(portfolio)) => Ok(Json(portfolio)),
+        Ok(Some(_)) => Ok(HttpResponse::Accepted().body("portfolio summary not yet computed")),
         Ok(None) => Err(error::ErrorNotFound("portfolio not found")),
         Err(e) => {
             tracing::error!("dao.get_portfolio error: {e}");
@@ -39,54 +320,354 @@ pub async fn get_portfolio(
     }
 }
 
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct SummaryParams {
+    // the symbol of the currency that the summary is converted to, if any
+    pub currency: Option<String>,
+
+    // the date as of which costs are converted, rather than as of when each lot was acquired
+    pub date: Option<NaiveDate>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct HoldingPeriodParams {
+    // the date as of which the holding periods are computed, rather than as of today. Formerly
+    // named date.
+    #[serde(alias = "date")]
+    pub as_of: Option<NaiveDate>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PutPortfolioParams {
+    // when true, the upload is validated and compared to the stored portfolio, but not persisted
+    pub dry_run: bool,
+
+    // the name of the import profile that a CSV is converted per, which defaults to the generic one
+    pub profile: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AddLotsParams {
+    // the name of the import profile that a CSV is converted per, which defaults to the generic one
+    pub profile: Option<String>,
+}
+
 pub async fn put_portfolio(
     path: Path<u32>,
-    csv: web::Bytes,
+    params: Query<PutPortfolioParams>,
+    payload: web::Payload,
     req: HttpRequest,
     data: Data<State>,
-) -> impl Responder {
+) -> HttpResponse {
     let portfolio_id = path.into_inner();
-    let content_length = util::get_content_length_header(&req);
-    if content_length.is_err() {
-        return match content_length.unwrap_err() {
-            Malformed(message) => {
-                tracing::debug!(%message, "malformed Content-Length header");
-                HttpResponse::BadRequest()
-            }
-            Missing => {
-                tracing::debug!("missing Content-Length header");
-                HttpResponse::LengthRequired()
+    let limits = &data.limits.portfolio;
+    let profile = match import_profile(params.profile.as_deref(), &data) {
+        Ok(profile) => profile,
+        Err(response) => return response,
+    };
+    let lots = match upload::read_lots(&req, payload, limits, &profile, limits.max_num_lots).await {
+        Ok(lots) => lots,
+        Err(response) => return response,
+    };
+    let current = match data.dao.get_portfolio(portfolio_id).await {
+        Ok(current) => current,
+        Err(error) => {
+            tracing::error!(?error, "failed to get portfolio");
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let current_version = current.as_ref().map(|p| p.version);
+    if !util::if_match_satisfied(&req, current_version) {
+        tracing::debug!(?current_version, "If-Match header not satisfied");
+        return HttpResponse::PreconditionFailed().finish();
+    }
+    let mut portfolio = Portfolio::new(portfolio_id, lots);
+    // the lots are as currently held, so they already reflect every stored corporate action
+    portfolio.corporate_actions = match corporate_action::stored_corporate_actions(&data).await {
+        Ok(actions) => actions.iter().map(CorporateAction::id).collect(),
+        Err(response) => return response,
+    };
+    if params.dry_run {
+        let current = current.unwrap_or_else(|| Portfolio::new(portfolio_id, Vec::new()));
+        return dry_run(&current, &portfolio);
+    }
+    portfolio.version = current_version.unwrap_or(0) + 1;
+    let version = PortfolioVersion::new(&portfolio, source_filename(&req));
+    replace_portfolio(&portfolio, None, version, &req, &data).await
+}
+
+// Stores the portfolio, along with the transactions that its lots were derived from, if any,
+// provided that the stored portfolio is of the version preceding it, and retains the given version
+// of it
+async fn replace_portfolio(
+    portfolio: &Portfolio,
+    transactions: Option<&PortfolioTransactions>,
+    version: PortfolioVersion,
+    req: &HttpRequest,
+    data: &Data<State>,
+) -> HttpResponse {
+    let expected_version = portfolio.version - 1;
+    let stored = match transactions {
+        Some(transactions) => {
+            data.dao
+                .put_portfolio_and_transactions(portfolio, transactions, expected_version)
+                .await
+        }
+        None => data.dao.put_portfolio(portfolio, expected_version).await,
+    };
+    match stored {
+        Ok(true) => {}
+        // the portfolio was modified since it was read
+        Ok(false) if req.headers().contains_key(IF_MATCH) => {
+            return HttpResponse::PreconditionFailed().finish()
+        }
+        Ok(false) => return HttpResponse::Conflict().finish(),
+        Err(error) => {
+            tracing::error!(?error, "failed to persist portfolio");
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+    if let Err(response) = record_version(version, data).await {
+        return response;
+    }
+    HttpResponse::Ok()
+        .insert_header(util::etag(portfolio.version))
+        .finish()
+}
+
+// Retains a version of a portfolio that was just modified
+async fn record_version(version: PortfolioVersion, data: &Data<State>) -> Result<(), HttpResponse> {
+    data.dao
+        .put_portfolio_version(&version)
+        .await
+        .map_err(|error| {
+            tracing::error!(?error, "failed to record portfolio version");
+            HttpResponse::InternalServerError().finish()
+        })
+}
+
+// the import profile of the given name, or the generic one if no name is given
+fn import_profile(name: Option<&str>, data: &State) -> Result<ImportProfile, HttpResponse> {
+    data.import_profiles.get(name).ok_or_else(|| {
+        tracing::debug!(?name, "unknown import profile");
+        HttpResponse::BadRequest().json(ErrorResponse::from(vec![ErrorDetail::unknown_profile(
+            name.unwrap_or_default(),
+        )]))
+    })
+}
+
+// the name of the file that the body of the request was uploaded from, if provided via the
+// Content-Disposition header
+fn source_filename(req: &HttpRequest) -> Option<String> {
+    req.get_header::<ContentDisposition>()
+        .and_then(|disposition| disposition.get_filename().map(String::from))
+}
+
+pub async fn add_lots(
+    path: Path<u32>,
+    params: Query<AddLotsParams>,
+    payload: web::Payload,
+    req: HttpRequest,
+    data: Data<State>,
+) -> HttpResponse {
+    let portfolio_id = path.into_inner();
+    let limits = &data.limits.portfolio;
+    let current = match data.dao.get_portfolio(portfolio_id).await {
+        Ok(current) => current,
+        Err(error) => {
+            tracing::error!(?error, "failed to get portfolio");
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let expected_version = match if_match_version(&req, current.as_ref().map(|p| p.version)) {
+        Ok(expected_version) => expected_version,
+        Err(response) => return response,
+    };
+    let num_existing_lots = match current {
+        Some(portfolio) => portfolio.lots.len(),
+        None => return HttpResponse::NotFound().finish(),
+    };
+    // the limit on the number of lots applies to the resulting portfolio
+    let max_num_lots = limits.max_num_lots.saturating_sub(num_existing_lots);
+    let profile = match import_profile(params.profile.as_deref(), &data) {
+        Ok(profile) => profile,
+        Err(response) => return response,
+    };
+    let lots = match upload::read_lots(&req, payload, limits, &profile, max_num_lots).await {
+        Ok(lots) => lots,
+        Err(response) => return response,
+    };
+    match data
+        .dao
+        .add_lots(portfolio_id, &lots, expected_version)
+        .await
+    {
+        Ok(Some(portfolio)) => {
+            let version = PortfolioVersion::new(&portfolio, source_filename(&req));
+            if let Err(response) = record_version(version, &data).await {
+                return response;
             }
-        };
-    }
-    let content_length = content_length.unwrap();
-    if content_length > data.limits.portfolio.max_file_size {
-        tracing::debug!(
-            content_length,
-            max_content_length = data.limits.portfolio.max_file_size,
-            "Content-Length header exceeds maximum",
-        );
-        return HttpResponse::PayloadTooLarge();
+            let added = &portfolio.lots[portfolio.lots.len() - lots.len()..];
+            HttpResponse::Ok()
+                .insert_header(util::etag(portfolio.version))
+                .json(added)
+        }
+        Ok(None) => unmodified(portfolio_id, expected_version, &data).await,
+        Err(error) => {
+            tracing::error!(?error, "failed to add lots");
+            HttpResponse::InternalServerError().finish()
+        }
     }
-    let lots = match csv_to_lot(csv) {
-        Ok(csv_lots) => csv_lots,
+}
+
+pub async fn update_lot(
+    path: Path<(u32, u32)>,
+    spec: Json<LotSpec>,
+    req: HttpRequest,
+    data: Data<State>,
+) -> HttpResponse {
+    let (portfolio_id, lot_id) = path.into_inner();
+    let mut lot = match Lot::try_from(spec.into_inner()) {
+        Ok(lot) => lot,
+        Err(invalid) => {
+            tracing::debug!(?invalid, "invalid lot");
+            return HttpResponse::BadRequest()
+                .json(ErrorResponse::from(vec![ErrorDetail::from(&invalid)]));
+        }
+    };
+    lot.id = Some(lot_id);
+    let expected_version = match read_if_match_version(portfolio_id, &req, &data).await {
+        Ok(expected_version) => expected_version,
+        Err(response) => return response,
+    };
+    match data
+        .dao
+        .update_lot(portfolio_id, &lot, expected_version)
+        .await
+    {
+        Ok(Some(portfolio)) => {
+            if let Err(response) =
+                record_version(PortfolioVersion::new(&portfolio, None), &data).await
+            {
+                return response;
+            }
+            HttpResponse::Ok()
+                .insert_header(util::etag(portfolio.version))
+                .json(lot)
+        }
+        Ok(None) => unmodified(portfolio_id, expected_version, &data).await,
         Err(error) => {
-            tracing::debug!(?error, "failed to convert CSV to Lots");
-            return HttpResponse::BadRequest();
+            tracing::error!(?error, "failed to update lot");
+            HttpResponse::InternalServerError().finish()
         }
+    }
+}
+
+pub async fn delete_lot(
+    path: Path<(u32, u32)>,
+    req: HttpRequest,
+    data: Data<State>,
+) -> HttpResponse {
+    let (portfolio_id, lot_id) = path.into_inner();
+    let expected_version = match read_if_match_version(portfolio_id, &req, &data).await {
+        Ok(expected_version) => expected_version,
+        Err(response) => return response,
     };
-    if lots.len() > data.limits.portfolio.max_num_lots {
-        return HttpResponse::PayloadTooLarge();
+    match data
+        .dao
+        .delete_lot(portfolio_id, lot_id, expected_version)
+        .await
+    {
+        Ok(Some(portfolio)) => {
+            if let Err(response) =
+                record_version(PortfolioVersion::new(&portfolio, None), &data).await
+            {
+                return response;
+            }
+            HttpResponse::NoContent()
+                .insert_header(util::etag(portfolio.version))
+                .finish()
+        }
+        Ok(None) => unmodified(portfolio_id, expected_version, &data).await,
+        Err(error) => {
+            tracing::error!(?error, "failed to delete lot");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// The version of the current portfolio (None if it does not exist) that a modification of it is
+// conditional upon, which is None unless the request has an If-Match header. Responds with 412 if
+// the header is not satisfied by the current version.
+fn if_match_version(
+    req: &HttpRequest,
+    current_version: Option<u32>,
+) -> Result<Option<u32>, HttpResponse> {
+    if !util::if_match_satisfied(req, current_version) {
+        tracing::debug!(?current_version, "If-Match header not satisfied");
+        return Err(HttpResponse::PreconditionFailed().finish());
     }
-    let portfolio = Portfolio {
-        id: portfolio_id,
-        lots,
+    Ok(current_version.filter(|_| req.headers().contains_key(IF_MATCH)))
+}
+
+// As if_match_version, but reads the current version of the portfolio only if the request has an
+// If-Match header
+async fn read_if_match_version(
+    portfolio_id: u32,
+    req: &HttpRequest,
+    data: &Data<State>,
+) -> Result<Option<u32>, HttpResponse> {
+    if !req.headers().contains_key(IF_MATCH) {
+        return Ok(None);
+    }
+    match data.dao.get_portfolio(portfolio_id).await {
+        Ok(current) => if_match_version(req, current.map(|p| p.version)),
+        Err(error) => {
+            tracing::error!(?error, "failed to get portfolio");
+            Err(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+// Responds to a modification of a portfolio that was not made, since either the portfolio or the
+// lot does not exist, or the portfolio was modified since the If-Match header was checked
+async fn unmodified(
+    portfolio_id: u32,
+    expected_version: Option<u32>,
+    data: &Data<State>,
+) -> HttpResponse {
+    let Some(expected_version) = expected_version else {
+        return HttpResponse::NotFound().finish();
     };
-    match data.dao.put_portfolio(&portfolio).await {
-        Ok(_) => HttpResponse::Ok(),
+    match data.dao.get_portfolio(portfolio_id).await {
+        Ok(Some(current)) if current.version != expected_version => {
+            HttpResponse::PreconditionFailed().finish()
+        }
+        Ok(_) => HttpResponse::NotFound().finish(),
         Err(error) => {
-            tracing::error!(?error, "failed to persist portfolio");
-            HttpResponse::InternalServerError()
+            tracing::error!(?error, "failed to get portfolio");
+            HttpResponse::InternalServerError().finish()
         }
     }
 }
+
+fn dry_run(current: &Portfolio, portfolio: &Portfolio) -> HttpResponse {
+    let summary_by_symbol = match (
+        current.get_summary_by_symbol(),
+        portfolio.get_summary_by_symbol(),
+    ) {
+        (Ok(before), Ok(after)) => before.diff(&after),
+        (Err(error), _) | (_, Err(error)) => {
+            tracing::debug!(?error, "failed to summarize portfolio");
+            return HttpResponse::BadRequest().finish();
+        }
+    };
+    HttpResponse::Ok().json(DryRunResponse {
+        diff: PortfolioDiff::between(&current.lots, &portfolio.lots),
+        summary_by_symbol,
+    })
+}
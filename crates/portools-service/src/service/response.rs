@@ -0,0 +1,301 @@
+use crate::decompress::DecompressError;
+use crate::digest::CsvError;
+use chrono::NaiveDate;
+use portools_common::model::{CorporateActionId, GroupSummaryChange, PortfolioDiff, RealizedGain};
+use portools_common::validate::Invalid;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// The JSON body of a response to a rejected request
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub errors: Vec<ErrorDetail>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ErrorDetail {
+    // stable, machine readable code identifying the kind of error
+    pub code: String,
+
+    // human readable description of the error
+    pub message: String,
+
+    // the 1-based number of the row that the error pertains to. For CSV, this does not count the
+    // header row. For JSON, this is the position of the element within the array
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub row: Option<usize>,
+
+    // the name of the field (e.g. the CSV column) that the error pertains to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+
+    // the offending value
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+
+    // for an archive containing multiple files, the name of the file that the error pertains to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+}
+
+impl ErrorDetail {
+    pub fn invalid_json(cause: &serde_json::Error) -> ErrorDetail {
+        ErrorDetail {
+            code: "invalid_json".into(),
+            message: format!("failed to parse JSON: {cause}"),
+            row: None,
+            field: None,
+            value: None,
+            file: None,
+        }
+    }
+
+    pub fn unknown_profile(name: &str) -> ErrorDetail {
+        ErrorDetail {
+            code: "unknown_profile".into(),
+            message: format!("unknown import profile: {name}"),
+            row: None,
+            field: Some("profile".into()),
+            value: Some(name.into()),
+            file: None,
+        }
+    }
+
+    pub fn missing_fx_rate(from: &str, to: &str, date: NaiveDate) -> ErrorDetail {
+        ErrorDetail {
+            code: "missing_fx_rate".into(),
+            message: format!("no rate for converting {from} to {to} as of {date}"),
+            row: None,
+            field: None,
+            value: None,
+            file: None,
+        }
+    }
+
+    // describes a transaction, at the given (0-based) index, that disposes of more shares than
+    // are held
+    pub fn insufficient_quantity(index: usize, held: Decimal) -> ErrorDetail {
+        ErrorDetail {
+            code: "insufficient_quantity".into(),
+            message: format!("quantity exceeds the {held} shares held"),
+            row: Some(index + 1),
+            field: Some("quantity".into()),
+            value: None,
+            file: None,
+        }
+    }
+
+    // describes a transaction, at the given (0-based) index, that disposes of shares of a specific
+    // lot that is not held by the account
+    pub fn lot_not_found(index: usize, lot_id: u32) -> ErrorDetail {
+        ErrorDetail {
+            code: "lot_not_found".into(),
+            message: format!("lot {lot_id} is not an open lot of the account and symbol"),
+            row: Some(index + 1),
+            field: Some("lot_id".into()),
+            value: Some(lot_id.to_string()),
+            file: None,
+        }
+    }
+
+    // describes a transaction, at the given (0-based) index, whose price is in a different
+    // currency than the cost basis of the shares that it disposes of
+    pub fn currency_mismatch(index: usize, price: &str, cost_basis: &str) -> ErrorDetail {
+        ErrorDetail {
+            code: "currency_mismatch".into(),
+            message: format!("price is in {price}, but the cost basis is in {cost_basis}"),
+            row: Some(index + 1),
+            field: Some("currency".into()),
+            value: Some(price.into()),
+            file: None,
+        }
+    }
+
+    // describes a corporate action, at the given (0-based) index, that pays cash in lieu in a
+    // currency other than that of the cost basis of a lot that it applies to
+    pub fn cash_in_lieu_currency_mismatch(
+        index: usize,
+        cash_in_lieu: &str,
+        cost_basis: &str,
+    ) -> ErrorDetail {
+        ErrorDetail {
+            code: "currency_mismatch".into(),
+            message: format!(
+                "cash in lieu is in {cash_in_lieu}, but the cost basis is in {cost_basis}"
+            ),
+            row: Some(index + 1),
+            field: Some("currency".into()),
+            value: Some(cash_in_lieu.into()),
+            file: None,
+        }
+    }
+
+    // describes a corporate action, at the given (0-based) index, that differs from the stored
+    // action of the same symbol and effective date
+    pub fn corporate_action_changed(index: usize, action: &CorporateActionId) -> ErrorDetail {
+        ErrorDetail {
+            code: "corporate_action_changed".into(),
+            message: format!(
+                "differs from the corporate action already applied for {} effective {}",
+                action.symbol, action.effective_date
+            ),
+            row: Some(index + 1),
+            field: None,
+            value: None,
+            file: None,
+        }
+    }
+
+    // describes a stored corporate action whose cash in lieu is in a different currency than the
+    // cost basis of a lot that it applies to
+    pub fn corporate_action_currency_mismatch(
+        action: &CorporateActionId,
+        cash_in_lieu: &str,
+        cost_basis: &str,
+    ) -> ErrorDetail {
+        ErrorDetail {
+            code: "currency_mismatch".into(),
+            message: format!(
+                "cash in lieu of the corporate action for {} effective {} is in {cash_in_lieu}, but the cost basis is in {cost_basis}",
+                action.symbol, action.effective_date
+            ),
+            row: None,
+            field: Some("currency".into()),
+            value: Some(cash_in_lieu.into()),
+            file: None,
+        }
+    }
+
+    // describes an invalid value within the JSON element at the given (0-based) index
+    pub fn invalid_element(index: usize, cause: &Invalid) -> ErrorDetail {
+        ErrorDetail {
+            row: Some(index + 1),
+            ..ErrorDetail::from(cause)
+        }
+    }
+}
+
+impl From<&DecompressError> for ErrorDetail {
+    fn from(error: &DecompressError) -> Self {
+        let (code, message) = match error {
+            DecompressError::TooLarge { max_size } => (
+                "too_large",
+                format!("decompressed content exceeds {max_size} bytes"),
+            ),
+            DecompressError::Malformed { cause } => {
+                ("malformed", format!("failed to decompress: {cause}"))
+            }
+            DecompressError::NoCsvFiles => ("no_csv_files", "archive contains no CSV files".into()),
+        };
+        ErrorDetail {
+            code: code.into(),
+            message,
+            row: None,
+            field: None,
+            value: None,
+            file: None,
+        }
+    }
+}
+
+impl From<&Invalid> for ErrorDetail {
+    fn from(cause: &Invalid) -> Self {
+        ErrorDetail {
+            code: cause.reason.code().into(),
+            message: cause.to_string(),
+            row: None,
+            field: Some(cause.field.clone()),
+            value: None,
+            file: None,
+        }
+    }
+}
+
+impl From<&CsvError> for ErrorDetail {
+    fn from(error: &CsvError) -> Self {
+        match error {
+            CsvError::RecordError { row, cause } => ErrorDetail {
+                code: "record_error".into(),
+                message: format!("failed to read row: {cause}"),
+                row: Some(row + 1),
+                field: None,
+                value: None,
+                file: None,
+            },
+            CsvError::RecordInvalid {
+                row,
+                column,
+                value,
+                cause,
+            } => ErrorDetail {
+                code: cause.reason.code().into(),
+                message: format!("{column} {}", cause.reason),
+                row: Some(row + 1),
+                field: Some(column.clone()),
+                value: value.clone(),
+                file: None,
+            },
+            CsvError::MissingHeader { name } => ErrorDetail {
+                code: "missing_header".into(),
+                message: format!("missing required header: {name}"),
+                row: None,
+                field: Some(name.clone()),
+                value: None,
+                file: None,
+            },
+            CsvError::HeaderError { cause } => ErrorDetail {
+                code: "header_error".into(),
+                message: format!("failed to read header row: {cause}"),
+                row: None,
+                field: None,
+                value: None,
+                file: None,
+            },
+        }
+    }
+}
+
+impl From<&CsvError> for ErrorResponse {
+    fn from(error: &CsvError) -> Self {
+        ErrorResponse {
+            errors: vec![error.into()],
+        }
+    }
+}
+
+impl From<&[CsvError]> for ErrorResponse {
+    fn from(errors: &[CsvError]) -> Self {
+        ErrorResponse {
+            errors: errors.iter().map(ErrorDetail::from).collect(),
+        }
+    }
+}
+
+impl From<Vec<ErrorDetail>> for ErrorResponse {
+    fn from(errors: Vec<ErrorDetail>) -> Self {
+        ErrorResponse { errors }
+    }
+}
+
+/// The JSON body of a response to a dry run of a portfolio upload
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct DryRunResponse {
+    // the lots that would be added, removed or changed
+    pub diff: PortfolioDiff,
+
+    // the symbols whose summary would change
+    pub summary_by_symbol: Vec<GroupSummaryChange<String>>,
+}
+
+/// An element of the JSON body of a response to an upload of corporate actions, for each portfolio
+/// that they were applied to
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct AppliedCorporateActions {
+    pub portfolio_id: u32,
+
+    // the version of the portfolio that the actions resulted in
+    pub version: u32,
+
+    // the gains realized by cash paid in lieu of fractional shares
+    pub realized_gains: Vec<RealizedGain>,
+}
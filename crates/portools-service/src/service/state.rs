@@ -1,7 +1,10 @@
-use crate::config::Limits;
+use crate::config::{Limits, WashSaleConfig};
+use crate::profile::ImportProfiles;
 use portools_common::dao::Dao;
 
 pub struct State {
     pub limits: Limits,
+    pub import_profiles: ImportProfiles,
+    pub wash_sales: WashSaleConfig,
     pub dao: Box<dyn Dao>,
 }
@@ -0,0 +1,232 @@
+use crate::digest::csv_to_transaction;
+use crate::service::corporate_action::stored_corporate_actions;
+use crate::service::response::{ErrorDetail, ErrorResponse};
+use crate::service::state::State;
+use crate::service::{replace_portfolio, source_filename, upload, util};
+use actix_web::web::{Data, Path};
+use actix_web::{web, HttpRequest, HttpResponse};
+use portools_common::model::{
+    find_wash_sales, CorporateAction, CorporateActionError, CurrencyError, Ledger, LedgerError,
+    Portfolio, PortfolioTransactions, PortfolioVersion, SaleError, Transaction,
+};
+
+// Replaces the transactions of the portfolio with those of the CSV, and replaces the lots of the
+// portfolio with the open lots that result from replaying them along with the stored corporate
+// actions
+pub async fn put_transactions(
+    path: Path<u32>,
+    payload: web::Payload,
+    req: HttpRequest,
+    data: Data<State>,
+) -> HttpResponse {
+    let portfolio_id = path.into_inner();
+    let limits = &data.limits.transactions;
+    if let Err(response) = upload::check_content_length(&req, limits.max_file_size) {
+        return response;
+    }
+    let csv = match upload::read_payload(payload, limits.max_file_size).await {
+        Ok(csv) => csv,
+        Err(response) => return response,
+    };
+    let transactions = match csv_to_transaction(&csv, limits.max_num_errors) {
+        Ok(transactions) => transactions,
+        Err(errors) => {
+            tracing::debug!(?errors, "failed to convert payload to transactions");
+            return HttpResponse::BadRequest().json(ErrorResponse::from(&errors[..]));
+        }
+    };
+    let corporate_actions = match stored_corporate_actions(&data).await {
+        Ok(actions) => actions,
+        Err(response) => return response,
+    };
+    let mut portfolio = match Ledger::replay(&transactions, &corporate_actions) {
+        Ok(ledger) => ledger.into_portfolio(portfolio_id),
+        Err(LedgerError::InsufficientQuantity { index, held }) => {
+            tracing::debug!(index, %held, "transaction disposes of more shares than are held");
+            return HttpResponse::UnprocessableEntity().json(ErrorResponse::from(vec![
+                ErrorDetail::insufficient_quantity(index, held),
+            ]));
+        }
+        Err(LedgerError::SaleError {
+            index,
+            cause: SaleError::LotNotFound { lot_id },
+        }) => {
+            tracing::debug!(
+                index,
+                lot_id,
+                "transaction disposes of a lot that is not held"
+            );
+            return HttpResponse::UnprocessableEntity().json(ErrorResponse::from(vec![
+                ErrorDetail::lot_not_found(index, lot_id),
+            ]));
+        }
+        Err(LedgerError::SaleError {
+            index,
+            cause:
+                SaleError::GainError {
+                    cause: CurrencyError::SymbolMismatch { left, right },
+                },
+        }) => {
+            tracing::debug!(index, %left, %right, "transaction price and cost basis in different currencies");
+            return HttpResponse::UnprocessableEntity().json(ErrorResponse::from(vec![
+                ErrorDetail::currency_mismatch(index, &left, &right),
+            ]));
+        }
+        Err(LedgerError::CorporateActionError { action, cause }) => match *cause {
+            CorporateActionError::CashInLieuError {
+                cause:
+                    SaleError::GainError {
+                        cause: CurrencyError::SymbolMismatch { left, right },
+                    },
+            } => {
+                tracing::debug!(?action, %left, %right, "cash in lieu and cost basis in different currencies");
+                return HttpResponse::UnprocessableEntity().json(ErrorResponse::from(vec![
+                    ErrorDetail::corporate_action_currency_mismatch(&action, &left, &right),
+                ]));
+            }
+            cause => {
+                tracing::error!(?action, ?cause, "failed to apply corporate action");
+                return HttpResponse::InternalServerError().finish();
+            }
+        },
+        Err(error) => {
+            tracing::error!(?error, "failed to replay transactions");
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    // the lots were derived along with every stored corporate action
+    portfolio.corporate_actions = corporate_actions.iter().map(CorporateAction::id).collect();
+    // the lots that remain are limited as though they had been uploaded directly
+    let max_num_lots = data.limits.portfolio.max_num_lots;
+    if portfolio.lots.len() > max_num_lots {
+        tracing::debug!(
+            max_num_lots,
+            "transactions result in more than the maximum number of lots"
+        );
+        return HttpResponse::PayloadTooLarge().finish();
+    }
+    let current_version = match data.dao.get_portfolio(portfolio_id).await {
+        Ok(current) => current.map(|p| p.version),
+        Err(error) => {
+            tracing::error!(?error, "failed to get portfolio");
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    if !util::if_match_satisfied(&req, current_version) {
+        tracing::debug!(?current_version, "If-Match header not satisfied");
+        return HttpResponse::PreconditionFailed().finish();
+    }
+    portfolio.version = current_version.unwrap_or(0) + 1;
+    let transactions = PortfolioTransactions {
+        id: portfolio_id,
+        version: portfolio.version,
+        transactions,
+    };
+    let version = PortfolioVersion::new(&portfolio, source_filename(&req));
+    replace_portfolio(&portfolio, Some(&transactions), version, &req, &data).await
+}
+
+// The transactions that the lots of the portfolio were derived from. Responds with 404 if the
+// portfolio does not exist, or if its lots were not derived from transactions.
+async fn current_transactions(
+    portfolio_id: u32,
+    data: &Data<State>,
+) -> Result<Vec<Transaction>, HttpResponse> {
+    let portfolio = match data.dao.get_portfolio(portfolio_id).await {
+        Ok(Some(portfolio)) => portfolio,
+        Ok(None) => return Err(HttpResponse::NotFound().finish()),
+        Err(error) => {
+            tracing::error!(?error, "failed to get portfolio");
+            return Err(HttpResponse::InternalServerError().finish());
+        }
+    };
+    match derived_transactions(&portfolio, data).await? {
+        Some(transactions) => Ok(transactions.transactions),
+        None => Err(HttpResponse::NotFound().finish()),
+    }
+}
+
+// The transactions that the lots of the given version of the portfolio were derived from, or None
+// if they were not derived from transactions
+pub(crate) async fn derived_transactions(
+    portfolio: &Portfolio,
+    data: &Data<State>,
+) -> Result<Option<PortfolioTransactions>, HttpResponse> {
+    let transactions = match data.dao.get_transactions(portfolio.id).await {
+        Ok(transactions) => transactions,
+        Err(error) => {
+            tracing::error!(?error, "failed to get transactions");
+            return Err(HttpResponse::InternalServerError().finish());
+        }
+    };
+    // the lots were since modified other than by transactions (e.g. a lot was added, or a snapshot
+    // of the lots was uploaded), so they are no longer derived from the transactions
+    Ok(transactions.filter(|transactions| {
+        let derived = transactions.version == portfolio.version;
+        if !derived {
+            tracing::debug!(
+                transactions.version,
+                portfolio.version,
+                "transactions are of a different version than the portfolio"
+            );
+        }
+        derived
+    }))
+}
+
+// Responds with the transactions that the lots of the portfolio were derived from
+pub async fn get_transactions(path: Path<u32>, data: Data<State>) -> HttpResponse {
+    let portfolio_id = path.into_inner();
+    match current_transactions(portfolio_id, &data).await {
+        Ok(transactions) => HttpResponse::Ok().json(transactions),
+        Err(response) => response,
+    }
+}
+
+// Responds with the gains realized by the sales among the transactions of the portfolio, and by
+// cash paid in lieu of fractional shares by corporate actions, in order of date
+pub async fn get_realized_gains(path: Path<u32>, data: Data<State>) -> HttpResponse {
+    let portfolio_id = path.into_inner();
+    let transactions = match current_transactions(portfolio_id, &data).await {
+        Ok(transactions) => transactions,
+        Err(response) => return response,
+    };
+    let corporate_actions = match stored_corporate_actions(&data).await {
+        Ok(actions) => actions,
+        Err(response) => return response,
+    };
+    // the transactions were replayed successfully when they were stored, and any corporate action
+    // stored since then was applied to the lots derived from them
+    match Ledger::replay(&transactions, &corporate_actions) {
+        Ok(ledger) => HttpResponse::Ok().json(ledger.realized_gains()),
+        Err(error) => {
+            tracing::error!(?error, "failed to replay transactions");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Responds with the sales among the transactions of the portfolio whose losses are disallowed as
+// wash sales, along with the adjustments to the cost basis of the shares that replaced them
+pub async fn get_wash_sales(path: Path<u32>, data: Data<State>) -> HttpResponse {
+    let portfolio_id = path.into_inner();
+    let transactions = match current_transactions(portfolio_id, &data).await {
+        Ok(transactions) => transactions,
+        Err(response) => return response,
+    };
+    let corporate_actions = match stored_corporate_actions(&data).await {
+        Ok(actions) => actions,
+        Err(response) => return response,
+    };
+    match find_wash_sales(
+        &transactions,
+        &corporate_actions,
+        &data.wash_sales.substantially_identical,
+    ) {
+        Ok(wash_sales) => HttpResponse::Ok().json(wash_sales),
+        Err(error) => {
+            tracing::error!(?error, "failed to find wash sales");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
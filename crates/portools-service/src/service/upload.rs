@@ -0,0 +1,235 @@
+use crate::config::PortfolioLimits;
+use crate::decompress::{gunzip, unzip_csvs, DecompressError};
+use crate::digest::{csv_to_lot_collecting_errors, CsvError, CsvLotDigester};
+use crate::profile::ImportProfile;
+use crate::service::response::{ErrorDetail, ErrorResponse};
+use crate::service::util;
+use crate::service::util::ContentLengthHeaderError::{Malformed, Missing};
+use crate::service::{APPLICATION_JSON, APPLICATION_ZIP};
+use actix_web::error::PayloadError;
+use actix_web::http::header::{ACCEPT_ENCODING, CONTENT_ENCODING};
+use actix_web::web::Bytes;
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use futures_util::StreamExt;
+use portools_common::model::{Lot, LotSpec};
+
+// Reads the lots within the payload, which is either a JSON array of lots or a CSV, depending upon
+// the Content-Type of the request. The payload may be sent without a Content-Length (e.g. with
+// chunked transfer encoding), so the limits are enforced as it is received: a CSV is converted
+// chunk by chunk, and the request is rejected as soon as it exceeds either the maximum size or
+// the given maximum number of lots.
+//
+// The payload may also be gzip compressed (per its Content-Encoding), or be a zip archive of
+// CSVs whose lots are merged in the order that the CSVs appear within the archive. Either is
+// buffered and decompressed up to the maximum decompressed size.
+//
+// CSVs are converted per the given import profile. Returns the response to send if the lots are
+// not valid.
+pub(crate) async fn read_lots(
+    req: &HttpRequest,
+    payload: web::Payload,
+    limits: &PortfolioLimits,
+    profile: &ImportProfile,
+    max_num_lots: usize,
+) -> Result<Vec<Lot>, HttpResponse> {
+    check_content_length(req, limits.max_file_size)?;
+    let is_json = req.content_type() == APPLICATION_JSON;
+    let lots = match get_content_encoding(req)? {
+        ContentEncoding::Identity if req.content_type() == APPLICATION_ZIP => {
+            let archive = read_payload(payload, limits.max_file_size).await?;
+            let csvs = unzip_csvs(&archive, limits.max_decompressed_size)
+                .map_err(decompress_error_response)?;
+            merge_csvs(csvs, profile, limits.max_num_errors)
+        }
+        ContentEncoding::Identity if is_json => json_to_lot(
+            &read_payload(payload, limits.max_file_size).await?,
+            limits.max_num_errors,
+        ),
+        ContentEncoding::Identity => stream_csv(payload, limits, profile, max_num_lots)
+            .await?
+            .map_err(|errors| ErrorResponse::from(&errors[..])),
+        ContentEncoding::Gzip => {
+            let compressed = read_payload(payload, limits.max_file_size).await?;
+            let decompressed = gunzip(&compressed, limits.max_decompressed_size)
+                .map_err(decompress_error_response)?;
+            if is_json {
+                json_to_lot(&decompressed, limits.max_num_errors)
+            } else {
+                csv_to_lot_collecting_errors(decompressed.into(), profile, limits.max_num_errors)
+                    .map_err(|errors| ErrorResponse::from(&errors[..]))
+            }
+        }
+    };
+    let lots = lots.map_err(|errors| {
+        tracing::debug!(?errors, "failed to convert payload to Lots");
+        HttpResponse::BadRequest().json(errors)
+    })?;
+    check_num_lots(lots.len(), max_num_lots)?;
+    Ok(lots)
+}
+
+enum ContentEncoding {
+    Identity,
+    Gzip,
+}
+
+fn get_content_encoding(req: &HttpRequest) -> Result<ContentEncoding, HttpResponse> {
+    let Some(encoding) = req.headers().get(CONTENT_ENCODING) else {
+        return Ok(ContentEncoding::Identity);
+    };
+    match encoding
+        .to_str()
+        .map(|encoding| encoding.trim().to_ascii_lowercase())
+    {
+        Ok(encoding) if encoding == "identity" => Ok(ContentEncoding::Identity),
+        Ok(encoding) if encoding == "gzip" || encoding == "x-gzip" => Ok(ContentEncoding::Gzip),
+        _ => {
+            tracing::debug!(?encoding, "unsupported Content-Encoding");
+            Err(HttpResponse::UnsupportedMediaType()
+                .insert_header((ACCEPT_ENCODING, "gzip"))
+                .finish())
+        }
+    }
+}
+
+// converts the CSV chunk by chunk, as it is received
+async fn stream_csv(
+    mut payload: web::Payload,
+    limits: &PortfolioLimits,
+    profile: &ImportProfile,
+    max_num_lots: usize,
+) -> Result<Result<Vec<Lot>, Vec<CsvError>>, HttpResponse> {
+    let mut digester = CsvLotDigester::new(profile, limits.max_num_errors);
+    let mut size = 0;
+    while let Some(chunk) = payload.next().await {
+        let chunk = check_chunk(chunk, &mut size, limits.max_file_size)?;
+        digester.digest(&chunk);
+        check_num_lots(digester.num_lots(), max_num_lots)?;
+    }
+    Ok(digester.finish())
+}
+
+// buffers the entire payload
+pub(crate) async fn read_payload(
+    mut payload: web::Payload,
+    max_file_size: usize,
+) -> Result<Vec<u8>, HttpResponse> {
+    let mut buffer = Vec::new();
+    let mut size = 0;
+    while let Some(chunk) = payload.next().await {
+        buffer.extend_from_slice(&check_chunk(chunk, &mut size, max_file_size)?);
+    }
+    Ok(buffer)
+}
+
+// checks that the next chunk of the payload was received, and that it does not take the size of
+// the payload received so far beyond the maximum
+fn check_chunk(
+    chunk: Result<Bytes, PayloadError>,
+    size: &mut usize,
+    max_file_size: usize,
+) -> Result<Bytes, HttpResponse> {
+    let chunk = chunk.map_err(|error| {
+        tracing::debug!(?error, "failed to read payload");
+        HttpResponse::BadRequest().finish()
+    })?;
+    *size += chunk.len();
+    if *size > max_file_size {
+        tracing::debug!(max_file_size, "payload exceeds maximum size");
+        return Err(HttpResponse::PayloadTooLarge().finish());
+    }
+    Ok(chunk)
+}
+
+fn decompress_error_response(error: DecompressError) -> HttpResponse {
+    tracing::debug!(?error, "failed to decompress payload");
+    let response = ErrorResponse::from(vec![ErrorDetail::from(&error)]);
+    match error {
+        DecompressError::TooLarge { .. } => HttpResponse::PayloadTooLarge().json(response),
+        _ => HttpResponse::BadRequest().json(response),
+    }
+}
+
+// Converts each of the CSVs, merging their lots. The errors of every CSV are reported, up to
+// max_errors in total, along with the name of the CSV that each of them pertains to.
+fn merge_csvs(
+    csvs: Vec<(String, Vec<u8>)>,
+    profile: &ImportProfile,
+    max_errors: usize,
+) -> Result<Vec<Lot>, ErrorResponse> {
+    let mut lots = Vec::new();
+    let mut errors = Vec::new();
+    for (name, csv) in csvs {
+        match csv_to_lot_collecting_errors(csv.into(), profile, max_errors - errors.len()) {
+            Ok(csv_lots) => lots.extend(csv_lots),
+            Err(csv_errors) => errors.extend(csv_errors.iter().map(|error| ErrorDetail {
+                file: Some(name.clone()),
+                ..ErrorDetail::from(error)
+            })),
+        }
+        if errors.len() >= max_errors {
+            break;
+        }
+    }
+    if errors.is_empty() {
+        Ok(lots)
+    } else {
+        Err(ErrorResponse::from(errors))
+    }
+}
+
+// Checks the Content-Length header, if any, against the maximum file size so that a payload that
+// is known to be too large is rejected before it is read
+pub(crate) fn check_content_length(
+    req: &HttpRequest,
+    max_file_size: usize,
+) -> Result<(), HttpResponse> {
+    let content_length = match util::get_content_length_header(req) {
+        Ok(content_length) => content_length,
+        Err(Malformed(message)) => {
+            tracing::debug!(%message, "malformed Content-Length header");
+            return Err(HttpResponse::BadRequest().finish());
+        }
+        Err(Missing) => return Ok(()),
+    };
+    if content_length > max_file_size {
+        tracing::debug!(
+            content_length,
+            max_content_length = max_file_size,
+            "Content-Length header exceeds maximum",
+        );
+        return Err(HttpResponse::PayloadTooLarge().finish());
+    }
+    Ok(())
+}
+
+fn check_num_lots(num_lots: usize, max_num_lots: usize) -> Result<(), HttpResponse> {
+    if num_lots > max_num_lots {
+        tracing::debug!(max_num_lots, "payload exceeds maximum number of lots");
+        return Err(HttpResponse::PayloadTooLarge().finish());
+    }
+    Ok(())
+}
+
+fn json_to_lot(json: &[u8], max_errors: usize) -> Result<Vec<Lot>, ErrorResponse> {
+    let specs: Vec<LotSpec> = serde_json::from_slice(json)
+        .map_err(|cause| ErrorResponse::from(vec![ErrorDetail::invalid_json(&cause)]))?;
+    let mut lots = Vec::with_capacity(specs.len());
+    let mut errors = Vec::new();
+    for (index, spec) in specs.into_iter().enumerate() {
+        match Lot::try_from(spec) {
+            Ok(lot) => lots.push(lot),
+            Err(invalid) => {
+                errors.push(ErrorDetail::invalid_element(index, &invalid));
+                if errors.len() >= max_errors {
+                    break;
+                }
+            }
+        }
+    }
+    if errors.is_empty() {
+        Ok(lots)
+    } else {
+        Err(ErrorResponse::from(errors))
+    }
+}
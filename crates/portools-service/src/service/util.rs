@@ -1,5 +1,5 @@
-use actix_web::http::header::CONTENT_LENGTH;
-use actix_web::HttpRequest;
+use actix_web::http::header::{ETag, EntityTag, IfMatch, IfNoneMatch, CONTENT_LENGTH};
+use actix_web::{HttpMessage, HttpRequest};
 use ContentLengthHeaderError::Malformed;
 
 pub fn get_content_length_header(
@@ -29,3 +29,41 @@ pub enum ContentLengthHeaderError {
     Missing,
     Malformed(String),
 }
+
+// the ETag of a portfolio of the given version, as JSON
+pub fn etag(version: u32) -> ETag {
+    ETag(EntityTag::new_strong(version.to_string()))
+}
+
+// the ETag of a portfolio of the given version, as CSV. Since each representation has its own
+// ETag, a copy of one is never taken to be an up to date copy of the other
+pub fn csv_etag(version: u32) -> ETag {
+    ETag(EntityTag::new_strong(format!("{version}-csv")))
+}
+
+// Whether the If-Match header of the request, if any, is satisfied by the current version of a
+// portfolio (None if it does not exist), as identified by the ETag of either representation. Per
+// RFC 9110, entity tags are compared strongly.
+pub fn if_match_satisfied(req: &HttpRequest, current_version: Option<u32>) -> bool {
+    match (req.get_header::<IfMatch>(), current_version) {
+        (None, _) => true,
+        (Some(_), None) => false,
+        (Some(IfMatch::Any), Some(_)) => true,
+        (Some(IfMatch::Items(tags)), Some(version)) => {
+            let (ETag(json), ETag(csv)) = (etag(version), csv_etag(version));
+            tags.iter()
+                .any(|tag| tag.strong_eq(&json) || tag.strong_eq(&csv))
+        }
+    }
+}
+
+// Whether the If-None-Match header of the request matches the ETag of the current representation
+// of a portfolio, meaning that the client's copy is up to date. Per RFC 9110, entity tags are
+// compared weakly.
+pub fn if_none_match_matches(req: &HttpRequest, current: &ETag) -> bool {
+    match req.get_header::<IfNoneMatch>() {
+        None => false,
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(current)),
+    }
+}
@@ -0,0 +1,95 @@
+use crate::service::state::State;
+use crate::service::{replace_portfolio, util};
+use actix_web::web::{Data, Path, Query};
+use actix_web::{HttpRequest, HttpResponse};
+use portools_common::model::{Page, PortfolioDiff, PortfolioVersion, PortfolioVersionInfo};
+
+// Responds with descriptions of the versions of the portfolio, the most recent first
+pub async fn list_versions(path: Path<u32>, page: Query<Page>, data: Data<State>) -> HttpResponse {
+    let portfolio_id = path.into_inner();
+    match data.dao.get_portfolio(portfolio_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(error) => {
+            tracing::error!(?error, "failed to get portfolio");
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+    match data.dao.list_portfolio_versions(portfolio_id, &page).await {
+        Ok(versions) => {
+            let infos: Vec<PortfolioVersionInfo> = versions.iter().map(|v| v.get_info()).collect();
+            HttpResponse::Ok().json(infos)
+        }
+        Err(error) => {
+            tracing::error!(?error, "failed to list portfolio versions");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+pub async fn get_version(path: Path<(u32, u32)>, data: Data<State>) -> HttpResponse {
+    let (portfolio_id, version) = path.into_inner();
+    match find_version(portfolio_id, version, &data).await {
+        Ok(version) => HttpResponse::Ok().json(version),
+        Err(response) => response,
+    }
+}
+
+// Responds with the changes to the lots from the first version to the other
+pub async fn diff_versions(path: Path<(u32, u32, u32)>, data: Data<State>) -> HttpResponse {
+    let (portfolio_id, version, other_version) = path.into_inner();
+    let before = match find_version(portfolio_id, version, &data).await {
+        Ok(before) => before,
+        Err(response) => return response,
+    };
+    let after = match find_version(portfolio_id, other_version, &data).await {
+        Ok(after) => after,
+        Err(response) => return response,
+    };
+    HttpResponse::Ok().json(PortfolioDiff::between(&before.lots, &after.lots))
+}
+
+// Makes the lots of the version current again, as a new version of the portfolio
+pub async fn restore_version(
+    path: Path<(u32, u32)>,
+    req: HttpRequest,
+    data: Data<State>,
+) -> HttpResponse {
+    let (portfolio_id, version) = path.into_inner();
+    let current = match data.dao.get_portfolio(portfolio_id).await {
+        Ok(Some(current)) => current,
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(error) => {
+            tracing::error!(?error, "failed to get portfolio");
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    if !util::if_match_satisfied(&req, Some(current.version)) {
+        tracing::debug!(current.version, "If-Match header not satisfied");
+        return HttpResponse::PreconditionFailed().finish();
+    }
+    let restored = match find_version(portfolio_id, version, &data).await {
+        Ok(version) => version.restore(&current),
+        Err(response) => return response,
+    };
+    let restored_version = PortfolioVersion {
+        restored_from: Some(version),
+        ..PortfolioVersion::new(&restored, None)
+    };
+    replace_portfolio(&restored, None, restored_version, &req, &data).await
+}
+
+async fn find_version(
+    portfolio_id: u32,
+    version: u32,
+    data: &Data<State>,
+) -> Result<PortfolioVersion, HttpResponse> {
+    match data.dao.get_portfolio_version(portfolio_id, version).await {
+        Ok(Some(version)) => Ok(version),
+        Ok(None) => Err(HttpResponse::NotFound().finish()),
+        Err(error) => {
+            tracing::error!(?error, "failed to get portfolio version");
+            Err(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
@@ -3,25 +3,1020 @@ mod tests {
     use crate::util;
     use crate::util::test_config;
     use actix_web::{test, App};
+    use chrono::Utc;
+    use portools_common::model::{
+        AcquisitionYear, AssetClass, CorporateAction, CorporateActionId, Currency, FxRate,
+        GroupSummary, HoldingPeriod, Lot, Portfolio, PortfolioDiff, PortfolioMetadata,
+        PortfolioSummary, PortfolioVersion, PortfolioVersionInfo, RealizedGain, Transaction,
+        TransactionKind, WashSale,
+    };
+    use portools_service::config::{Limits, PortfolioLimits, UploadLimits};
+    use portools_service::service;
+    use portools_service::service::response::{
+        AppliedCorporateActions, DryRunResponse, ErrorResponse,
+    };
+    use rust_decimal::Decimal;
+    use std::collections::HashMap;
+
+    #[actix_web::test]
+    async fn test_portfolio_get_not_found() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        util::put_portfolio(1, "valid.csv", &app).await;
+
+        // assert the state
+        let get_request = test::TestRequest::get().uri("/portfolio/2").to_request();
+        let get_response = test::call_service(&app, get_request).await;
+        assert_eq!(404, get_response.status().as_u16());
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_put_with_valid_then_get() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        util::put_portfolio(1, "valid.csv", &app).await;
+        let resp = util::get_portfolio(1, &app).await;
+        assert_eq!(&util::expected_valid_portfolio(1), &resp);
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_get_with_decimals_as_strings() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        util::put_portfolio(1, "valid.csv", &app).await;
+        let req = test::TestRequest::get().uri("/portfolio/1").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let lot = &body["lots"][0];
+        assert_eq!("1", lot["quantity"]);
+        assert_eq!("100.47", lot["cost_basis"]["amount"]);
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_update_existing() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        util::put_portfolio(1, "valid.csv", &app).await;
+        let resp = util::get_portfolio(1, &app).await;
+        assert_eq!(&util::expected_valid_portfolio(1), &resp);
+
+        util::put_portfolio(1, "valid_2.csv", &app).await;
+        let resp = util::get_portfolio(1, &app).await;
+        assert_eq!(&util::expected_valid_2_portfolio(1), &resp);
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_put_multiple() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        util::put_portfolio(1, "valid.csv", &app).await;
+        util::put_portfolio(2, "valid_2.csv", &app).await;
+        let resp_1 = util::get_portfolio(1, &app).await;
+        assert_eq!(&util::expected_valid_portfolio(1), &resp_1);
+
+        let resp = util::get_portfolio(2, &app).await;
+        assert_eq!(&util::expected_valid_2_portfolio(2), &resp);
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_put_with_invalid_value() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        let resp = util::put_csv("/portfolio/1", "row_with_invalid_value.csv", &app).await;
+        assert_eq!(400, resp.status().as_u16());
+        let body: ErrorResponse = test::read_body_json(resp).await;
+        assert_eq!(1, body.errors.len());
+        let error = &body.errors[0];
+        assert_eq!("parse_money_error", error.code);
+        assert_eq!(Some(2), error.row);
+        assert_eq!(Some("cost_per_share".into()), error.field);
+        assert_eq!(Some("NOT_A_NUMBER".into()), error.value);
+        assert!(!error.message.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_put_with_invalid_values() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        let resp = util::put_csv("/portfolio/1", "rows_with_invalid_values.csv", &app).await;
+        assert_eq!(400, resp.status().as_u16());
+        let body: ErrorResponse = test::read_body_json(resp).await;
+        let errors: Vec<_> = body
+            .errors
+            .iter()
+            .map(|error| {
+                (
+                    &error.code[..],
+                    error.row,
+                    error.field.as_deref(),
+                    error.value.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    "parse_money_error",
+                    Some(2),
+                    Some("cost_per_share"),
+                    Some("NOT_A_NUMBER")
+                ),
+                ("must_be_positive", Some(3), Some("quantity"), Some("-3")),
+                (
+                    "parse_money_error",
+                    Some(5),
+                    Some("cost_per_share"),
+                    Some("ALSO_NOT_A_NUMBER")
+                ),
+            ],
+            errors
+        );
+        assert_eq!(404, util::get_status("/portfolio/1", &app).await);
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_put_with_invalid_values_exceeding_max_num_errors() {
+        let dao = util::init_dao().await;
+        let limits = Limits {
+            portfolio: PortfolioLimits {
+                max_num_errors: 2,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let app = test::init_service(App::new().configure(move |cfg| {
+            util::test_config_with_limits(cfg, dao, limits);
+        }))
+        .await;
+
+        let resp = util::put_csv("/portfolio/1", "rows_with_invalid_values.csv", &app).await;
+        assert_eq!(400, resp.status().as_u16());
+        let body: ErrorResponse = test::read_body_json(resp).await;
+        let rows: Vec<_> = body.errors.iter().map(|error| error.row).collect();
+        assert_eq!(vec![Some(2), Some(3)], rows);
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_put_dry_run() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        util::put_portfolio(1, "valid.csv", &app).await;
+        let resp = util::put_csv("/portfolio/1?dry_run=true", "valid_2.csv", &app).await;
+        assert_eq!(200, resp.status().as_u16());
+        let body: DryRunResponse = test::read_body_json(resp).await;
+        let expected_diff = PortfolioDiff::between(
+            &util::expected_valid_portfolio(1).lots,
+            &util::expected_valid_2_portfolio(1).lots,
+        );
+        assert_eq!(expected_diff, body.diff);
+        assert_eq!(3, body.diff.removed.len());
+        assert_eq!(2, body.diff.added.len());
+        assert_eq!(4, body.summary_by_symbol.len());
+
+        // nothing was persisted
+        let resp = util::get_portfolio(1, &app).await;
+        assert_eq!(&util::expected_valid_portfolio(1), &resp);
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_put_dry_run_for_new_portfolio() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        let resp = util::put_csv("/portfolio/1?dry_run=true", "valid.csv", &app).await;
+        assert_eq!(200, resp.status().as_u16());
+        let body: DryRunResponse = test::read_body_json(resp).await;
+        assert_eq!(3, body.diff.added.len());
+        assert_eq!(404, util::get_status("/portfolio/1", &app).await);
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_put_json_then_get() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        let expected = util::expected_valid_portfolio(1);
+        let resp = util::put_json("/portfolio/1", &util::to_specs(&expected.lots), &app).await;
+        assert_eq!(200, resp.status().as_u16());
+        let resp = util::get_portfolio(1, &app).await;
+        assert_eq!(&expected, &resp);
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_put_json_with_invalid_lots() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        let mut specs = util::to_specs(&util::expected_valid_portfolio(1).lots);
+        specs[0].symbol = "".into();
+        specs[2].quantity = Decimal::ZERO;
+        let resp = util::put_json("/portfolio/1", &specs, &app).await;
+        assert_eq!(400, resp.status().as_u16());
+        let body: ErrorResponse = test::read_body_json(resp).await;
+        let errors: Vec<(&str, Option<usize>)> = body
+            .errors
+            .iter()
+            .map(|e| (e.code.as_str(), e.row))
+            .collect();
+        assert_eq!(
+            vec![
+                ("must_have_longer_len", Some(1)),
+                ("must_be_positive", Some(3))
+            ],
+            errors
+        );
+        assert_eq!(404, util::get_status("/portfolio/1", &app).await);
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_put_malformed_json() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        let json = r#"[{"account": "Taxable"}"#;
+        let req = test::TestRequest::put()
+            .uri("/portfolio/1")
+            .append_header(("Content-Type", "application/json"))
+            .append_header(("Content-Length", json.len()))
+            .set_payload(json)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(400, resp.status().as_u16());
+        let body: ErrorResponse = test::read_body_json(resp).await;
+        assert_eq!(1, body.errors.len());
+        assert_eq!("invalid_json", body.errors[0].code);
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_put_json_dry_run() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        util::put_portfolio(1, "valid.csv", &app).await;
+        let specs = util::to_specs(&util::expected_valid_2_portfolio(1).lots);
+        let resp = util::put_json("/portfolio/1?dry_run=true", &specs, &app).await;
+        assert_eq!(200, resp.status().as_u16());
+        let body: DryRunResponse = test::read_body_json(resp).await;
+        assert_eq!(3, body.diff.removed.len());
+        assert_eq!(2, body.diff.added.len());
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_get_csv_then_put() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        util::put_portfolio(1, "valid.csv", &app).await;
+        let req = test::TestRequest::get()
+            .uri("/portfolio/1")
+            .append_header(("Accept", "text/csv"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(200, resp.status().as_u16());
+        assert_eq!("text/csv", resp.headers().get("Content-Type").unwrap());
+        let csv = test::read_body(resp).await;
+
+        // the exported CSV may be uploaded as is
+        let req = test::TestRequest::put()
+            .uri("/portfolio/2")
+            .append_header(("Content-Length", csv.len()))
+            .set_payload(csv)
+            .to_request();
+        assert_eq!(200, test::call_service(&app, req).await.status().as_u16());
+        let resp = util::get_portfolio(2, &app).await;
+        assert_eq!(&util::expected_valid_portfolio(2), &resp);
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_get_prefers_json() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        util::put_portfolio(1, "valid.csv", &app).await;
+        for accept in ["application/json, text/csv;q=0.5", "*/*", "text/html"] {
+            let req = test::TestRequest::get()
+                .uri("/portfolio/1")
+                .append_header(("Accept", accept))
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(
+                "application/json",
+                resp.headers().get("Content-Type").unwrap(),
+                "Accept: {accept}"
+            );
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_get_with_query() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        util::put_portfolio(1, "valid.csv", &app).await;
+        util::post_csv("/portfolio/1/lots", "valid_2.csv", &app).await;
+        let lot_ids = |portfolio: Portfolio| -> Vec<u32> {
+            portfolio.lots.iter().filter_map(|lot| lot.id).collect()
+        };
+        let get = |query: &str| {
+            let req = test::TestRequest::get()
+                .uri(&format!("/portfolio/1?{query}"))
+                .to_request();
+            test::call_and_read_body_json(&app, req)
+        };
+
+        assert_eq!(vec![2, 3], lot_ids(get("account=IRA&symbol=BND").await));
+        assert_eq!(
+            vec![3],
+            lot_ids(get("acquired_after=2023-03-28&acquired_before=2023-04-01").await)
+        );
+        // ties are broken by id
+        assert_eq!(
+            vec![3, 5, 2],
+            lot_ids(get("sort=date_acquired&order=desc&limit=3").await)
+        );
+        assert_eq!(vec![2, 3], lot_ids(get("sort=id&offset=1&limit=2").await));
+        assert_eq!(
+            400,
+            util::get_status("/portfolio/1?acquired_after=oops", &app).await
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_list() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        let list = |query: &str| {
+            let req = test::TestRequest::get()
+                .uri(&format!("/portfolio?{query}"))
+                .to_request();
+            test::call_and_read_body_json(&app, req)
+        };
+        let listed: Vec<PortfolioMetadata> = list("").await;
+        assert!(listed.is_empty());
+
+        util::put_portfolio(3, "valid_2.csv", &app).await;
+        util::put_portfolio(1, "valid.csv", &app).await;
+        util::put_portfolio(2, "valid.csv", &app).await;
+
+        let listed: Vec<PortfolioMetadata> = list("").await;
+        let ids: Vec<u32> = listed.iter().map(|m| m.id).collect();
+        assert_eq!(vec![1, 2, 3], ids);
+        let metadata = &listed[2];
+        assert_eq!(2, metadata.num_lots);
+        assert!(metadata.last_updated.is_some());
+        // 5 * 55.55 + 4 * 222.22
+        assert_eq!(
+            vec![Currency::new(Decimal::new(116663, 2), "USD").unwrap()],
+            metadata.total_cost
+        );
+
+        let listed: Vec<PortfolioMetadata> = list("offset=1&limit=1").await;
+        let ids: Vec<u32> = listed.iter().map(|m| m.id).collect();
+        assert_eq!(vec![2], ids);
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_etag() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        let resp = util::put_csv("/portfolio/1", "valid.csv", &app).await;
+        assert_eq!(Some("\"1\""), util::etag(&resp).as_deref());
+
+        let req = test::TestRequest::get().uri("/portfolio/1").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(200, resp.status().as_u16());
+        assert_eq!(Some("\"1\""), util::etag(&resp).as_deref());
+
+        let req = test::TestRequest::get()
+            .uri("/portfolio/1")
+            .append_header(("If-None-Match", "\"1\""))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(304, resp.status().as_u16());
+
+        // modifying a lot increments the version
+        let req = test::TestRequest::delete()
+            .uri("/portfolio/1/lots/1")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(Some("\"2\""), util::etag(&resp).as_deref());
+
+        let req = test::TestRequest::get()
+            .uri("/portfolio/1")
+            .append_header(("If-None-Match", "\"1\""))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(200, resp.status().as_u16());
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_etag_per_representation() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        util::put_portfolio(1, "valid.csv", &app).await;
+        let req = test::TestRequest::get()
+            .uri("/portfolio/1")
+            .append_header(("Accept", "text/csv"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(Some("\"1-csv\""), util::etag(&resp).as_deref());
+        assert_eq!("Accept", resp.headers().get("Vary").unwrap());
+
+        // a copy of the CSV is not an up to date copy of the JSON
+        let req = test::TestRequest::get()
+            .uri("/portfolio/1")
+            .append_header(("If-None-Match", "\"1-csv\""))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(200, resp.status().as_u16());
+        assert_eq!(Some("\"1\""), util::etag(&resp).as_deref());
+        assert_eq!("Accept", resp.headers().get("Vary").unwrap());
+
+        let req = test::TestRequest::get()
+            .uri("/portfolio/1")
+            .append_header(("Accept", "text/csv"))
+            .append_header(("If-None-Match", "\"1-csv\""))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(304, resp.status().as_u16());
+
+        // either ETag identifies the version that a modification is conditional upon
+        let csv = util::load_bytes("valid.csv");
+        let req = test::TestRequest::put()
+            .uri("/portfolio/1")
+            .append_header(("If-Match", "\"1-csv\""))
+            .append_header(("Content-Length", csv.len()))
+            .set_payload(csv)
+            .to_request();
+        assert_eq!(200, test::call_service(&app, req).await.status().as_u16());
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_put_if_match() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        let put_if_match = |uri: &'static str, etag: &'static str| {
+            let csv = util::load_bytes("valid_2.csv");
+            let req = test::TestRequest::put()
+                .uri(uri)
+                .append_header(("Content-Length", csv.len()))
+                .append_header(("If-Match", etag))
+                .set_payload(csv)
+                .to_request();
+            test::call_service(&app, req)
+        };
+
+        // the portfolio must exist
+        assert_eq!(
+            412,
+            put_if_match("/portfolio/1", "*").await.status().as_u16()
+        );
+        assert_eq!(404, util::get_status("/portfolio/1", &app).await);
+
+        util::put_portfolio(1, "valid.csv", &app).await;
+        let resp = put_if_match("/portfolio/1", "\"1\"").await;
+        assert_eq!(200, resp.status().as_u16());
+        assert_eq!(Some("\"2\""), util::etag(&resp).as_deref());
+
+        // the version is now stale
+        let resp = put_if_match("/portfolio/1", "\"1\"").await;
+        assert_eq!(412, resp.status().as_u16());
+        let resp = util::get_portfolio(1, &app).await;
+        assert_eq!(&util::expected_valid_2_portfolio(1), &resp);
+    }
+
+    #[actix_web::test]
+    async fn test_lots_if_match() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        util::put_portfolio(1, "valid.csv", &app).await;
+        let add_if_match = |etag: &'static str| {
+            let csv = util::load_bytes("valid_2.csv");
+            let req = test::TestRequest::post()
+                .uri("/portfolio/1/lots")
+                .append_header(("Content-Length", csv.len()))
+                .append_header(("If-Match", etag))
+                .set_payload(csv)
+                .to_request();
+            test::call_service(&app, req)
+        };
+        let spec = util::to_specs(&util::expected_valid_portfolio(1).lots).remove(0);
+        let update_if_match = |etag: &'static str| {
+            let req = test::TestRequest::put()
+                .uri("/portfolio/1/lots/1")
+                .append_header(("If-Match", etag))
+                .set_json(&spec)
+                .to_request();
+            test::call_service(&app, req)
+        };
+        let delete_if_match = |etag: &'static str| {
+            let req = test::TestRequest::delete()
+                .uri("/portfolio/1/lots/1")
+                .append_header(("If-Match", etag))
+                .to_request();
+            test::call_service(&app, req)
+        };
+
+        // each modification is rejected if the version is stale
+        assert_eq!(412, add_if_match("\"2\"").await.status().as_u16());
+        assert_eq!(412, update_if_match("\"2\"").await.status().as_u16());
+        assert_eq!(412, delete_if_match("\"2\"").await.status().as_u16());
+        let resp = util::get_portfolio(1, &app).await;
+        assert_eq!(&util::expected_valid_portfolio(1), &resp);
+
+        let resp = add_if_match("\"1\"").await;
+        assert_eq!(200, resp.status().as_u16());
+        assert_eq!(Some("\"2\""), util::etag(&resp).as_deref());
+        let resp = update_if_match("\"2\"").await;
+        assert_eq!(200, resp.status().as_u16());
+        assert_eq!(Some("\"3\""), util::etag(&resp).as_deref());
+        let resp = delete_if_match("\"3\"").await;
+        assert_eq!(204, resp.status().as_u16());
+        assert_eq!(Some("\"4\""), util::etag(&resp).as_deref());
+
+        // the portfolio must exist
+        let req = test::TestRequest::delete()
+            .uri("/portfolio/2/lots/1")
+            .append_header(("If-Match", "*"))
+            .to_request();
+        assert_eq!(412, test::call_service(&app, req).await.status().as_u16());
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_put_without_content_length() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        // as when the payload is sent with chunked transfer encoding
+        let req = test::TestRequest::put()
+            .uri("/portfolio/1")
+            .set_payload(util::load_bytes("valid.csv"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(200, resp.status().as_u16());
+        let resp = util::get_portfolio(1, &app).await;
+        assert_eq!(&util::expected_valid_portfolio(1), &resp);
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_put_exceeding_max_file_size() {
+        let dao = util::init_dao().await;
+        let limits = Limits {
+            portfolio: PortfolioLimits {
+                max_file_size: 100,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let app = test::init_service(App::new().configure(move |cfg| {
+            util::test_config_with_limits(cfg, dao, limits);
+        }))
+        .await;
+
+        let resp = util::put_csv("/portfolio/1", "valid.csv", &app).await;
+        assert_eq!(413, resp.status().as_u16());
+
+        // the limit applies even when the size of the payload is not known in advance
+        let req = test::TestRequest::put()
+            .uri("/portfolio/1")
+            .set_payload(util::load_bytes("valid.csv"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(413, resp.status().as_u16());
+        assert_eq!(404, util::get_status("/portfolio/1", &app).await);
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_put_exceeding_max_num_lots() {
+        let dao = util::init_dao().await;
+        let limits = Limits {
+            portfolio: PortfolioLimits {
+                max_num_lots: 4,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let app = test::init_service(App::new().configure(move |cfg| {
+            util::test_config_with_limits(cfg, dao, limits);
+        }))
+        .await;
+
+        util::put_portfolio(1, "valid.csv", &app).await;
+
+        // the limit applies to the lots of the resulting portfolio
+        let resp = util::post_csv("/portfolio/1/lots", "valid_2.csv", &app).await;
+        assert_eq!(413, resp.status().as_u16());
+        let lots = util::expected_valid_2_portfolio(1).lots;
+        let resp = util::post_json("/portfolio/1/lots", &util::to_specs(&lots), &app).await;
+        assert_eq!(413, resp.status().as_u16());
+        let resp = util::get_portfolio(1, &app).await;
+        assert_eq!(&util::expected_valid_portfolio(1), &resp);
+
+        let lots = util::expected_valid_2_portfolio(1).lots;
+        let resp = util::post_json("/portfolio/1/lots", &util::to_specs(&lots[..1]), &app).await;
+        assert_eq!(200, resp.status().as_u16());
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_put_gzip() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        let req = test::TestRequest::put()
+            .uri("/portfolio/1")
+            .append_header(("Content-Encoding", "gzip"))
+            .set_payload(util::gzip(&util::load_bytes("valid.csv")))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(200, resp.status().as_u16());
+        let resp = util::get_portfolio(1, &app).await;
+        assert_eq!(&util::expected_valid_portfolio(1), &resp);
+
+        let req = test::TestRequest::put()
+            .uri("/portfolio/1")
+            .append_header(("Content-Encoding", "br"))
+            .set_payload(util::load_bytes("valid.csv"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(415, resp.status().as_u16());
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_put_gzip_exceeding_max_decompressed_size() {
+        let dao = util::init_dao().await;
+        let limits = Limits {
+            portfolio: PortfolioLimits {
+                max_decompressed_size: 1_000,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let app = test::init_service(App::new().configure(move |cfg| {
+            util::test_config_with_limits(cfg, dao, limits);
+        }))
+        .await;
+
+        let csv = util::load_bytes("valid.csv").repeat(20);
+        let req = test::TestRequest::put()
+            .uri("/portfolio/1")
+            .append_header(("Content-Encoding", "gzip"))
+            .set_payload(util::gzip(&csv))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(413, resp.status().as_u16());
+        let body: ErrorResponse = test::read_body_json(resp).await;
+        assert_eq!("too_large", body.errors[0].code);
+        assert_eq!(404, util::get_status("/portfolio/1", &app).await);
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_put_zip() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        let archive = util::zip(&["valid.csv", "valid_2.csv"]);
+        let resp = util::put_zip("/portfolio/1", archive, &app).await;
+        assert_eq!(200, resp.status().as_u16());
+
+        // the lots of the CSVs are merged, in the order of the archive
+        let mut expected = util::expected_valid_portfolio(1);
+        expected.add_lots(util::expected_valid_2_portfolio(1).lots);
+        let resp = util::get_portfolio(1, &app).await;
+        assert_eq!(&expected, &resp);
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_put_zip_with_invalid_csv() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        let archive = util::zip(&["valid.csv", "row_with_invalid_value.csv"]);
+        let resp = util::put_zip("/portfolio/1", archive, &app).await;
+        assert_eq!(400, resp.status().as_u16());
+        let body: ErrorResponse = test::read_body_json(resp).await;
+        assert_eq!(1, body.errors.len());
+        let error = &body.errors[0];
+        assert_eq!("parse_money_error", error.code);
+        assert_eq!(Some(2), error.row);
+        assert_eq!(Some("row_with_invalid_value.csv".into()), error.file);
+
+        let resp = util::put_zip("/portfolio/1", b"not a zip".to_vec(), &app).await;
+        assert_eq!(400, resp.status().as_u16());
+        assert_eq!(404, util::get_status("/portfolio/1", &app).await);
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_put_with_profile() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        let resp = util::put_csv("/portfolio/1?profile=fidelity", "fidelity.csv", &app).await;
+        assert_eq!(200, resp.status().as_u16());
+        let resp = util::get_portfolio(1, &app).await;
+        assert_eq!(&util::expected_valid_portfolio(1), &resp);
+
+        // the generic profile does not recognize the headers
+        let resp = util::post_csv("/portfolio/1/lots", "fidelity.csv", &app).await;
+        assert_eq!(400, resp.status().as_u16());
+
+        let resp = util::post_csv("/portfolio/1/lots?profile=unknown", "valid.csv", &app).await;
+        assert_eq!(400, resp.status().as_u16());
+        let body: ErrorResponse = test::read_body_json(resp).await;
+        assert_eq!("unknown_profile", body.errors[0].code);
+    }
+
+    #[actix_web::test]
+    async fn test_portfolio_delete() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        util::put_portfolio(1, "valid.csv", &app).await;
+        util::put_portfolio(2, "valid_2.csv", &app).await;
+        assert_eq!(204, util::delete_portfolio(1, &app).await);
+        assert_eq!(404, util::get_status("/portfolio/1", &app).await);
+        assert_eq!(404, util::delete_portfolio(1, &app).await);
+
+        // other portfolios are unaffected
+        let resp = util::get_portfolio(2, &app).await;
+        assert_eq!(&util::expected_valid_2_portfolio(2), &resp);
+    }
+
+    #[actix_web::test]
+    async fn test_lots_add_csv() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        util::put_portfolio(1, "valid.csv", &app).await;
+        let resp = util::post_csv("/portfolio/1/lots", "valid_2.csv", &app).await;
+        assert_eq!(200, resp.status().as_u16());
+        let added: Vec<Lot> = test::read_body_json(resp).await;
+
+        let mut expected = util::expected_valid_portfolio(1);
+        let expected_added = expected
+            .add_lots(util::expected_valid_2_portfolio(1).lots)
+            .to_vec();
+        assert_eq!(expected_added, added);
+        let resp = util::get_portfolio(1, &app).await;
+        assert_eq!(&expected, &resp);
+    }
+
+    #[actix_web::test]
+    async fn test_lots_add_json() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        util::put_portfolio(1, "valid.csv", &app).await;
+        let lots = util::expected_valid_2_portfolio(1).lots;
+        let resp = util::post_json("/portfolio/1/lots", &util::to_specs(&lots), &app).await;
+        assert_eq!(200, resp.status().as_u16());
+
+        let mut expected = util::expected_valid_portfolio(1);
+        expected.add_lots(lots);
+        let resp = util::get_portfolio(1, &app).await;
+        assert_eq!(&expected, &resp);
+    }
+
+    #[actix_web::test]
+    async fn test_lots_add_json_with_invalid_lot() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        util::put_portfolio(1, "valid.csv", &app).await;
+        let mut specs = util::to_specs(&util::expected_valid_2_portfolio(1).lots);
+        specs[1].quantity = Decimal::ZERO;
+        let resp = util::post_json("/portfolio/1/lots", &specs, &app).await;
+        assert_eq!(400, resp.status().as_u16());
+        let body: ErrorResponse = test::read_body_json(resp).await;
+        assert_eq!(1, body.errors.len());
+        assert_eq!("must_be_positive", body.errors[0].code);
+        assert_eq!(Some(2), body.errors[0].row);
+        assert_eq!(Some("quantity".into()), body.errors[0].field);
+
+        // nothing was added
+        let resp = util::get_portfolio(1, &app).await;
+        assert_eq!(&util::expected_valid_portfolio(1), &resp);
+    }
+
+    #[actix_web::test]
+    async fn test_lots_add_to_missing_portfolio() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        let resp = util::post_csv("/portfolio/1/lots", "valid.csv", &app).await;
+        assert_eq!(404, resp.status().as_u16());
+    }
+
+    #[actix_web::test]
+    async fn test_lot_update() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        util::put_portfolio(1, "valid.csv", &app).await;
+        let mut expected = util::expected_valid_portfolio(1);
+        let mut lot = expected.get_lot(2).unwrap().clone();
+        lot.quantity = Decimal::from(42);
+        let spec = util::to_specs(&[lot.clone()]).remove(0);
+
+        let req = test::TestRequest::put()
+            .uri("/portfolio/1/lots/2")
+            .set_json(&spec)
+            .to_request();
+        let updated: Lot = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(lot, updated);
+        expected.update_lot(lot);
+        let resp = util::get_portfolio(1, &app).await;
+        assert_eq!(&expected, &resp);
+
+        let req = test::TestRequest::put()
+            .uri("/portfolio/1/lots/99")
+            .set_json(&spec)
+            .to_request();
+        assert_eq!(404, test::call_service(&app, req).await.status().as_u16());
+    }
+
+    #[actix_web::test]
+    async fn test_lot_delete() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        util::put_portfolio(1, "valid.csv", &app).await;
+        let req = test::TestRequest::delete()
+            .uri("/portfolio/1/lots/2")
+            .to_request();
+        assert_eq!(204, test::call_service(&app, req).await.status().as_u16());
+
+        let mut expected = util::expected_valid_portfolio(1);
+        expected.remove_lot(2);
+        let resp = util::get_portfolio(1, &app).await;
+        assert_eq!(&expected, &resp);
+
+        let req = test::TestRequest::delete()
+            .uri("/portfolio/1/lots/2")
+            .to_request();
+        assert_eq!(404, test::call_service(&app, req).await.status().as_u16());
+    }
 
     #[actix_web::test]
-    async fn test_portfolio_get_not_found() {
+    async fn test_versions() {
         let dao = util::init_dao().await;
         let app = test::init_service(App::new().configure(move |cfg| {
             test_config(cfg, dao);
         }))
         .await;
 
-        util::put_portfolio(1, "valid.csv", &app).await;
+        let csv = util::load_bytes("valid.csv");
+        let req = test::TestRequest::put()
+            .uri("/portfolio/1")
+            .append_header(("Content-Length", csv.len()))
+            .append_header(("Content-Disposition", "attachment; filename=\"valid.csv\""))
+            .set_payload(csv)
+            .to_request();
+        assert_eq!(200, test::call_service(&app, req).await.status().as_u16());
+        util::put_portfolio(1, "valid_2.csv", &app).await;
 
-        // assert the state
-        let get_request = test::TestRequest::get().uri("/portfolio/2").to_request();
-        let get_response = test::call_service(&app, get_request).await;
-        assert_eq!(404, get_response.status().as_u16());
+        let req = test::TestRequest::get()
+            .uri("/portfolio/1/versions")
+            .to_request();
+        let versions: Vec<PortfolioVersionInfo> = test::call_and_read_body_json(&app, req).await;
+        let summary: Vec<(u32, Option<&str>, usize)> = versions
+            .iter()
+            .map(|v| (v.version, v.source.as_deref(), v.num_lots))
+            .collect();
+        assert_eq!(vec![(2, None, 2), (1, Some("valid.csv"), 3)], summary);
+
+        let req = test::TestRequest::get()
+            .uri("/portfolio/1/versions/1")
+            .to_request();
+        let version: PortfolioVersion = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(util::expected_valid_portfolio(1).lots, version.lots);
+
+        let req = test::TestRequest::get()
+            .uri("/portfolio/1/versions/1/diff/2")
+            .to_request();
+        let diff: PortfolioDiff = test::call_and_read_body_json(&app, req).await;
+        let expected_diff = PortfolioDiff::between(
+            &util::expected_valid_portfolio(1).lots,
+            &util::expected_valid_2_portfolio(1).lots,
+        );
+        assert_eq!(expected_diff, diff);
+
+        assert_eq!(404, util::get_status("/portfolio/1/versions/3", &app).await);
+        assert_eq!(404, util::get_status("/portfolio/2/versions", &app).await);
     }
 
     #[actix_web::test]
-    async fn test_portfolio_put_with_valid_then_get() {
+    async fn test_versions_restore() {
         let dao = util::init_dao().await;
         let app = test::init_service(App::new().configure(move |cfg| {
             test_config(cfg, dao);
@@ -29,12 +1024,42 @@ mod tests {
         .await;
 
         util::put_portfolio(1, "valid.csv", &app).await;
-        let resp = util::get_portfolio(1, &app).await;
-        assert_eq!(&util::expected_valid_portfolio(1), &resp);
+        util::put_portfolio(1, "valid_2.csv", &app).await;
+
+        let req = test::TestRequest::post()
+            .uri("/portfolio/1/versions/1/restore")
+            .append_header(("If-Match", "\"1\""))
+            .to_request();
+        assert_eq!(412, test::call_service(&app, req).await.status().as_u16());
+
+        let req = test::TestRequest::post()
+            .uri("/portfolio/1/versions/1/restore")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(200, resp.status().as_u16());
+        assert_eq!(Some("\"3\""), util::etag(&resp).as_deref());
+
+        let mut expected = util::expected_valid_portfolio(1);
+        // the ids of lots are not reused
+        expected.next_lot_id = 4;
+        assert_eq!(&expected, &util::get_portfolio(1, &app).await);
+
+        let req = test::TestRequest::get()
+            .uri("/portfolio/1/versions?limit=1")
+            .to_request();
+        let versions: Vec<PortfolioVersionInfo> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(1, versions.len());
+        assert_eq!(3, versions[0].version);
+        assert_eq!(Some(1), versions[0].restored_from);
+
+        let req = test::TestRequest::post()
+            .uri("/portfolio/1/versions/7/restore")
+            .to_request();
+        assert_eq!(404, test::call_service(&app, req).await.status().as_u16());
     }
 
     #[actix_web::test]
-    async fn test_portfolio_update_existing() {
+    async fn test_versions_of_deleted_portfolio() {
         let dao = util::init_dao().await;
         let app = test::init_service(App::new().configure(move |cfg| {
             test_config(cfg, dao);
@@ -42,29 +1067,549 @@ mod tests {
         .await;
 
         util::put_portfolio(1, "valid.csv", &app).await;
-        let resp = util::get_portfolio(1, &app).await;
-        assert_eq!(&util::expected_valid_portfolio(1), &resp);
+        util::put_portfolio(1, "valid_2.csv", &app).await;
+        assert_eq!(204, util::delete_portfolio(1, &app).await);
 
         util::put_portfolio(1, "valid_2.csv", &app).await;
-        let resp = util::get_portfolio(1, &app).await;
-        assert_eq!(&util::expected_valid_2_portfolio(1), &resp);
+        let req = test::TestRequest::get()
+            .uri("/portfolio/1/versions")
+            .to_request();
+        let versions: Vec<PortfolioVersionInfo> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(1, versions.len());
+        assert_eq!(2, versions[0].num_lots);
     }
 
     #[actix_web::test]
-    async fn test_portfolio_put_multiple() {
+    async fn test_summary_by_symbol() {
+        let state = util::init_state().await;
+        let app_state = state.clone();
+        let app = test::init_service(App::new().configure(move |cfg| {
+            service::config(cfg, &app_state);
+        }))
+        .await;
+
+        let uri = "/portfolio/1/summary/symbol";
+        assert_eq!(404, util::get_status(uri, &app).await);
+
+        util::put_portfolio(1, "valid.csv", &app).await;
+        assert_eq!(202, util::get_status(uri, &app).await);
+
+        let expected = util::expected_valid_portfolio(1)
+            .get_summary_by_symbol()
+            .unwrap();
+        state.dao.put_summary_by_symbol(&expected).await.unwrap();
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp: PortfolioSummary<String> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(expected, resp);
+    }
+
+    #[actix_web::test]
+    async fn test_summary_by_asset_class() {
+        let state = util::init_state().await;
+        let app_state = state.clone();
+        let app = test::init_service(App::new().configure(move |cfg| {
+            service::config(cfg, &app_state);
+        }))
+        .await;
+
+        let uri = "/portfolio/1/summary/asset_class";
+        assert_eq!(404, util::get_status(uri, &app).await);
+
+        util::put_portfolio(1, "valid.csv", &app).await;
+        assert_eq!(202, util::get_status(uri, &app).await);
+
+        let by_asset_class = |lot: &Lot| match &lot.symbol[..] {
+            "VOO" => AssetClass::UsStocks,
+            _ => AssetClass::UsBonds,
+        };
+        let expected = util::expected_valid_portfolio(1)
+            .get_summary_by(by_asset_class)
+            .unwrap();
+        state
+            .dao
+            .put_summary_by_asset_class(&expected)
+            .await
+            .unwrap();
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp: PortfolioSummary<AssetClass> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(expected, resp);
+    }
+
+    #[actix_web::test]
+    async fn test_summary_by_holding_period() {
+        let state = util::init_state().await;
+        let app_state = state.clone();
+        let app = test::init_service(App::new().configure(move |cfg| {
+            service::config(cfg, &app_state);
+        }))
+        .await;
+
+        let uri = "/portfolio/1/summary/holding_period";
+        assert_eq!(404, util::get_status(uri, &app).await);
+        assert_eq!(
+            404,
+            util::get_status(&format!("{uri}?date=2024-03-29"), &app).await
+        );
+
+        util::put_portfolio(1, "valid.csv", &app).await;
+        assert_eq!(202, util::get_status(uri, &app).await);
+
+        let portfolio = util::expected_valid_portfolio(1);
+        let today = Utc::now().date_naive();
+        let expected = portfolio.get_summary_by_holding_period(today).unwrap();
+        state
+            .dao
+            .put_summary_by_holding_period(&expected)
+            .await
+            .unwrap();
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp: PortfolioSummary<HoldingPeriod> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(expected, resp);
+
+        // a summary stored as of an earlier date is recomputed as of today
+        let stale = portfolio
+            .get_summary_by_holding_period(util::date(2023, 3, 30))
+            .unwrap();
+        state
+            .dao
+            .put_summary_by_holding_period(&stale)
+            .await
+            .unwrap();
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp: PortfolioSummary<HoldingPeriod> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(expected, resp);
+        assert_eq!(Some(today), resp.as_of);
+
+        // computed upon request, where the lot acquired on 2023-03-29 is not yet long-term
+        let req = test::TestRequest::get()
+            .uri(&format!("{uri}?as_of=2024-03-29"))
+            .to_request();
+        let resp: PortfolioSummary<HoldingPeriod> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            portfolio
+                .get_summary_by_holding_period(util::date(2024, 3, 29))
+                .unwrap(),
+            resp
+        );
+        assert_eq!(2, resp.group_to_summary.len());
+        // as was the parameter formerly named date
+        let req = test::TestRequest::get()
+            .uri(&format!("{uri}?date=2024-03-29"))
+            .to_request();
+        let former: PortfolioSummary<HoldingPeriod> =
+            test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp, former);
+    }
+
+    #[actix_web::test]
+    async fn test_summary_by_acquisition_year() {
+        let state = util::init_state().await;
+        let app_state = state.clone();
+        let app = test::init_service(App::new().configure(move |cfg| {
+            service::config(cfg, &app_state);
+        }))
+        .await;
+
+        let uri = "/portfolio/1/summary/acquisition_year";
+        assert_eq!(404, util::get_status(uri, &app).await);
+
+        util::put_portfolio(1, "valid.csv", &app).await;
+        assert_eq!(202, util::get_status(uri, &app).await);
+
+        let expected = util::expected_valid_portfolio(1)
+            .get_summary_by_acquisition_year()
+            .unwrap();
+        state
+            .dao
+            .put_summary_by_acquisition_year(&expected)
+            .await
+            .unwrap();
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp: PortfolioSummary<AcquisitionYear> =
+            test::call_and_read_body_json(&app, req).await;
+        assert_eq!(expected, resp);
+        assert!(resp.group_to_summary.contains_key(&AcquisitionYear(2023)));
+    }
+
+    #[actix_web::test]
+    async fn test_fx_rates() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        let resp = util::post_csv("/fx_rates", "fx_rates_with_unknown_currency.csv", &app).await;
+        assert_eq!(400, resp.status().as_u16());
+        let body: ErrorResponse = test::read_body_json(resp).await;
+        assert_eq!(1, body.errors.len());
+        assert_eq!("unknown_currency", body.errors[0].code);
+        assert_eq!(Some(2), body.errors[0].row);
+
+        let resp = util::post_csv("/fx_rates", "fx_rates.csv", &app).await;
+        assert_eq!(204, resp.status().as_u16());
+        // the same rates again replace, rather than duplicate, the stored ones
+        let resp = util::post_csv("/fx_rates", "fx_rates.csv", &app).await;
+        assert_eq!(204, resp.status().as_u16());
+
+        let req = test::TestRequest::get().uri("/fx_rates").to_request();
+        let rates: Vec<FxRate> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            vec![
+                FxRate::new(
+                    "EUR",
+                    "USD",
+                    util::date(2023, 6, 1),
+                    "1.25".parse().unwrap()
+                )
+                .unwrap(),
+                FxRate::new("USD", "EUR", util::date(2023, 3, 1), "0.9".parse().unwrap()).unwrap(),
+            ],
+            rates
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_fx_rates_exceeding_max_file_size() {
+        let dao = util::init_dao().await;
+        let limits = Limits {
+            fx_rates: UploadLimits {
+                max_file_size: 10,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let app = test::init_service(App::new().configure(move |cfg| {
+            util::test_config_with_limits(cfg, dao, limits);
+        }))
+        .await;
+
+        let resp = util::post_csv("/fx_rates", "fx_rates.csv", &app).await;
+        assert_eq!(413, resp.status().as_u16());
+        // the limits of each type of upload are independent of one another
+        let resp = util::put_csv("/portfolio/1", "valid.csv", &app).await;
+        assert_eq!(200, resp.status().as_u16());
+    }
+
+    #[actix_web::test]
+    async fn test_summary_by_symbol_in_currency() {
         let dao = util::init_dao().await;
         let app = test::init_service(App::new().configure(move |cfg| {
             test_config(cfg, dao);
         }))
         .await;
 
+        let uri = "/portfolio/1/summary/symbol?currency=EUR";
+        assert_eq!(404, util::get_status(uri, &app).await);
         util::put_portfolio(1, "valid.csv", &app).await;
-        util::put_portfolio(2, "valid_2.csv", &app).await;
-        let resp_1 = util::get_portfolio(1, &app).await;
-        assert_eq!(&util::expected_valid_portfolio(1), &resp_1);
+        assert_eq!(422, util::get_status(uri, &app).await);
+        let resp = util::post_csv("/fx_rates", "fx_rates.csv", &app).await;
+        assert_eq!(204, resp.status().as_u16());
 
-        let resp = util::get_portfolio(2, &app).await;
-        assert_eq!(&util::expected_valid_2_portfolio(2), &resp);
+        let eur = |amount: &str| {
+            GroupSummary::new(vec![Currency::new(amount.parse().unwrap(), "EUR").unwrap()]).unwrap()
+        };
+
+        // converted as of when each lot was acquired
+        let req = test::TestRequest::get()
+            .uri("/portfolio/1/summary/symbol?currency=eur")
+            .to_request();
+        let resp: PortfolioSummary<String> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            HashMap::from([
+                ("VOO".to_string(), eur("90.423")),
+                ("BND".to_string(), eur("1171.089"))
+            ]),
+            resp.group_to_summary
+        );
+
+        // converted as of the given date, by the inverse of the more recent rate
+        let req = test::TestRequest::get()
+            .uri("/portfolio/1/summary/symbol?currency=EUR&date=2023-06-30")
+            .to_request();
+        let resp: PortfolioSummary<String> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            HashMap::from([
+                ("VOO".to_string(), eur("80.376")),
+                ("BND".to_string(), eur("1040.968"))
+            ]),
+            resp.group_to_summary
+        );
+
+        let resp = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri("/portfolio/1/summary/symbol?currency=JPY")
+                .to_request(),
+        )
+        .await;
+        assert_eq!(422, resp.status().as_u16());
+        let body: ErrorResponse = test::read_body_json(resp).await;
+        assert_eq!("missing_fx_rate", body.errors[0].code);
+        assert_eq!(
+            400,
+            util::get_status("/portfolio/1/summary/symbol?currency=XYZ", &app).await
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_transactions() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        let uri = "/portfolio/1/transactions";
+        assert_eq!(404, util::get_status(uri, &app).await);
+        let resp = util::put_csv(uri, "transactions_selling_too_many.csv", &app).await;
+        assert_eq!(422, resp.status().as_u16());
+        let body: ErrorResponse = test::read_body_json(resp).await;
+        assert_eq!("insufficient_quantity", body.errors[0].code);
+        assert_eq!(Some(3), body.errors[0].row);
+        let resp = util::put_csv(uri, "transactions_in_different_currencies.csv", &app).await;
+        assert_eq!(422, resp.status().as_u16());
+        let body: ErrorResponse = test::read_body_json(resp).await;
+        assert_eq!("currency_mismatch", body.errors[0].code);
+        assert_eq!(Some(2), body.errors[0].row);
+        // the lot acquired by row 2 is held by another account
+        let resp = util::put_csv(uri, "transactions_selling_lot_not_held.csv", &app).await;
+        assert_eq!(422, resp.status().as_u16());
+        let body: ErrorResponse = test::read_body_json(resp).await;
+        assert_eq!("lot_not_found", body.errors[0].code);
+        assert_eq!(Some(3), body.errors[0].row);
+        assert_eq!(404, util::get_status("/portfolio/1", &app).await);
+
+        let resp = util::put_csv(uri, "transactions.csv", &app).await;
+        assert_eq!(200, resp.status().as_u16());
+        assert_eq!(Some("\"1\"".to_string()), util::etag(&resp));
+
+        // the lots are those remaining open, ordered by account and then by symbol, where each is
+        // identified by the row of the transaction that acquired it
+        let lot = |id, account, symbol, date, quantity, price| {
+            let mut lot = util::new_lot(account, symbol, date, quantity, price);
+            lot.id = Some(id);
+            lot
+        };
+        let mut expected = Portfolio::new(1, Vec::new());
+        expected.lots = vec![
+            lot(3, "IRA", "BND", "2023/03/20", 10, 72.5),
+            lot(1, "Taxable", "VOO", "2023/03/01", 1, 300.0),
+            lot(2, "Taxable", "VOO", "2023/03/15", 1, 310.0),
+        ];
+        expected.next_lot_id = 4;
+        assert_eq!(expected, util::get_portfolio(1, &app).await);
+
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let transactions: Vec<Transaction> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(4, transactions.len());
+        assert_eq!(TransactionKind::DividendReinvestment, transactions[1].kind);
+
+        let req = test::TestRequest::get()
+            .uri("/portfolio/1/transactions/realized_gains")
+            .to_request();
+        let gains: Vec<RealizedGain> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(1, gains.len());
+        assert_eq!(Decimal::from(4), gains[0].quantity);
+        assert_eq!(
+            Currency::new(Decimal::from(80), "USD").unwrap(),
+            gains[0].gain
+        );
+        assert_eq!(HoldingPeriod::ShortTerm, gains[0].holding_period);
+    }
+
+    #[actix_web::test]
+    async fn test_transactions_cleared_when_lots_replaced() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        let uri = "/portfolio/1/transactions";
+        let resp = util::put_csv(uri, "transactions.csv", &app).await;
+        assert_eq!(200, resp.status().as_u16());
+        assert_eq!(200, util::get_status(uri, &app).await);
+
+        // the lots are no longer derived from the transactions
+        util::put_portfolio(1, "valid.csv", &app).await;
+        assert_eq!(404, util::get_status(uri, &app).await);
+        assert_eq!(
+            404,
+            util::get_status(&format!("{uri}/realized_gains"), &app).await
+        );
+        assert_eq!(
+            404,
+            util::get_status(&format!("{uri}/wash_sales"), &app).await
+        );
+
+        // nor are they once a version derived from them is restored
+        let resp = util::put_csv(uri, "transactions.csv", &app).await;
+        assert_eq!(200, resp.status().as_u16());
+        let req = test::TestRequest::post()
+            .uri("/portfolio/1/versions/1/restore")
+            .to_request();
+        assert_eq!(200, test::call_service(&app, req).await.status().as_u16());
+        assert_eq!(404, util::get_status(uri, &app).await);
+
+        // nor once a lot is added, updated or removed
+        let resp = util::put_csv(uri, "transactions.csv", &app).await;
+        assert_eq!(200, resp.status().as_u16());
+        let resp = util::post_csv("/portfolio/1/lots", "valid_2.csv", &app).await;
+        assert_eq!(200, resp.status().as_u16());
+        assert_eq!(404, util::get_status(uri, &app).await);
+        let resp = util::put_csv(uri, "transactions.csv", &app).await;
+        assert_eq!(200, resp.status().as_u16());
+        let req = test::TestRequest::delete()
+            .uri("/portfolio/1/lots/1")
+            .to_request();
+        assert_eq!(204, test::call_service(&app, req).await.status().as_u16());
+        assert_eq!(404, util::get_status(uri, &app).await);
+    }
+
+    #[actix_web::test]
+    async fn test_wash_sales() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        let uri = "/portfolio/1/transactions/wash_sales";
+        assert_eq!(404, util::get_status(uri, &app).await);
+        let resp = util::put_csv(
+            "/portfolio/1/transactions",
+            "transactions_with_wash_sale.csv",
+            &app,
+        )
+        .await;
+        assert_eq!(200, resp.status().as_u16());
+
+        // the shares bought within the IRA replace two of the five sold at a loss
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let wash_sales: Vec<WashSale> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(1, wash_sales.len());
+        assert_eq!(Decimal::TWO, wash_sales[0].quantity);
+        let usd = |amount: i64| Currency::new(Decimal::from(amount), "USD").unwrap();
+        assert_eq!(usd(100), wash_sales[0].disallowed_loss);
+        let replacements = &wash_sales[0].replacements;
+        assert_eq!(1, replacements.len());
+        assert_eq!(2, replacements[0].index);
+        assert_eq!("IRA", replacements[0].account);
+        assert_eq!(usd(50), replacements[0].cost_basis_adjustment);
+    }
+
+    #[actix_web::test]
+    async fn test_corporate_actions() {
+        let dao = util::init_dao().await;
+        let app = test::init_service(App::new().configure(move |cfg| {
+            test_config(cfg, dao);
+        }))
+        .await;
+
+        let resp = util::put_csv("/portfolio/1", "valid.csv", &app).await;
+        assert_eq!(200, resp.status().as_u16());
+        let resp = util::put_csv("/portfolio/2", "valid_2.csv", &app).await;
+        assert_eq!(200, resp.status().as_u16());
+
+        // nothing is stored if an action fails to apply
+        let uri = "/corporate_actions";
+        let resp = util::post_csv(uri, "corporate_actions_in_different_currencies.csv", &app).await;
+        assert_eq!(422, resp.status().as_u16());
+        let body: ErrorResponse = test::read_body_json(resp).await;
+        assert_eq!("currency_mismatch", body.errors[0].code);
+        assert_eq!(Some(1), body.errors[0].row);
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let actions: Vec<CorporateAction> = test::call_and_read_body_json(&app, req).await;
+        assert!(actions.is_empty());
+        assert_eq!(
+            util::expected_valid_portfolio(1),
+            util::get_portfolio(1, &app).await
+        );
+
+        let resp = util::post_csv(uri, "corporate_actions.csv", &app).await;
+        assert_eq!(200, resp.status().as_u16());
+        let applied: Vec<AppliedCorporateActions> = test::read_body_json(resp).await;
+        assert_eq!(
+            vec![(1, 2), (2, 2)],
+            applied
+                .iter()
+                .map(|applied| (applied.portfolio_id, applied.version))
+                .collect::<Vec<_>>()
+        );
+        assert!(applied
+            .iter()
+            .all(|applied| applied.realized_gains.is_empty()));
+
+        // only the lot acquired before the split is split, and each portfolio records the actions
+        // that were applied to it
+        let action_id = |symbol: &str, effective_date| CorporateActionId {
+            symbol: symbol.into(),
+            effective_date,
+        };
+        let mut expected = util::expected_valid_portfolio(1);
+        expected.lots[1].quantity = Decimal::from(4);
+        expected.lots[1].cost_basis = Currency::new("100.13".parse().unwrap(), "USD").unwrap();
+        expected.corporate_actions = vec![action_id("BND", util::date(2023, 3, 29))];
+        assert_eq!(expected, util::get_portfolio(1, &app).await);
+        let mut expected = util::expected_valid_2_portfolio(2);
+        expected.lots[1].symbol = "VTIX".into();
+        expected.corporate_actions = vec![action_id("VTI", util::date(2023, 4, 1))];
+        assert_eq!(expected, util::get_portfolio(2, &app).await);
+
+        // the actions are only applied once
+        let resp = util::post_csv(uri, "corporate_actions.csv", &app).await;
+        assert_eq!(200, resp.status().as_u16());
+        let applied: Vec<AppliedCorporateActions> = test::read_body_json(resp).await;
+        assert!(applied.is_empty());
+        assert_eq!(expected, util::get_portfolio(2, &app).await);
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let actions: Vec<CorporateAction> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            vec!["BND", "VTI"],
+            actions
+                .iter()
+                .map(|action| action.symbol.as_str())
+                .collect::<Vec<_>>()
+        );
+
+        // transactions are replayed along with the stored actions, so the shares bought prior to
+        // the split are split
+        let resp = util::put_csv("/portfolio/3/transactions", "transactions.csv", &app).await;
+        assert_eq!(200, resp.status().as_u16());
+        let portfolio = util::get_portfolio(3, &app).await;
+        assert_eq!("BND", portfolio.lots[0].symbol);
+        assert_eq!(Decimal::from(20), portfolio.lots[0].quantity);
+        assert_eq!(
+            Currency::new("36.25".parse().unwrap(), "USD").unwrap(),
+            portfolio.lots[0].cost_basis
+        );
+        let all_actions = vec![
+            action_id("BND", util::date(2023, 3, 29)),
+            action_id("VTI", util::date(2023, 4, 1)),
+        ];
+        assert_eq!(all_actions, portfolio.corporate_actions);
+
+        // whereas uploaded lots are as currently held, so already reflect the stored actions
+        let resp = util::put_csv("/portfolio/4", "valid.csv", &app).await;
+        assert_eq!(200, resp.status().as_u16());
+        let mut expected = util::expected_valid_portfolio(4);
+        expected.corporate_actions = all_actions;
+        assert_eq!(expected, util::get_portfolio(4, &app).await);
+        let resp = util::post_csv(uri, "corporate_actions.csv", &app).await;
+        let applied: Vec<AppliedCorporateActions> = test::read_body_json(resp).await;
+        assert!(applied.is_empty());
+
+        // an action that was already applied may not be changed
+        let resp = util::post_csv(uri, "corporate_actions_changed.csv", &app).await;
+        assert_eq!(422, resp.status().as_u16());
+        let body: ErrorResponse = test::read_body_json(resp).await;
+        assert_eq!(1, body.errors.len());
+        assert_eq!("corporate_action_changed", body.errors[0].code);
+        assert_eq!(Some(2), body.errors[0].row);
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let actions: Vec<CorporateAction> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(Decimal::TWO, actions[0].ratio);
     }
 }
 
@@ -72,9 +1617,14 @@ mod util {
     use actix_http::Request;
     use actix_web::dev::{Service, ServiceResponse};
     use actix_web::test;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
     use std::env::VarError;
+    use std::io::{Cursor, Write};
     use std::path::PathBuf;
     use test_util::resource;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
 
     use actix_web::web::{Bytes, Data, ServiceConfig};
     use chrono::NaiveDate;
@@ -82,13 +1632,13 @@ mod util {
     use portools_common::dao::local::InMemoryDao;
     use portools_common::dao::mongo::MongoDao;
     use portools_common::dao::{mongo, Dao};
-    use portools_common::model::{Currency, Lot, Portfolio};
+    use portools_common::model::{Currency, Lot, LotSpec, Portfolio};
+    use portools_service::config::Limits;
     use portools_service::service;
     use portools_service::service::state::State;
     use rust_decimal::Decimal;
-    use tracing;
 
-    const DATE_FORMAT: &'static str = "%Y/%m/%d";
+    const DATE_FORMAT: &str = "%Y/%m/%d";
 
     pub async fn put_portfolio(
         id: u32,
@@ -96,14 +1646,121 @@ mod util {
         app: &(impl Service<Request, Response = ServiceResponse, Error = actix_web::error::Error>
               + Sized),
     ) {
+        let put_response = put_csv(&format!("/portfolio/{id}"), csv_file, app).await;
+        assert_eq!(200, put_response.status().as_u16());
+    }
+
+    pub async fn put_csv(
+        uri: &str,
+        csv_file: &str,
+        app: &(impl Service<Request, Response = ServiceResponse, Error = actix_web::error::Error>
+              + Sized),
+    ) -> ServiceResponse {
         let csv = load_bytes(csv_file);
         let put_request = test::TestRequest::put()
-            .uri(&format!("/portfolio/{id}"))
+            .uri(uri)
             .append_header(("Content-Length", csv.len()))
             .set_payload(csv)
             .to_request();
-        let put_response = test::call_service(&app, put_request).await;
-        assert_eq!(200, put_response.status().as_u16());
+        test::call_service(&app, put_request).await
+    }
+
+    pub async fn post_csv(
+        uri: &str,
+        csv_file: &str,
+        app: &(impl Service<Request, Response = ServiceResponse, Error = actix_web::error::Error>
+              + Sized),
+    ) -> ServiceResponse {
+        let csv = load_bytes(csv_file);
+        let req = test::TestRequest::post()
+            .uri(uri)
+            .append_header(("Content-Length", csv.len()))
+            .set_payload(csv)
+            .to_request();
+        test::call_service(&app, req).await
+    }
+
+    pub async fn post_json(
+        uri: &str,
+        specs: &[LotSpec],
+        app: &(impl Service<Request, Response = ServiceResponse, Error = actix_web::error::Error>
+              + Sized),
+    ) -> ServiceResponse {
+        send_json(test::TestRequest::post().uri(uri), specs, app).await
+    }
+
+    pub async fn put_json(
+        uri: &str,
+        specs: &[LotSpec],
+        app: &(impl Service<Request, Response = ServiceResponse, Error = actix_web::error::Error>
+              + Sized),
+    ) -> ServiceResponse {
+        send_json(test::TestRequest::put().uri(uri), specs, app).await
+    }
+
+    async fn send_json(
+        req: test::TestRequest,
+        specs: &[LotSpec],
+        app: &(impl Service<Request, Response = ServiceResponse, Error = actix_web::error::Error>
+              + Sized),
+    ) -> ServiceResponse {
+        let json = serde_json::to_vec(specs).unwrap();
+        let req = req
+            .append_header(("Content-Type", "application/json"))
+            .append_header(("Content-Length", json.len()))
+            .set_payload(json)
+            .to_request();
+        test::call_service(&app, req).await
+    }
+
+    pub fn to_specs(lots: &[Lot]) -> Vec<LotSpec> {
+        lots.iter()
+            .map(|lot| LotSpec {
+                account: lot.account.clone(),
+                symbol: lot.symbol.clone(),
+                date_acquired: lot.date_acquired,
+                quantity: lot.quantity,
+                cost_basis: lot.cost_basis.clone(),
+            })
+            .collect()
+    }
+
+    pub async fn put_zip(
+        uri: &str,
+        archive: Vec<u8>,
+        app: &(impl Service<Request, Response = ServiceResponse, Error = actix_web::error::Error>
+              + Sized),
+    ) -> ServiceResponse {
+        let req = test::TestRequest::put()
+            .uri(uri)
+            .append_header(("Content-Type", "application/zip"))
+            .set_payload(archive)
+            .to_request();
+        test::call_service(&app, req).await
+    }
+
+    pub fn gzip(content: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(content).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    // an archive of the given resources
+    pub fn zip(resources: &[&str]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for resource in resources {
+            writer
+                .start_file(*resource, FileOptions::default())
+                .unwrap();
+            writer.write_all(&load_bytes(resource)).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    pub fn etag(resp: &ServiceResponse) -> Option<String> {
+        resp.headers()
+            .get("ETag")
+            .map(|etag| etag.to_str().unwrap().to_string())
     }
 
     pub async fn get_portfolio(
@@ -115,7 +1772,44 @@ mod util {
             .uri(&format!("/portfolio/{id}"))
             .to_request();
         let resp: Portfolio = test::call_and_read_body_json(&app, req).await;
-        resp
+        // clear the values recorded upon each modification, which the expected portfolios do not
+        // account for
+        assert!(resp.last_updated.is_some());
+        assert!(resp.version > 0);
+        Portfolio {
+            version: 0,
+            last_updated: None,
+            ..resp
+        }
+    }
+
+    pub async fn delete_portfolio(
+        id: u32,
+        app: &(impl Service<Request, Response = ServiceResponse, Error = actix_web::error::Error>
+              + Sized),
+    ) -> u16 {
+        let req = test::TestRequest::delete()
+            .uri(&format!("/portfolio/{id}"))
+            .to_request();
+        test::call_service(&app, req).await.status().as_u16()
+    }
+
+    pub async fn get_status(
+        uri: &str,
+        app: &(impl Service<Request, Response = ServiceResponse, Error = actix_web::error::Error>
+              + Sized),
+    ) -> u16 {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        test::call_service(&app, req).await.status().as_u16()
+    }
+
+    pub async fn init_state() -> Data<State> {
+        Data::new(State {
+            limits: Default::default(),
+            import_profiles: Default::default(),
+            wash_sales: Default::default(),
+            dao: init_dao().await,
+        })
     }
 
     pub async fn init_dao() -> Box<dyn Dao> {
@@ -141,30 +1835,40 @@ mod util {
         }
     }
 
+    pub fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
     pub fn expected_valid_portfolio(id: u32) -> Portfolio {
-        Portfolio {
+        Portfolio::new(
             id,
-            lots: vec![
+            vec![
                 new_lot("Taxable", "VOO", "2023/03/27", 1, 100.47),
                 new_lot("IRA", "BND", "2023/03/28", 2, 200.26),
                 new_lot("IRA", "BND", "2023/03/29", 3, 300.23),
             ],
-        }
+        )
     }
 
     pub fn expected_valid_2_portfolio(id: u32) -> Portfolio {
-        Portfolio {
+        Portfolio::new(
             id,
-            lots: vec![
+            vec![
                 new_lot("Taxable", "VTEB", "2023/03/27", 5, 55.55),
                 new_lot("IRA", "VTI", "2023/03/28", 4, 222.22),
             ],
-        }
+        )
     }
 
     pub fn test_config(cfg: &mut ServiceConfig, dao: Box<dyn Dao>) {
+        test_config_with_limits(cfg, dao, Default::default());
+    }
+
+    pub fn test_config_with_limits(cfg: &mut ServiceConfig, dao: Box<dyn Dao>, limits: Limits) {
         let app_state = Data::new(State {
-            limits: Default::default(),
+            limits,
+            import_profiles: Default::default(),
+            wash_sales: Default::default(),
             dao,
         });
         service::config(cfg, &app_state);
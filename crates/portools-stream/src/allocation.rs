@@ -1,3 +1,4 @@
+use chrono::Utc;
 use mongodb::error::Error;
 use portools_common::dao::Dao;
 use portools_common::model::{AssetClass, Lot, Portfolio, PortfolioSummary, PortfolioSummaryError};
@@ -7,15 +8,33 @@ pub struct PortfolioSummaryManager {
 }
 
 impl PortfolioSummaryManager {
-
     // todo(): integration test coverage
 
-    pub async fn summarize(
-        &self,
-        portfolio: &Portfolio,
-    ) -> Result<(), AllocationServiceError> {
+    pub async fn summarize(&self, portfolio: &Portfolio) -> Result<(), AllocationServiceError> {
         self.summarize_by_asset_class(portfolio).await?;
-        self.summarize_by_symbol(portfolio).await
+        self.summarize_by_symbol(portfolio).await?;
+        self.summarize_by_holding_period(portfolio).await?;
+        self.summarize_by_acquisition_year(portfolio).await
+    }
+
+    pub async fn delete_summaries(&self, portfolio_id: u32) -> Result<(), AllocationServiceError> {
+        self.dao
+            .delete_summary_by_asset_class(portfolio_id)
+            .await
+            .map_err(|cause| AllocationServiceError::DataAccessError { cause })?;
+        self.dao
+            .delete_summary_by_symbol(portfolio_id)
+            .await
+            .map_err(|cause| AllocationServiceError::DataAccessError { cause })?;
+        self.dao
+            .delete_summary_by_holding_period(portfolio_id)
+            .await
+            .map_err(|cause| AllocationServiceError::DataAccessError { cause })?;
+        self.dao
+            .delete_summary_by_acquisition_year(portfolio_id)
+            .await
+            .map_err(|cause| AllocationServiceError::DataAccessError { cause })?;
+        Ok(())
     }
 
     pub async fn summarize_by_asset_class(
@@ -43,6 +62,36 @@ impl PortfolioSummaryManager {
             .await
             .map_err(|cause| AllocationServiceError::DataAccessError { cause })
     }
+
+    // The holding periods are as of when the portfolio was last modified, which is recorded as the
+    // as_of date of the summary. portools-service recomputes the summary when it is read on a later
+    // date, as lots may since have been held for over a year.
+    pub async fn summarize_by_holding_period(
+        &self,
+        portfolio: &Portfolio,
+    ) -> Result<(), AllocationServiceError> {
+        let as_of = portfolio.last_updated.unwrap_or_else(Utc::now).date_naive();
+        let summary = portfolio
+            .get_summary_by_holding_period(as_of)
+            .map_err(|cause| AllocationServiceError::PortfolioSummaryError { cause })?;
+        self.dao
+            .put_summary_by_holding_period(&summary)
+            .await
+            .map_err(|cause| AllocationServiceError::DataAccessError { cause })
+    }
+
+    pub async fn summarize_by_acquisition_year(
+        &self,
+        portfolio: &Portfolio,
+    ) -> Result<(), AllocationServiceError> {
+        let summary = portfolio
+            .get_summary_by_acquisition_year()
+            .map_err(|cause| AllocationServiceError::PortfolioSummaryError { cause })?;
+        self.dao
+            .put_summary_by_acquisition_year(&summary)
+            .await
+            .map_err(|cause| AllocationServiceError::DataAccessError { cause })
+    }
 }
 
 pub fn get_summary_by_asset_class(
@@ -145,10 +194,7 @@ mod tests {
     fn get_summary_by_asset_class_with_one_lot() {
         let id = 1;
         let lot = lot_for_symbol("VOO");
-        let portfolio = Portfolio {
-            id,
-            lots: vec![lot.clone()],
-        };
+        let portfolio = Portfolio::new(id, vec![lot.clone()]);
 
         let allocation = get_summary_by_asset_class(&portfolio).unwrap();
         assert_eq!(
@@ -156,8 +202,9 @@ mod tests {
                 id,
                 group_to_summary: HashMap::from([(
                     AssetClass::UsStocks,
-                    GroupSummary::new(lot.get_total_cost().unwrap()).unwrap()
-                )])
+                    GroupSummary::new(vec![lot.get_total_cost().unwrap()]).unwrap()
+                )]),
+                as_of: None,
             },
             allocation
         )
@@ -183,25 +230,21 @@ mod tests {
         )
         .unwrap();
 
-        let portfolio = Portfolio {
-            id,
-            lots: vec![lot_1.clone(), lot_2.clone()],
-        };
+        let portfolio = Portfolio::new(id, vec![lot_1.clone(), lot_2.clone()]);
         let allocation = get_summary_by_asset_class(&portfolio).unwrap();
         assert_eq!(
             PortfolioSummary {
                 id,
                 group_to_summary: HashMap::from([(
                     AssetClass::UsStocks,
-                    GroupSummary::new(
-                        lot_1
-                            .get_total_cost()
-                            .unwrap()
-                            .add(&lot_2.get_total_cost().unwrap())
-                            .unwrap()
-                    )
+                    GroupSummary::new(vec![lot_1
+                        .get_total_cost()
+                        .unwrap()
+                        .add(&lot_2.get_total_cost().unwrap())
+                        .unwrap()])
                     .unwrap()
-                )])
+                )]),
+                as_of: None,
             },
             allocation
         )
@@ -227,25 +270,21 @@ mod tests {
         )
         .unwrap();
 
-        let portfolio = Portfolio {
-            id,
-            lots: vec![lot_1.clone(), lot_2.clone()],
-        };
+        let portfolio = Portfolio::new(id, vec![lot_1.clone(), lot_2.clone()]);
         let allocation = get_summary_by_asset_class(&portfolio).unwrap();
         assert_eq!(
             PortfolioSummary {
                 id,
                 group_to_summary: HashMap::from([(
                     AssetClass::UsStocks,
-                    GroupSummary::new(
-                        lot_1
-                            .get_total_cost()
-                            .unwrap()
-                            .add(&lot_2.get_total_cost().unwrap())
-                            .unwrap()
-                    )
+                    GroupSummary::new(vec![lot_1
+                        .get_total_cost()
+                        .unwrap()
+                        .add(&lot_2.get_total_cost().unwrap())
+                        .unwrap()])
                     .unwrap()
-                )])
+                )]),
+                as_of: None,
             },
             allocation
         )
@@ -271,10 +310,7 @@ mod tests {
         )
         .unwrap();
 
-        let portfolio = Portfolio {
-            id,
-            lots: vec![lot_1.clone(), lot_2.clone()],
-        };
+        let portfolio = Portfolio::new(id, vec![lot_1.clone(), lot_2.clone()]);
         let allocation = get_summary_by_asset_class(&portfolio).unwrap();
         assert_eq!(
             PortfolioSummary {
@@ -282,13 +318,14 @@ mod tests {
                 group_to_summary: HashMap::from([
                     (
                         AssetClass::UsStocks,
-                        GroupSummary::new(lot_1.get_total_cost().unwrap()).unwrap()
+                        GroupSummary::new(vec![lot_1.get_total_cost().unwrap()]).unwrap()
                     ),
                     (
                         AssetClass::UsBonds,
-                        GroupSummary::new(lot_2.get_total_cost().unwrap()).unwrap()
+                        GroupSummary::new(vec![lot_2.get_total_cost().unwrap()]).unwrap()
                     ),
-                ])
+                ]),
+                as_of: None,
             },
             allocation
         );
@@ -322,10 +359,7 @@ mod tests {
         )
         .unwrap();
 
-        let portfolio = Portfolio {
-            id,
-            lots: vec![lot_1.clone(), lot_2.clone(), lot_3.clone()],
-        };
+        let portfolio = Portfolio::new(id, vec![lot_1.clone(), lot_2.clone(), lot_3.clone()]);
         let allocation = get_summary_by_asset_class(&portfolio).unwrap();
         assert_eq!(
             PortfolioSummary {
@@ -333,20 +367,19 @@ mod tests {
                 group_to_summary: HashMap::from([
                     (
                         AssetClass::UsStocks,
-                        GroupSummary::new(
-                            lot_1
-                                .get_total_cost()
-                                .unwrap()
-                                .add(&lot_3.get_total_cost().unwrap())
-                                .unwrap()
-                        )
+                        GroupSummary::new(vec![lot_1
+                            .get_total_cost()
+                            .unwrap()
+                            .add(&lot_3.get_total_cost().unwrap())
+                            .unwrap()])
                         .unwrap()
                     ),
                     (
                         AssetClass::IntlStocks,
-                        GroupSummary::new(lot_2.get_total_cost().unwrap()).unwrap()
+                        GroupSummary::new(vec![lot_2.get_total_cost().unwrap()]).unwrap()
                     ),
-                ])
+                ]),
+                as_of: None,
             },
             allocation
         );
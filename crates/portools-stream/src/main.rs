@@ -1,12 +1,13 @@
 use allocation::PortfolioSummaryManager;
 use mongo_util::change_stream;
+use mongodb::bson::{Bson, Document};
 use mongodb::change_stream::event::{ChangeStreamEvent, OperationType};
 use mongodb::change_stream::ChangeStream;
 use mongodb::options::{
     ChangeStreamOptions, FullDocumentBeforeChangeType, FullDocumentType, ReadConcern,
 };
 use mongodb::Client;
-use portools_common::dao::mongo::{MongoDao, DB_NAME};
+use portools_common::dao::mongo::{MongoDao, DB_NAME, PORTFOLIO_KEY_FIELD};
 use portools_common::log;
 use portools_common::model::Portfolio;
 use portools_stream::allocation;
@@ -61,12 +62,9 @@ async fn consume_next_change_event(
 ) {
     // todo: add tracing span with at least portfolio id
     match event.operation_type {
-        OperationType::Insert | OperationType::Replace => {
+        OperationType::Insert | OperationType::Replace | OperationType::Update => {
             if let Some(ref portfolio) = event.full_document {
-                match allocation_service
-                    .summarize(portfolio)
-                    .await
-                {
+                match allocation_service.summarize(portfolio).await {
                     Err(error) => {
                         tracing::error!(?error, "failed to update portfolio summary by asset class")
                     }
@@ -74,13 +72,34 @@ async fn consume_next_change_event(
                 }
             }
         }
-        // todo: support delete (and other operation types?)
+        // the deleted portfolio is identified by the key of its document, so that a pre-image of
+        // the document is not required
+        OperationType::Delete => match event.document_key.as_ref().and_then(portfolio_id) {
+            Some(portfolio_id) => match allocation_service.delete_summaries(portfolio_id).await {
+                Err(error) => tracing::error!(?error, "failed to delete portfolio summaries"),
+                Ok(_) => tracing::info!("deleted portfolio summaries"),
+            },
+            None => tracing::warn!(
+                document_key = ?event.document_key,
+                "delete event is missing the id of the portfolio"
+            ),
+        },
         _ => {
             tracing::warn!(operation_type = ?event.operation_type, "unsupported operation type")
         }
     }
 }
 
+// Returns the id of the portfolio keyed by the document key, unless the portfolio was stored with
+// a generated key (i.e. prior to being keyed by id)
+fn portfolio_id(document_key: &Document) -> Option<u32> {
+    match document_key.get(PORTFOLIO_KEY_FIELD)? {
+        Bson::Int32(id) => u32::try_from(*id).ok(),
+        Bson::Int64(id) => u32::try_from(*id).ok(),
+        _ => None,
+    }
+}
+
 async fn init_change_stream(
     client: &Client,
 ) -> mongodb::error::Result<ChangeStream<ChangeStreamEvent<Portfolio>>> {
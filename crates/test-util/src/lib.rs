@@ -23,7 +23,7 @@ pub mod assertion {
         assert_eq!(expected, actual);
     }
 
-    pub fn assert_result_vec_eq_fn<T, E, F>(expected: &Vec<T>, result: &Result<Vec<T>, E>, eq: F)
+    pub fn assert_result_vec_eq_fn<T, E, F>(expected: &[T], result: &Result<Vec<T>, E>, eq: F)
     where
         T: Debug + PartialEq,
         E: Debug,
@@ -33,7 +33,7 @@ pub mod assertion {
         assert_vec_eq_fn(expected, actual, eq);
     }
 
-    pub fn assert_vec_eq_fn<T, F>(expected: &Vec<T>, actual: &Vec<T>, eq: F)
+    pub fn assert_vec_eq_fn<T, F>(expected: &[T], actual: &[T], eq: F)
     where
         T: Debug + PartialEq,
         F: Fn(&T, &T) -> bool,